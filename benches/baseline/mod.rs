@@ -0,0 +1,284 @@
+// The tokenizer as it was before tokens borrowed from the input: it copies
+// the input into a `Vec<char>` and builds a `String` for every token. Kept
+// only as the baseline the benchmark measures the current one against.
+
+use sqlfmt::dialect::Dialect;
+use sqlfmt::tokenizer::Token;
+
+const KEYWORDS: &[&str] = &[
+    "SELECT", "FROM", "WHERE", "JOIN", "LEFT", "RIGHT", "INNER", "OUTER",
+    "CROSS", "FULL", "ON", "AND", "OR", "NOT", "IN", "IS", "NULL", "AS",
+    "GROUP", "BY", "ORDER", "HAVING", "LIMIT", "OFFSET", "INSERT", "INTO",
+    "VALUES", "UPDATE", "SET", "DELETE", "CREATE", "TABLE", "DROP", "ALTER",
+    "INDEX", "VIEW", "UNION", "ALL", "DISTINCT", "EXCEPT", "INTERSECT",
+    "EXISTS", "BETWEEN", "LIKE", "CASE", "WHEN", "THEN", "ELSE", "END",
+    "ASC", "DESC", "TRUE", "FALSE", "CAST", "WITH", "RECURSIVE", "PRIMARY",
+    "KEY", "FOREIGN", "REFERENCES", "CONSTRAINT", "DEFAULT", "CHECK",
+    "UNIQUE", "IF", "REPLACE", "TEMPORARY", "TEMP", "RETURNING",
+    "NATURAL", "USING", "FETCH", "NEXT", "ROWS", "ONLY", "FIRST",
+    "NULLS", "LAST", "COUNT", "SUM", "AVG", "MIN", "MAX", "COALESCE",
+    "OVER", "PARTITION", "ROW_NUMBER", "RANK", "DENSE_RANK", "LAG", "LEAD",
+    "WINDOW", "RANGE", "UNBOUNDED", "PRECEDING", "FOLLOWING", "CURRENT",
+    "ROW", "GRANT", "REVOKE", "ROLLBACK", "COMMIT", "BEGIN", "TRANSACTION",
+    "SAVEPOINT", "RELEASE", "TRIGGER", "EXECUTE", "PROCEDURE", "FUNCTION",
+    "DECLARE", "CURSOR", "OPEN", "CLOSE",
+];
+
+fn is_keyword(word: &str, dialect: Dialect) -> bool {
+    let upper = word.to_uppercase();
+    KEYWORDS.contains(&upper.as_str()) || dialect.extra_keywords().contains(&upper.as_str())
+}
+
+// Scans a quoted run starting at `start` (the opening quote) up to and
+// including `close`, or to the end of input. Doubled closing quotes and, if
+// `backslash` is set, backslash escapes stay inside the run.
+fn scan_quoted(chars: &[char], start: usize, close: char, backslash: bool) -> usize {
+    let len = chars.len();
+    let mut i = start + 1;
+    while i < len {
+        if backslash && chars[i] == '\\' {
+            i += 2;
+        } else if chars[i] == close {
+            if i + 1 < len && chars[i + 1] == close {
+                i += 2; // escaped quote
+            } else {
+                return i + 1;
+            }
+        } else {
+            i += 1;
+        }
+    }
+    len
+}
+
+// Length of the `$$` / `$tag$` delimiter starting at `start`, if any. Tags
+// follow identifier rules, so `$1` is not a delimiter.
+fn dollar_tag_len(chars: &[char], start: usize) -> Option<usize> {
+    let mut i = start + 1;
+    if i < chars.len() && (chars[i].is_alphabetic() || chars[i] == '_') {
+        while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+            i += 1;
+        }
+    }
+    (i < chars.len() && chars[i] == '$').then_some(i + 1 - start)
+}
+
+// End of the dollar-quoted string opened by `tag` at `start`, or the end of
+// input if it is never closed.
+fn scan_dollar_quoted(chars: &[char], start: usize, tag: &[char]) -> usize {
+    let mut i = start + tag.len();
+    while i + tag.len() <= chars.len() {
+        if chars[i..i + tag.len()] == *tag {
+            return i + tag.len();
+        }
+        i += 1;
+    }
+    chars.len()
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+// Length of the bind parameter starting at `i`, if any.
+fn placeholder_len(chars: &[char], i: usize) -> Option<usize> {
+    let len = chars.len();
+    let next = chars.get(i + 1).copied();
+    let run = |from: usize, pred: fn(char) -> bool| {
+        let mut j = from;
+        while j < len && pred(chars[j]) {
+            j += 1;
+        }
+        j - i
+    };
+    match chars[i] {
+        // $1
+        '$' if next.is_some_and(|c| c.is_ascii_digit()) => Some(run(i + 1, |c| c.is_ascii_digit())),
+        // ? and ?1
+        '?' => Some(run(i + 1, |c| c.is_ascii_digit())),
+        // :name, but not the second colon of `::`
+        ':' if next.is_some_and(|c| c.is_alphabetic() || c == '_') && (i == 0 || chars[i - 1] != ':') => {
+            Some(run(i + 1, is_word_char))
+        }
+        // @param, @@global
+        '@' if next.is_some_and(|c| c.is_alphabetic() || c == '_' || c == '@') => {
+            let start = if next == Some('@') { i + 2 } else { i + 1 };
+            Some(run(start, is_word_char))
+        }
+        // %(name)s
+        '%' if next == Some('(') => {
+            let close = (i + 2..len).find(|&j| chars[j] == ')')?;
+            let name_ok = (i + 2..close).all(|j| is_word_char(chars[j]));
+            (name_ok && chars.get(close + 1).is_some_and(|c| c.is_ascii_alphabetic())).then_some(close + 2 - i)
+        }
+        _ => None,
+    }
+}
+
+pub fn tokenize_with(input: &str, dialect: Dialect) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let chars: Vec<char> = input.chars().collect();
+    let len = chars.len();
+    let mut i = 0;
+
+    while i < len {
+        let ch = chars[i];
+
+        // Whitespace
+        if ch.is_ascii_whitespace() {
+            let start = i;
+            while i < len && chars[i].is_ascii_whitespace() {
+                i += 1;
+            }
+            tokens.push(Token::Whitespace(chars[start..i].iter().collect()));
+            continue;
+        }
+
+        // Line comment (`#` in MySQL and BigQuery)
+        if (ch == '-' && i + 1 < len && chars[i + 1] == '-') || (ch == '#' && dialect.hash_comments()) {
+            let start = i;
+            while i < len && chars[i] != '\n' {
+                i += 1;
+            }
+            tokens.push(Token::Comment(chars[start..i].iter().collect()));
+            continue;
+        }
+
+        // Block comment
+        if ch == '/' && i + 1 < len && chars[i + 1] == '*' {
+            let start = i;
+            i += 2;
+            while i + 1 < len && !(chars[i] == '*' && chars[i + 1] == '/') {
+                i += 1;
+            }
+            // skip */, or run to the end of an unterminated comment
+            i = (i + 2).min(len);
+            tokens.push(Token::Comment(chars[start..i].iter().collect()));
+            continue;
+        }
+
+        // String literal
+        if ch == '\'' {
+            let start = i;
+            i = scan_quoted(&chars, i, '\'', dialect.backslash_escapes());
+            tokens.push(Token::StringLiteral(chars[start..i].iter().collect()));
+            continue;
+        }
+
+        // Dollar-quoted string (PostgreSQL function bodies)
+        if ch == '$' && dialect.dollar_quoted_strings() {
+            if let Some(tag_len) = dollar_tag_len(&chars, i) {
+                let start = i;
+                i = scan_dollar_quoted(&chars, i, &chars[i..i + tag_len]);
+                tokens.push(Token::StringLiteral(chars[start..i].iter().collect()));
+                continue;
+            }
+        }
+
+        // Quoted identifier (ANSI SQL, PostgreSQL); a string in MySQL and BigQuery
+        if ch == '"' {
+            let start = i;
+            if dialect.double_quote_is_identifier() {
+                i = scan_quoted(&chars, i, '"', false);
+                tokens.push(Token::Identifier(chars[start..i].iter().collect()));
+            } else {
+                i = scan_quoted(&chars, i, '"', dialect.backslash_escapes());
+                tokens.push(Token::StringLiteral(chars[start..i].iter().collect()));
+            }
+            continue;
+        }
+
+        // Backtick-quoted identifier (MySQL, MariaDB, SQLite, BigQuery)
+        if ch == '`' && dialect.backtick_identifiers() {
+            let start = i;
+            i = scan_quoted(&chars, i, '`', false);
+            tokens.push(Token::Identifier(chars[start..i].iter().collect()));
+            continue;
+        }
+
+        // Bracket-quoted identifier (MSSQL, T-SQL, SQLite)
+        if ch == '[' && dialect.bracket_identifiers() {
+            let start = i;
+            i += 1;
+            while i < len && chars[i] != ']' {
+                i += 1;
+            }
+            if i < len {
+                i += 1;
+            }
+            tokens.push(Token::Identifier(chars[start..i].iter().collect()));
+            continue;
+        }
+
+        // Number
+        if ch.is_ascii_digit() || (ch == '.' && i + 1 < len && chars[i + 1].is_ascii_digit()) {
+            let start = i;
+            while i < len && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            tokens.push(Token::NumberLiteral(chars[start..i].iter().collect()));
+            continue;
+        }
+
+        // Punctuation
+        match ch {
+            '(' => { tokens.push(Token::OpenParen); i += 1; continue; }
+            ')' => { tokens.push(Token::CloseParen); i += 1; continue; }
+            ',' => { tokens.push(Token::Comma); i += 1; continue; }
+            ';' => { tokens.push(Token::Semicolon); i += 1; continue; }
+            _ => {}
+        }
+
+        // Multi-char operators
+        if i + 1 < len {
+            let ahead: String = chars[i..(i + 3).min(len)].iter().collect();
+            if let Some(op) = dialect.operators().iter().find(|op| ahead.starts_with(*op)) {
+                tokens.push(Token::Operator(op.to_string()));
+                i += op.chars().count();
+                continue;
+            }
+        }
+
+        // Bind parameters
+        if let Some(n) = placeholder_len(&chars, i) {
+            tokens.push(Token::Placeholder(chars[i..i + n].iter().collect()));
+            i += n;
+            continue;
+        }
+
+        // Single-char operators
+        if matches!(ch, '=' | '<' | '>' | '+' | '-' | '*' | '/' | '%' | '&' | '|' | '^' | '~') {
+            tokens.push(Token::Operator(ch.to_string()));
+            i += 1;
+            continue;
+        }
+
+        // Word (keyword or identifier)
+        if ch.is_alphanumeric() || ch == '_' {
+            let start = i;
+            while i < len && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            if is_keyword(&word, dialect) {
+                tokens.push(Token::Keyword(word));
+            } else {
+                tokens.push(Token::Identifier(word));
+            }
+            continue;
+        }
+
+        // Dot
+        if ch == '.' {
+            tokens.push(Token::Operator(".".to_string()));
+            i += 1;
+            continue;
+        }
+
+        // Other
+        tokens.push(Token::Other(ch.to_string()));
+        i += 1;
+    }
+
+    tokens
+}
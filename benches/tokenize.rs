@@ -0,0 +1,85 @@
+// Throughput on a synthetic multi-megabyte schema dump:
+//
+//     cargo bench --bench tokenize
+//
+// Uses plain `Instant` timing so it runs on stable without extra crates.
+// `baseline` is the old `Vec<char>` tokenizer, for comparison.
+
+mod baseline;
+
+use sqlfmt::dialect::Dialect;
+use sqlfmt::formatter::{beautify, colorize_with, Palette};
+use sqlfmt::tokenizer::{tokenize_with, tokens_with};
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const TARGET_BYTES: usize = 4 << 20;
+
+fn schema_dump() -> String {
+    let mut out = String::with_capacity(TARGET_BYTES + 1024);
+    let mut n = 0;
+    while out.len() < TARGET_BYTES {
+        out.push_str(&format!(
+            "-- Table: public.orders_{n}\n\
+             CREATE TABLE public.orders_{n} (\n    \
+                 id bigint NOT NULL,\n    \
+                 customer_id integer REFERENCES customers (id),\n    \
+                 \"Total Amount\" numeric(12, 2) DEFAULT 0.00,\n    \
+                 note text DEFAULT 'n/a',\n    \
+                 created_at timestamp with time zone DEFAULT now()\n\
+             );\n\
+             INSERT INTO public.orders_{n} VALUES (1, 42, 19.99, 'it''s here', '2024-01-01'), (2, 7, 5.00, NULL, $1);\n\
+             /* {n} */ SELECT o.id, sum(o.total) FROM public.orders_{n} o WHERE o.id >= :min GROUP BY o.id;\n\n"
+        ));
+        n += 1;
+    }
+    out
+}
+
+// Runs `f` until about a second has passed and reports the best run.
+fn bench(name: &str, bytes: usize, mut f: impl FnMut()) -> Duration {
+    let mut best = Duration::MAX;
+    let started = Instant::now();
+    let mut runs = 0;
+    while runs < 3 || (started.elapsed() < Duration::from_secs(1) && runs < 50) {
+        let t = Instant::now();
+        f();
+        best = best.min(t.elapsed());
+        runs += 1;
+    }
+    let mb_per_s = bytes as f64 / (1 << 20) as f64 / best.as_secs_f64();
+    println!("{name:<28} {:>9.2} ms {mb_per_s:>9.1} MB/s  ({runs} runs)", best.as_secs_f64() * 1e3);
+    best
+}
+
+fn main() {
+    let sql = schema_dump();
+    let formatted = beautify(&tokenize_with(&sql, Dialect::PostgreSql));
+    println!("input: {:.1} MB", sql.len() as f64 / (1 << 20) as f64);
+
+    let before = bench("baseline (Vec<char>)", sql.len(), || {
+        black_box(baseline::tokenize_with(black_box(&sql), Dialect::PostgreSql));
+    });
+    let owned = bench("tokenize_with (owned)", sql.len(), || {
+        black_box(tokenize_with(black_box(&sql), Dialect::PostgreSql));
+    });
+    let borrowed = bench("tokens_with (borrowed)", sql.len(), || {
+        black_box(tokens_with(black_box(&sql), Dialect::PostgreSql).count());
+    });
+    bench("tokens_with (collected)", sql.len(), || {
+        black_box(tokens_with(black_box(&sql), Dialect::PostgreSql).collect::<Vec<_>>());
+    });
+    bench("colorize_with", formatted.len(), || {
+        black_box(colorize_with(black_box(&formatted), &Palette::ansi(), Dialect::PostgreSql));
+    });
+    let tokens = tokenize_with(&sql, Dialect::PostgreSql);
+    bench("beautify (tokenized)", sql.len(), || {
+        black_box(beautify(black_box(&tokens)));
+    });
+    println!(
+        "speedup over baseline: owned {:.1}x, borrowed {:.1}x",
+        before.as_secs_f64() / owned.as_secs_f64(),
+        before.as_secs_f64() / borrowed.as_secs_f64()
+    );
+}
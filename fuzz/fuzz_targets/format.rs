@@ -0,0 +1,27 @@
+// The invariants of tests/property_tests.rs, driven by libFuzzer:
+//
+//     cargo +nightly fuzz run format
+//
+// A crash found here belongs in tests/corpus/ once it is minimized
+// (`cargo fuzz tmin format <artifact>`).
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use sqlfmt::dialect::Dialect;
+use sqlfmt::formatter::{beautify, minify};
+use sqlfmt::tokenizer::{tokenize, tokenize_with, Token};
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(input) = std::str::from_utf8(data) else {
+        return;
+    };
+    for dialect in Dialect::NAMES.iter().filter_map(|name| Dialect::from_name(name)) {
+        let text: String = tokenize_with(input, dialect).iter().map(Token::text).collect();
+        assert_eq!(text, input, "token texts do not reproduce the input ({dialect:?})");
+    }
+
+    let once = beautify(&tokenize(input));
+    assert_eq!(beautify(&tokenize(&once)), once, "beautify is not idempotent");
+    assert_eq!(minify(&tokenize(&once)), minify(&tokenize(input)), "minify(beautify(x)) != minify(x)");
+});
@@ -0,0 +1,183 @@
+use crate::dialect::Dialect;
+use crate::splitter::{is_word, statement_end_at_eof, StatementKind, TRANSACTION_WORDS};
+use crate::tokenizer::{tokens_with, TokenRef};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatementType {
+    /// `SELECT`, `VALUES`, `TABLE` and `WITH ... SELECT`.
+    Select,
+    /// `INSERT` (and MySQL `REPLACE`).
+    Insert,
+    Update,
+    Delete,
+    Merge,
+    /// `COPY` to or from a file or stdin.
+    Copy,
+    CreateTable,
+    /// `CREATE` of anything other than a table (view, index, function, ...).
+    Create,
+    /// `ALTER`, `RENAME` and `COMMENT ON`, which change existing objects.
+    Alter,
+    Drop,
+    Truncate,
+    Grant,
+    Revoke,
+    /// `BEGIN`, `START TRANSACTION`, `COMMIT`, `ROLLBACK`, `SAVEPOINT`, ...
+    Transaction,
+    Other,
+}
+
+impl StatementType {
+    pub fn kind(self) -> StatementKind {
+        match self {
+            StatementType::Select
+            | StatementType::Insert
+            | StatementType::Update
+            | StatementType::Delete
+            | StatementType::Merge
+            | StatementType::Copy => StatementKind::Dml,
+            StatementType::CreateTable
+            | StatementType::Create
+            | StatementType::Alter
+            | StatementType::Drop
+            | StatementType::Truncate => StatementKind::Ddl,
+            StatementType::Grant | StatementType::Revoke => StatementKind::Dcl,
+            StatementType::Transaction => StatementKind::Tcl,
+            StatementType::Other => StatementKind::Other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Classification {
+    pub statement_type: StatementType,
+    /// Only reads data: a query with no `INTO` and no data-modifying `WITH`.
+    pub read_only: bool,
+    /// Throws away data or schema: `DROP`, `TRUNCATE`, `ALTER ... DROP`, and
+    /// `DELETE` or `UPDATE` without a `WHERE`.
+    pub destructive: bool,
+    /// Changes the schema (`statement_type.kind()` is `Ddl`).
+    pub ddl: bool,
+}
+
+impl Classification {
+    fn of(statement_type: StatementType, read_only: bool, destructive: bool) -> Self {
+        let ddl = statement_type.kind() == StatementKind::Ddl;
+        Classification { statement_type, read_only, destructive, ddl }
+    }
+}
+
+// Words that may come between `CREATE` and `TABLE`.
+const CREATE_TABLE_MODIFIERS: &[&str] =
+    &["OR", "REPLACE", "GLOBAL", "LOCAL", "TEMP", "TEMPORARY", "UNLOGGED", "EXTERNAL", "TRANSIENT", "VIRTUAL"];
+
+/// Classifies the first statement of `sql`.
+pub fn classify(sql: &str) -> Classification {
+    classify_with(sql, Dialect::Generic)
+}
+
+pub fn classify_with(sql: &str, dialect: Dialect) -> Classification {
+    // Code tokens of the first statement, with their paren depth.
+    let mut words = Vec::new();
+    let mut depth = 0usize;
+    for token in tokens_with(&sql[..statement_end_at_eof(sql, dialect)], dialect) {
+        match token {
+            // Jinja tags (`{{ config(...) }}` atop a dbt model) aren't SQL.
+            _ if token.is_trivia() || matches!(token, TokenRef::Template(_)) => continue,
+            TokenRef::OpenParen => depth += 1,
+            TokenRef::CloseParen => depth = depth.saturating_sub(1),
+            _ => words.push((depth, token)),
+        }
+    }
+    let Some(&(top, first)) = words.first() else {
+        return Classification::of(StatementType::Other, false, false);
+    };
+    let second = words.get(1).map(|&(_, t)| t);
+    let at_top = |names: &[&str]| words.iter().any(|(d, t)| *d == top && is_word(t, names));
+
+    let statement_type = if is_word(&first, &["WITH"]) {
+        // The statement the CTEs are for is the first top-level DML word.
+        words
+            .iter()
+            .skip(1)
+            .find(|(d, t)| *d == top && is_word(t, &["SELECT", "VALUES", "INSERT", "UPDATE", "DELETE", "MERGE"]))
+            .map_or(StatementType::Other, |(_, t)| leading_type(t, None, &words))
+    } else {
+        leading_type(&first, second, &words)
+    };
+
+    let filtered = at_top(&["WHERE"]);
+    // `WITH d AS (DELETE FROM t RETURNING *) SELECT ...` changes as many rows
+    // as a top-level DELETE would.
+    let unfiltered_cte = is_word(&first, &["WITH"])
+        && words.iter().enumerate().any(|(i, &(d, t))| {
+            d > top
+                && is_word(&t, &["DELETE", "UPDATE"])
+                && !is_lock(&words, i)
+                && !words[i + 1..]
+                    .iter()
+                    .take_while(|(e, _)| *e >= d)
+                    .any(|(e, t)| *e == d && is_word(t, &["WHERE"]))
+        });
+    let destructive = unfiltered_cte
+        || match statement_type {
+            StatementType::Drop | StatementType::Truncate => true,
+            StatementType::Delete | StatementType::Update => !filtered,
+            StatementType::Alter => at_top(&["DROP"]),
+            _ => false,
+        };
+    let read_only = statement_type == StatementType::Select
+        && !at_top(&["INTO"])
+        && !words.iter().enumerate().any(|(i, (_, t))| {
+            is_word(t, &["INSERT", "DELETE", "MERGE"]) || (is_word(t, &["UPDATE"]) && !is_lock(&words, i))
+        });
+    Classification::of(statement_type, read_only, destructive)
+}
+
+// `FOR UPDATE` and `FOR NO KEY UPDATE` only take locks.
+fn is_lock(words: &[(usize, TokenRef)], i: usize) -> bool {
+    is_word(&words[i].1, &["UPDATE"]) && i > 0 && is_word(&words[i - 1].1, &["FOR", "KEY"])
+}
+
+// The type of a statement starting with `first` (then `second`).
+fn leading_type(first: &TokenRef, second: Option<TokenRef>, words: &[(usize, TokenRef)]) -> StatementType {
+    let is = |names: &[&str]| is_word(first, names);
+    let second_is = |names: &[&str]| second.is_some_and(|t| is_word(&t, names));
+    if is(&["SELECT", "VALUES", "TABLE"]) {
+        StatementType::Select
+    } else if is(&["INSERT", "REPLACE", "UPSERT"]) {
+        StatementType::Insert
+    } else if is(&["UPDATE"]) {
+        StatementType::Update
+    } else if is(&["DELETE"]) {
+        StatementType::Delete
+    } else if is(&["MERGE"]) {
+        StatementType::Merge
+    } else if is(&["COPY"]) {
+        StatementType::Copy
+    } else if is(&["CREATE"]) {
+        let object = words.iter().skip(1).map(|(_, t)| t).find(|t| !is_word(t, CREATE_TABLE_MODIFIERS));
+        if object.is_some_and(|t| is_word(t, &["TABLE"])) {
+            StatementType::CreateTable
+        } else {
+            StatementType::Create
+        }
+    } else if is(&["ALTER", "RENAME", "COMMENT"]) {
+        StatementType::Alter
+    } else if is(&["DROP"]) {
+        StatementType::Drop
+    } else if is(&["TRUNCATE"]) {
+        StatementType::Truncate
+    } else if is(&["GRANT"]) {
+        StatementType::Grant
+    } else if is(&["REVOKE"]) {
+        StatementType::Revoke
+    } else if is(&["COMMIT", "ROLLBACK", "SAVEPOINT", "RELEASE", "ABORT", "END", "START"])
+        || (is(&["BEGIN"]) && second.is_none_or(|t| t == TokenRef::Semicolon || is_word(&t, TRANSACTION_WORDS)))
+        || (is(&["SET"]) && second_is(&["TRANSACTION"]))
+    {
+        StatementType::Transaction
+    } else {
+        StatementType::Other
+    }
+}
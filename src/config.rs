@@ -0,0 +1,448 @@
+use crate::dialect::Dialect;
+use crate::files;
+use crate::formatter::{CommaStyle, FormatOptions, KeywordCase};
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// File names looked for in each directory, in order of preference.
+pub const FILE_NAMES: &[&str] = &["sqlfmt.toml", ".sqlfmt.toml"];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum ColorWhen {
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorWhen {
+    pub const NAMES: &'static [&'static str] = &["auto", "always", "never"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "auto" => Some(ColorWhen::Auto),
+            "always" => Some(ColorWhen::Always),
+            "never" => Some(ColorWhen::Never),
+            _ => None,
+        }
+    }
+}
+
+/// Settings from a `sqlfmt.toml`. Every field is optional so that a config
+/// file and the command line can be layered with [`Config::merge`].
+///
+/// Keys use the command-line flag names:
+///
+/// ```toml
+/// dialect = "postgresql"
+/// indent = 4
+/// keyword-case = "lower"
+/// exclude = ["migrations/legacy/**"]
+/// ```
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Config {
+    pub indent_width: Option<usize>,
+    pub use_tabs: Option<bool>,
+    pub keyword_case: Option<KeywordCase>,
+    pub lines_between_statements: Option<usize>,
+    pub max_line_width: Option<usize>,
+    pub comma_style: Option<CommaStyle>,
+    pub format_sql_bodies: Option<bool>,
+    pub max_blank_lines: Option<usize>,
+    pub dialect: Option<Dialect>,
+    pub color: Option<ColorWhen>,
+    /// Globs selecting the files formatted when a directory is walked
+    /// (default: every `*.sql` file).
+    pub include: Option<Vec<String>>,
+    /// Globs of files skipped when a directory is walked.
+    pub exclude: Option<Vec<String>>,
+    /// Directory the globs are relative to: the one holding the config file.
+    pub root: Option<PathBuf>,
+}
+
+impl Config {
+    /// Finds the config file that applies to `dir`: the first `sqlfmt.toml`
+    /// or `.sqlfmt.toml` in `dir` or one of its ancestors.
+    pub fn discover(dir: &Path) -> Option<PathBuf> {
+        let dir = std::path::absolute(dir).ok()?;
+        dir.ancestors()
+            .flat_map(|d| FILE_NAMES.iter().map(move |name| d.join(name)))
+            .find(|path| path.is_file())
+    }
+
+    /// Reads and parses a config file. Errors are prefixed with the path and
+    /// line number.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        let mut config = Self::parse(&text).map_err(|e| format!("{}:{e}", path.display()))?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+        config.root = Some(std::path::absolute(dir).unwrap_or_else(|_| dir.to_path_buf()));
+        Ok(config)
+    }
+
+    /// Parses config text: a subset of TOML with top-level `key = value`
+    /// pairs, where values are strings, integers, booleans or arrays of
+    /// strings. Errors start with the line number (`"3: unknown key 'x'"`).
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut config = Self::default();
+        let mut lines = text.lines().enumerate();
+        while let Some((idx, line)) = lines.next() {
+            let line_no = idx + 1;
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            if line.starts_with('[') {
+                return Err(format!(
+                    "{line_no}: tables are not supported; put settings at the top level"
+                ));
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(format!("{line_no}: expected 'key = value'"));
+            };
+            let key = key.trim();
+            if key.is_empty()
+                || !key
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            {
+                return Err(format!("{line_no}: invalid key '{key}'"));
+            }
+
+            // Arrays may continue over several lines.
+            let mut value = value.to_string();
+            while open_brackets(&value) > 0 {
+                match lines.next() {
+                    Some((_, next)) => {
+                        value.push('\n');
+                        value.push_str(strip_comment(next));
+                    }
+                    None => return Err(format!("{line_no}: unclosed array for '{key}'")),
+                }
+            }
+
+            let value = parse_value(&value).map_err(|e| format!("{line_no}: {e} for '{key}'"))?;
+            config
+                .set(key, value)
+                .map_err(|e| format!("{line_no}: {e}"))?;
+        }
+        Ok(config)
+    }
+
+    fn set(&mut self, key: &str, value: Value) -> Result<(), String> {
+        match key {
+            "indent" => self.indent_width = Some(value.count(key)?),
+            "tabs" => self.use_tabs = Some(value.boolean(key)?),
+            "keyword-case" => {
+                self.keyword_case =
+                    Some(value.named(key, KeywordCase::from_name, KeywordCase::NAMES)?)
+            }
+            "lines-between-statements" => self.lines_between_statements = Some(value.count(key)?),
+            "max-line-width" => self.max_line_width = Some(value.count(key)?),
+            "commas" => {
+                self.comma_style =
+                    Some(value.named(key, CommaStyle::from_name, CommaStyle::NAMES)?)
+            }
+            "format-sql-bodies" => self.format_sql_bodies = Some(value.boolean(key)?),
+            "max-blank-lines" => self.max_blank_lines = Some(value.count(key)?),
+            "dialect" => {
+                self.dialect = Some(value.named(key, Dialect::from_name, Dialect::NAMES)?)
+            }
+            "color" => {
+                self.color = Some(value.named(key, ColorWhen::from_name, ColorWhen::NAMES)?)
+            }
+            "include" => self.include = Some(value.strings(key)?),
+            "exclude" => self.exclude = Some(value.strings(key)?),
+            _ => return Err(format!("unknown key '{key}'")),
+        }
+        Ok(())
+    }
+
+    /// Layers `other` on top of `self`: every setting `other` has replaces
+    /// the one in `self`.
+    pub fn merge(&mut self, other: &Config) {
+        self.indent_width = other.indent_width.or(self.indent_width);
+        self.use_tabs = other.use_tabs.or(self.use_tabs);
+        self.keyword_case = other.keyword_case.or(self.keyword_case);
+        self.lines_between_statements = other
+            .lines_between_statements
+            .or(self.lines_between_statements);
+        self.max_line_width = other.max_line_width.or(self.max_line_width);
+        self.comma_style = other.comma_style.or(self.comma_style);
+        self.format_sql_bodies = other.format_sql_bodies.or(self.format_sql_bodies);
+        self.max_blank_lines = other.max_blank_lines.or(self.max_blank_lines);
+        self.dialect = other.dialect.or(self.dialect);
+        self.color = other.color.or(self.color);
+        if other.include.is_some() {
+            self.include = other.include.clone();
+        }
+        if other.exclude.is_some() {
+            self.exclude = other.exclude.clone();
+        }
+        if other.root.is_some() {
+            self.root = other.root.clone();
+        }
+    }
+
+    /// The formatter options, with defaults for anything not set.
+    pub fn format_options(&self) -> FormatOptions {
+        let defaults = FormatOptions::default();
+        FormatOptions {
+            indent_width: self.indent_width.unwrap_or(defaults.indent_width),
+            use_tabs: self.use_tabs.unwrap_or(defaults.use_tabs),
+            keyword_case: self.keyword_case.unwrap_or(defaults.keyword_case),
+            lines_between_statements: self
+                .lines_between_statements
+                .unwrap_or(defaults.lines_between_statements),
+            max_line_width: self.max_line_width.or(defaults.max_line_width),
+            comma_style: self.comma_style.unwrap_or(defaults.comma_style),
+            format_sql_bodies: self.format_sql_bodies.unwrap_or(defaults.format_sql_bodies),
+            max_blank_lines: self.max_blank_lines.unwrap_or(defaults.max_blank_lines),
+        }
+    }
+
+    /// Whether a file found while walking a directory should be formatted,
+    /// according to `include` and `exclude`.
+    pub fn includes(&self, path: &Path) -> bool {
+        let rel = self.relative_path(path);
+        let included = match &self.include {
+            Some(globs) => globs.iter().any(|g| glob_match(g, &rel)),
+            None => files::has_sql_extension(path),
+        };
+        included && !self.excludes(path)
+    }
+
+    /// Whether an `exclude` glob matches `path`.
+    pub fn excludes(&self, path: &Path) -> bool {
+        let rel = self.relative_path(path);
+        self.exclude.iter().flatten().any(|g| glob_match(g, &rel))
+    }
+
+    // `path` relative to the config root, with `/` separators.
+    fn relative_path(&self, path: &Path) -> String {
+        let absolute = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+        let rel = match &self.root {
+            Some(root) => absolute.strip_prefix(root).unwrap_or(path),
+            None => path,
+        };
+        let parts: Vec<_> = rel
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect();
+        parts.join("/")
+    }
+}
+
+/// Matches a `/`-separated path against a glob. `*` and `?` stay within one
+/// path segment, `**` spans any number of them. A pattern without a `/` is
+/// matched against the file name alone, so `*.gen.sql` works at any depth.
+pub fn glob_match(pattern: &str, path: &str) -> bool {
+    let pattern = pattern.strip_prefix("./").unwrap_or(pattern);
+    let path: Vec<char> = if pattern.contains('/') {
+        path.chars().collect()
+    } else {
+        path.rsplit('/').next().unwrap_or(path).chars().collect()
+    };
+    let pattern: Vec<char> = pattern.chars().collect();
+    glob_match_at(&pattern, &path)
+}
+
+fn glob_match_at(p: &[char], s: &[char]) -> bool {
+    match p.first() {
+        None => s.is_empty(),
+        Some('*') if p.get(1) == Some(&'*') => {
+            let rest = &p[2..];
+            match rest.strip_prefix(&['/']) {
+                // `**/` matches zero or more whole directories.
+                Some(after) => {
+                    glob_match_at(after, s)
+                        || (0..s.len()).any(|i| s[i] == '/' && glob_match_at(after, &s[i + 1..]))
+                }
+                None => (0..=s.len()).any(|i| glob_match_at(rest, &s[i..])),
+            }
+        }
+        Some('*') => (0..=s.len())
+            .take_while(|&i| i == 0 || s[i - 1] != '/')
+            .any(|i| glob_match_at(&p[1..], &s[i..])),
+        Some('?') => s.first().is_some_and(|&c| c != '/') && glob_match_at(&p[1..], &s[1..]),
+        Some(&c) => s.first() == Some(&c) && glob_match_at(&p[1..], &s[1..]),
+    }
+}
+
+enum Value {
+    Str(String),
+    Int(i64),
+    Bool(bool),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    fn count(self, key: &str) -> Result<usize, String> {
+        match self {
+            Value::Int(n) if n >= 0 => Ok(n as usize),
+            _ => Err(format!(
+                "invalid value for '{key}': expected a non-negative integer"
+            )),
+        }
+    }
+
+    fn boolean(self, key: &str) -> Result<bool, String> {
+        match self {
+            Value::Bool(b) => Ok(b),
+            _ => Err(format!("invalid value for '{key}': expected true or false")),
+        }
+    }
+
+    fn named<T>(
+        self,
+        key: &str,
+        from_name: fn(&str) -> Option<T>,
+        names: &[&str],
+    ) -> Result<T, String> {
+        match self {
+            Value::Str(s) => from_name(&s).ok_or_else(|| {
+                format!(
+                    "invalid value '{s}' for '{key}' [possible values: {}]",
+                    names.join(", ")
+                )
+            }),
+            _ => Err(format!("invalid value for '{key}': expected a string")),
+        }
+    }
+
+    fn strings(self, key: &str) -> Result<Vec<String>, String> {
+        let invalid = || format!("invalid value for '{key}': expected an array of strings");
+        match self {
+            Value::Array(items) => items
+                .into_iter()
+                .map(|item| match item {
+                    Value::Str(s) => Ok(s),
+                    _ => Err(invalid()),
+                })
+                .collect(),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+// Drops a trailing `# comment`, leaving `#` inside strings alone.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match quote {
+            Some(_) if escaped => escaped = false,
+            Some('"') if c == '\\' => escaped = true,
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == '#' => return &line[..i],
+            None => {}
+        }
+    }
+    line
+}
+
+// Unclosed `[` outside strings.
+fn open_brackets(text: &str) -> isize {
+    let mut depth = 0;
+    let mut quote = None;
+    let mut escaped = false;
+    for c in text.chars() {
+        match quote {
+            Some(_) if escaped => escaped = false,
+            Some('"') if c == '\\' => escaped = true,
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == '[' => depth += 1,
+            None if c == ']' => depth -= 1,
+            None => {}
+        }
+    }
+    depth
+}
+
+type Chars<'a> = std::iter::Peekable<std::str::Chars<'a>>;
+
+fn parse_value(text: &str) -> Result<Value, String> {
+    let mut chars = text.chars().peekable();
+    let value = value(&mut chars)?;
+    skip_whitespace(&mut chars);
+    match chars.next() {
+        None => Ok(value),
+        Some(_) => Err("unexpected text after value".to_string()),
+    }
+}
+
+fn skip_whitespace(chars: &mut Chars) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+fn value(chars: &mut Chars) -> Result<Value, String> {
+    skip_whitespace(chars);
+    match chars.peek() {
+        Some('"') => {
+            chars.next();
+            let mut s = String::new();
+            loop {
+                match chars.next() {
+                    Some('"') => return Ok(Value::Str(s)),
+                    Some('\\') => match chars.next() {
+                        Some('n') => s.push('\n'),
+                        Some('t') => s.push('\t'),
+                        Some(c @ ('"' | '\\')) => s.push(c),
+                        _ => return Err("invalid escape in string".to_string()),
+                    },
+                    Some('\n') | None => return Err("unterminated string".to_string()),
+                    Some(c) => s.push(c),
+                }
+            }
+        }
+        Some('\'') => {
+            chars.next();
+            let mut s = String::new();
+            loop {
+                match chars.next() {
+                    Some('\'') => return Ok(Value::Str(s)),
+                    Some('\n') | None => return Err("unterminated string".to_string()),
+                    Some(c) => s.push(c),
+                }
+            }
+        }
+        Some('[') => {
+            chars.next();
+            let mut items = Vec::new();
+            loop {
+                skip_whitespace(chars);
+                if chars.next_if_eq(&']').is_some() {
+                    return Ok(Value::Array(items));
+                }
+                items.push(value(chars)?);
+                skip_whitespace(chars);
+                match chars.next() {
+                    Some(',') => {}
+                    Some(']') => return Ok(Value::Array(items)),
+                    _ => return Err("expected ',' or ']' in array".to_string()),
+                }
+            }
+        }
+        Some(_) => {
+            let mut word = String::new();
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace() && *c != ',' && *c != ']') {
+                word.push(c);
+            }
+            match word.as_str() {
+                "true" => Ok(Value::Bool(true)),
+                "false" => Ok(Value::Bool(false)),
+                _ => word
+                    .replace('_', "")
+                    .parse()
+                    .map(Value::Int)
+                    .map_err(|_| format!("invalid value '{word}'")),
+            }
+        }
+        None => Err("missing value".to_string()),
+    }
+}
@@ -0,0 +1,126 @@
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Dialect {
+    /// Accepts every quoting style; what `tokenize` has always done.
+    #[default]
+    Generic,
+    Ansi,
+    PostgreSql,
+    MySql,
+    Sqlite,
+    TSql,
+    BigQuery,
+    Snowflake,
+}
+
+const POSTGRES_KEYWORDS: &[&str] = &[
+    "ILIKE", "LATERAL", "CONFLICT", "DO", "NOTHING", "RETURNS", "LANGUAGE", "VACUUM",
+    "ANALYZE", "MATERIALIZED",
+];
+
+const MYSQL_KEYWORDS: &[&str] = &[
+    "AUTO_INCREMENT", "ENGINE", "DUPLICATE", "REGEXP", "SHOW", "DESCRIBE", "IGNORE",
+    "STRAIGHT_JOIN",
+];
+
+const SQLITE_KEYWORDS: &[&str] = &["AUTOINCREMENT", "PRAGMA", "GLOB", "VACUUM", "ATTACH", "DETACH"];
+
+const TSQL_KEYWORDS: &[&str] = &["TOP", "GO", "IDENTITY", "OUTPUT", "MERGE", "NOLOCK", "APPLY"];
+
+const BIGQUERY_KEYWORDS: &[&str] = &["STRUCT", "ARRAY", "QUALIFY", "UNNEST", "MERGE"];
+
+const SNOWFLAKE_KEYWORDS: &[&str] = &["QUALIFY", "ILIKE", "LATERAL", "FLATTEN", "MERGE", "SAMPLE"];
+
+// Multi-character operators, longest first so `->>` wins over `->`.
+const BASE_OPERATORS: &[&str] = &["<>", "<=", ">=", "!=", "||"];
+
+const GENERIC_OPERATORS: &[&str] = &["->>", "::", "->", "<>", "<=", ">=", "!=", "||"];
+
+const POSTGRES_OPERATORS: &[&str] = &[
+    "->>", "#>>", "!~*", "::", "->", "#>", "@>", "<@", "&&", "~*", "!~", "?|", "?&", "<<", ">>",
+    "<>", "<=", ">=", "!=", "||",
+];
+
+const MYSQL_OPERATORS: &[&str] = &["<=>", "->>", ":=", "->", "&&", "<<", ">>", "<>", "<=", ">=", "!=", "||"];
+
+const SQLITE_OPERATORS: &[&str] = &["->>", "==", "->", "<<", ">>", "<>", "<=", ">=", "!=", "||"];
+
+const SNOWFLAKE_OPERATORS: &[&str] = &["::", "->", "<>", "<=", ">=", "!=", "||"];
+
+impl Dialect {
+    pub const NAMES: &'static [&'static str] =
+        &["generic", "ansi", "postgresql", "mysql", "sqlite", "tsql", "bigquery", "snowflake"];
+
+    /// Looks up a dialect by name (case-insensitive). Accepts a few common
+    /// aliases such as `postgres` and `mssql`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "generic" => Some(Dialect::Generic),
+            "ansi" => Some(Dialect::Ansi),
+            "postgresql" | "postgres" | "psql" | "pg" => Some(Dialect::PostgreSql),
+            "mysql" | "mariadb" => Some(Dialect::MySql),
+            "sqlite" => Some(Dialect::Sqlite),
+            "tsql" | "t-sql" | "mssql" | "sqlserver" => Some(Dialect::TSql),
+            "bigquery" => Some(Dialect::BigQuery),
+            "snowflake" => Some(Dialect::Snowflake),
+            _ => None,
+        }
+    }
+
+    /// `"..."` quotes an identifier (true) or a string (false).
+    pub fn double_quote_is_identifier(self) -> bool {
+        !matches!(self, Dialect::MySql | Dialect::BigQuery)
+    }
+
+    pub fn backtick_identifiers(self) -> bool {
+        matches!(self, Dialect::Generic | Dialect::MySql | Dialect::Sqlite | Dialect::BigQuery)
+    }
+
+    pub fn bracket_identifiers(self) -> bool {
+        matches!(self, Dialect::Generic | Dialect::Sqlite | Dialect::TSql)
+    }
+
+    /// Backslash escapes a character inside string literals (`'it\'s'`).
+    pub fn backslash_escapes(self) -> bool {
+        matches!(self, Dialect::MySql | Dialect::BigQuery)
+    }
+
+    /// `$$ ... $$` and `$tag$ ... $tag$` quote string bodies.
+    pub fn dollar_quoted_strings(self) -> bool {
+        matches!(self, Dialect::Generic | Dialect::PostgreSql | Dialect::Snowflake)
+    }
+
+    /// `#` starts a line comment.
+    pub fn hash_comments(self) -> bool {
+        matches!(self, Dialect::MySql | Dialect::BigQuery)
+    }
+
+    /// A bare `?` is a bind parameter. In PostgreSQL it is the JSONB
+    /// key-exists operator (`data ? 'key'`).
+    pub fn question_mark_placeholders(self) -> bool {
+        self != Dialect::PostgreSql
+    }
+
+    pub fn operators(self) -> &'static [&'static str] {
+        match self {
+            Dialect::PostgreSql => POSTGRES_OPERATORS,
+            Dialect::MySql => MYSQL_OPERATORS,
+            Dialect::Sqlite => SQLITE_OPERATORS,
+            Dialect::Snowflake => SNOWFLAKE_OPERATORS,
+            Dialect::Generic => GENERIC_OPERATORS,
+            Dialect::Ansi | Dialect::TSql | Dialect::BigQuery => BASE_OPERATORS,
+        }
+    }
+
+    /// Keywords on top of the common set.
+    pub fn extra_keywords(self) -> &'static [&'static str] {
+        match self {
+            Dialect::PostgreSql => POSTGRES_KEYWORDS,
+            Dialect::MySql => MYSQL_KEYWORDS,
+            Dialect::Sqlite => SQLITE_KEYWORDS,
+            Dialect::TSql => TSQL_KEYWORDS,
+            Dialect::BigQuery => BIGQUERY_KEYWORDS,
+            Dialect::Snowflake => SNOWFLAKE_KEYWORDS,
+            Dialect::Generic | Dialect::Ansi => &[],
+        }
+    }
+}
@@ -0,0 +1,237 @@
+// Lines of unchanged context shown around each change, as in `diff -u`.
+const CONTEXT: usize = 3;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Op {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// Renders a unified diff (`diff -u` style, three lines of context) turning
+/// `old` into `new`. Returns an empty string when the two are identical.
+pub fn unified(old: &str, new: &str, old_label: &str, new_label: &str) -> String {
+    let a: Vec<&str> = old.split_inclusive('\n').collect();
+    let b: Vec<&str> = new.split_inclusive('\n').collect();
+    let ops = myers(&a, &b);
+    if ops.iter().all(|&(op, _, _)| op == Op::Equal) {
+        return String::new();
+    }
+
+    let mut out = format!("--- {old_label}\n+++ {new_label}\n");
+    for (start, end) in hunks(&ops) {
+        let hunk = &ops[start..end];
+        let old_len = hunk.iter().filter(|(op, _, _)| *op != Op::Insert).count();
+        let new_len = hunk.iter().filter(|(op, _, _)| *op != Op::Delete).count();
+        let (_, old_pos, new_pos) = hunk[0];
+        out.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            range(old_pos, old_len),
+            range(new_pos, new_len)
+        ));
+        for &(op, i, j) in hunk {
+            let (prefix, line) = match op {
+                Op::Equal => (' ', a[i]),
+                Op::Delete => ('-', a[i]),
+                Op::Insert => ('+', b[j]),
+            };
+            out.push(prefix);
+            out.push_str(line);
+            if !line.ends_with('\n') {
+                out.push_str("\n\\ No newline at end of file\n");
+            }
+        }
+    }
+    out
+}
+
+/// Colors a diff produced by [`unified`] with ANSI escapes: removed lines red,
+/// added lines green, hunk headers cyan and file headers bold.
+pub fn colorize(diff: &str) -> String {
+    let mut out = String::with_capacity(diff.len() * 2);
+    for line in diff.split_inclusive('\n') {
+        let text = line.strip_suffix('\n').unwrap_or(line);
+        let color = if text.starts_with("---") || text.starts_with("+++") {
+            "\x1b[1m"
+        } else if text.starts_with("@@") {
+            "\x1b[36m"
+        } else if text.starts_with('-') {
+            "\x1b[31m"
+        } else if text.starts_with('+') {
+            "\x1b[32m"
+        } else {
+            ""
+        };
+        if color.is_empty() {
+            out.push_str(line);
+        } else {
+            out.push_str(color);
+            out.push_str(text);
+            out.push_str("\x1b[0m");
+            out.push_str(&line[text.len()..]);
+        }
+    }
+    out
+}
+
+// `start,len` with a 1-based start; an empty range names the line before it.
+fn range(pos: usize, len: usize) -> String {
+    match len {
+        0 => format!("{pos},0"),
+        1 => format!("{}", pos + 1),
+        _ => format!("{},{len}", pos + 1),
+    }
+}
+
+// Groups the edit script into hunks: runs of changes whose surrounding
+// context would touch or overlap are merged. Returns half-open op ranges.
+fn hunks(ops: &[(Op, usize, usize)]) -> Vec<(usize, usize)> {
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for (idx, &(op, _, _)) in ops.iter().enumerate() {
+        if op == Op::Equal {
+            continue;
+        }
+        let start = idx.saturating_sub(CONTEXT);
+        let end = (idx + 1 + CONTEXT).min(ops.len());
+        match hunks.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+    hunks
+}
+
+// Myers' O(ND) shortest edit script, in linear space: the middle of an
+// optimal path is found by searching from both ends at once, and the halves
+// on either side are diffed recursively. Each op carries the positions in
+// `a` and `b` it applies at (for inserts the `a` position is where the line
+// lands, and vice versa for deletes), which the hunk headers need.
+fn myers(a: &[&str], b: &[&str]) -> Vec<(Op, usize, usize)> {
+    let mut ops = Vec::with_capacity(a.len().max(b.len()));
+    diff_into(a, b, 0, 0, &mut ops);
+    // Within a run of changes, deletes come before inserts.
+    let mut start = 0;
+    while start < ops.len() {
+        if ops[start].0 == Op::Equal {
+            start += 1;
+            continue;
+        }
+        let end = ops[start..].iter().position(|&(op, _, _)| op == Op::Equal).map_or(ops.len(), |n| start + n);
+        let (_, x, y) = ops[start];
+        let deletes = ops[start..end].iter().filter(|&&(op, _, _)| op == Op::Delete).count();
+        for (n, op) in ops[start..end].iter_mut().enumerate() {
+            *op = if n < deletes {
+                (Op::Delete, x + n, y)
+            } else {
+                (Op::Insert, x + deletes, y + n - deletes)
+            };
+        }
+        start = end;
+    }
+    ops
+}
+
+// Appends the edit script turning `a` into `b` to `ops`; `x` and `y` are
+// where the slices start in the full inputs.
+fn diff_into(a: &[&str], b: &[&str], x: usize, y: usize, ops: &mut Vec<(Op, usize, usize)>) {
+    let prefix = a.iter().zip(b).take_while(|(p, q)| p == q).count();
+    let suffix = a[prefix..].iter().rev().zip(b[prefix..].iter().rev()).take_while(|(p, q)| p == q).count();
+    ops.extend((0..prefix).map(|n| (Op::Equal, x + n, y + n)));
+    let (a_mid, b_mid) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+    let (x_mid, y_mid) = (x + prefix, y + prefix);
+    match middle(a_mid, b_mid) {
+        Some((sx, sy)) => {
+            diff_into(&a_mid[..sx], &b_mid[..sy], x_mid, y_mid, ops);
+            diff_into(&a_mid[sx..], &b_mid[sy..], x_mid + sx, y_mid + sy, ops);
+        }
+        None => {
+            ops.extend((0..a_mid.len()).map(|n| (Op::Delete, x_mid + n, y_mid)));
+            ops.extend((0..b_mid.len()).map(|n| (Op::Insert, x_mid + a_mid.len(), y_mid + n)));
+        }
+    }
+    let (x_end, y_end) = (x + a.len() - suffix, y + b.len() - suffix);
+    ops.extend((0..suffix).map(|n| (Op::Equal, x_end + n, y_end + n)));
+}
+
+// A point an optimal path from the start of `a` and `b` to their ends goes
+// through, strictly between the two, where the forward and reverse searches
+// meet. `None` when there is nothing to split: one side is empty or the two
+// have no line in common.
+fn middle(a: &[&str], b: &[&str]) -> Option<(usize, usize)> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    if n == 0 || m == 0 {
+        return None;
+    }
+    let max_d = (n + m + 1) / 2;
+    let offset = max_d + 1;
+    let width = 2 * offset + 1;
+    // Furthest `x` reached on each diagonal, forward from the start and
+    // backward from the end (counted from the end).
+    let mut forward = vec![-1isize; width as usize];
+    let mut reverse = vec![-1isize; width as usize];
+    forward[(offset + 1) as usize] = 0;
+    reverse[(offset + 1) as usize] = 0;
+    let delta = n - m;
+    // With an odd delta the paths meet on a forward step, else on a reverse one.
+    let odd = delta % 2 != 0;
+    // Diagonals that have run off the edit graph are skipped.
+    let (mut k1_start, mut k1_end, mut k2_start, mut k2_end) = (0, 0, 0, 0);
+    for d in 0..max_d {
+        let mut k1 = -d + k1_start;
+        while k1 <= d - k1_end {
+            let i = (offset + k1) as usize;
+            let mut x1 = if k1 == -d || (k1 != d && forward[i - 1] < forward[i + 1]) {
+                forward[i + 1]
+            } else {
+                forward[i - 1] + 1
+            };
+            let mut y1 = x1 - k1;
+            while x1 < n && y1 < m && a[x1 as usize] == b[y1 as usize] {
+                x1 += 1;
+                y1 += 1;
+            }
+            forward[i] = x1;
+            if x1 > n {
+                k1_end += 2;
+            } else if y1 > m {
+                k1_start += 2;
+            } else if odd {
+                let j = offset + delta - k1;
+                if (0..width).contains(&j) && reverse[j as usize] != -1 && x1 >= n - reverse[j as usize] {
+                    return Some((x1 as usize, y1 as usize));
+                }
+            }
+            k1 += 2;
+        }
+        let mut k2 = -d + k2_start;
+        while k2 <= d - k2_end {
+            let i = (offset + k2) as usize;
+            let mut x2 = if k2 == -d || (k2 != d && reverse[i - 1] < reverse[i + 1]) {
+                reverse[i + 1]
+            } else {
+                reverse[i - 1] + 1
+            };
+            let mut y2 = x2 - k2;
+            while x2 < n && y2 < m && a[(n - x2 - 1) as usize] == b[(m - y2 - 1) as usize] {
+                x2 += 1;
+                y2 += 1;
+            }
+            reverse[i] = x2;
+            if x2 > n {
+                k2_end += 2;
+            } else if y2 > m {
+                k2_start += 2;
+            } else if !odd {
+                let j = offset + delta - k2;
+                if (0..width).contains(&j) && forward[j as usize] != -1 {
+                    let x1 = forward[j as usize];
+                    if x1 >= n - x2 {
+                        return Some((x1 as usize, (x1 - (delta - k2)) as usize));
+                    }
+                }
+            }
+            k2 += 2;
+        }
+    }
+    None
+}
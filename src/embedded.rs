@@ -0,0 +1,576 @@
+use crate::dialect::Dialect;
+use crate::formatter::{beautify_with, FormatOptions};
+use crate::tokenizer::tokenize_with;
+
+use std::ops::Range;
+use std::path::Path;
+
+/// A programming language whose string literals can hold SQL.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    Python,
+    Rust,
+    Go,
+}
+
+impl Language {
+    /// The language of a source file, by its extension (`.py`, `.rs`, `.go`).
+    pub fn from_path(path: &Path) -> Option<Language> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "py" => Some(Language::Python),
+            "rs" => Some(Language::Rust),
+            "go" => Some(Language::Go),
+            _ => None,
+        }
+    }
+
+    // Functions and macros whose first string argument is SQL.
+    fn call_sites(self) -> &'static [&'static str] {
+        match self {
+            Language::Python => &["execute", "executemany", "executescript", "mogrify", "read_sql", "read_sql_query"],
+            Language::Rust => &[
+                "query",
+                "query_as",
+                "query_scalar",
+                "query_unchecked",
+                "query_as_unchecked",
+                "query_scalar_unchecked",
+                "execute",
+                "execute_batch",
+                "prepare",
+                "prepare_cached",
+                "query_row",
+            ],
+            Language::Go => &[
+                "Query",
+                "QueryContext",
+                "QueryRow",
+                "QueryRowContext",
+                "Queryx",
+                "QueryxContext",
+                "QueryRowx",
+                "QueryRowxContext",
+                "Exec",
+                "ExecContext",
+                "MustExec",
+                "MustExecContext",
+                "Prepare",
+                "PrepareContext",
+                "Preparex",
+                "Get",
+                "GetContext",
+                "Select",
+                "SelectContext",
+                "NamedExec",
+                "NamedExecContext",
+                "NamedQuery",
+                "NamedQueryContext",
+            ],
+        }
+    }
+
+    // One level of indentation, for SQL moved onto lines of its own.
+    fn indent_unit(self) -> &'static str {
+        match self {
+            Language::Go => "\t",
+            Language::Python | Language::Rust => "    ",
+        }
+    }
+}
+
+// How a literal's contents are written, for the kinds that can span lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Quoting {
+    // Python `"""..."""` or `'''...'''`, with an `r` prefix or not.
+    PythonTriple { quote: u8, raw: bool },
+    // Rust `"..."`.
+    RustEscaped,
+    // Rust `r"..."`, `r#"..."#`, ... with this many `#`.
+    RustRaw(usize),
+    // Go `` `...` ``.
+    GoRaw,
+}
+
+/// A string literal in a program that holds SQL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SqlString {
+    /// 1-based line the literal starts on.
+    pub line: usize,
+    /// Byte range of the literal's contents, between its quotes.
+    pub content: Range<usize>,
+    /// Indentation of the line the literal starts on.
+    pub indent: String,
+    quoting: Quoting,
+    unit: &'static str,
+}
+
+impl SqlString {
+    /// The SQL in the literal, with escapes resolved and the indentation its
+    /// lines share taken off. None if it uses an escape that can't be
+    /// written back the same way (such as Python's `\N{...}`).
+    pub fn code(&self, source: &str) -> Option<String> {
+        let value = self.value(source)?;
+        let mut lines = value.lines();
+        let first = lines.next().unwrap_or_default().trim_start();
+        let rest: Vec<&str> = lines.collect();
+        let common = common_indent(&rest);
+        let mut code = first.to_string();
+        for line in rest {
+            code.push('\n');
+            code.push_str(line.get(common.len()..).unwrap_or_default());
+        }
+        Some(code.trim_matches('\n').trim_end().to_string())
+    }
+
+    /// The contents to write between the quotes for `formatted` SQL: on
+    /// lines of their own, indented one level past the literal's line (or as
+    /// the SQL already was, if it started on a line of its own), with the
+    /// closing quote back at the literal's indentation. None if the literal
+    /// can't hold the text.
+    pub fn literal(&self, source: &str, formatted: &str) -> Option<String> {
+        let value = self.value(source)?;
+        let mut lines = value.lines();
+        let own_line = lines.next().is_some_and(|first| first.trim().is_empty());
+        let rest: Vec<&str> = lines.collect();
+        let base = if own_line && rest.iter().any(|l| !l.trim().is_empty()) {
+            common_indent(&rest).to_string()
+        } else {
+            format!("{}{}", self.indent, self.unit)
+        };
+        let mut text = String::from("\n");
+        for line in formatted.trim_end().lines() {
+            // Blank lines stay empty; lines of strings keep their spaces.
+            if !line.is_empty() {
+                text.push_str(&base);
+            }
+            text.push_str(line);
+            text.push('\n');
+        }
+        text.push_str(&self.indent);
+        encode(&text, self.quoting)
+    }
+
+    // The literal's value, with escapes resolved.
+    fn value(&self, source: &str) -> Option<String> {
+        let raw = &source[self.content.clone()];
+        match self.quoting {
+            Quoting::PythonTriple { raw: false, .. } => unescape_python(raw),
+            Quoting::RustEscaped => unescape_rust(raw),
+            Quoting::PythonTriple { raw: true, .. } | Quoting::RustRaw(_) | Quoting::GoRaw => Some(raw.to_string()),
+        }
+    }
+}
+
+// The leading whitespace shared by the non-blank `lines`.
+fn common_indent<'a>(lines: &[&'a str]) -> &'a str {
+    let mut common: Option<&str> = None;
+    for line in lines.iter().filter(|l| !l.trim().is_empty()) {
+        let indent = &line[..line.len() - line.trim_start().len()];
+        common = Some(match common {
+            None => indent,
+            Some(c) => {
+                let same = c.bytes().zip(indent.bytes()).take_while(|(a, b)| a == b).count();
+                &c[..same]
+            }
+        });
+    }
+    common.unwrap_or_default()
+}
+
+fn unescape_python(raw: &str) -> Option<String> {
+    let mut out = String::with_capacity(raw.len());
+    let mut chars = raw.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        let Some(next) = chars.next() else {
+            out.push('\\');
+            break;
+        };
+        match next {
+            '\n' => {}
+            '\\' | '\'' | '"' => out.push(next),
+            'n' => out.push('\n'),
+            't' => out.push('\t'),
+            'r' => out.push('\r'),
+            'a' => out.push('\u{7}'),
+            'b' => out.push('\u{8}'),
+            'f' => out.push('\u{c}'),
+            'v' => out.push('\u{b}'),
+            '0'..='7' => {
+                let mut code = next.to_digit(8)?;
+                for _ in 0..2 {
+                    match chars.peek().and_then(|c| c.to_digit(8)) {
+                        Some(d) => {
+                            code = code * 8 + d;
+                            chars.next();
+                        }
+                        None => break,
+                    }
+                }
+                out.push(char::from_u32(code)?);
+            }
+            'x' | 'u' | 'U' => {
+                let digits = match next {
+                    'x' => 2,
+                    'u' => 4,
+                    _ => 8,
+                };
+                let hex: String = (0..digits).map_while(|_| chars.next()).collect();
+                out.push(char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?);
+            }
+            // `\N{name}` would need the Unicode name table.
+            'N' => return None,
+            // Unknown escapes keep their backslash.
+            _ => {
+                out.push('\\');
+                out.push(next);
+            }
+        }
+    }
+    Some(out)
+}
+
+fn unescape_rust(raw: &str) -> Option<String> {
+    let mut out = String::with_capacity(raw.len());
+    let mut chars = raw.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next()? {
+            // A line continuation also skips the next line's indentation.
+            '\n' => {
+                while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            }
+            c @ ('\\' | '\'' | '"') => out.push(c),
+            'n' => out.push('\n'),
+            't' => out.push('\t'),
+            'r' => out.push('\r'),
+            '0' => out.push('\0'),
+            'x' => {
+                let hex: String = (0..2).map_while(|_| chars.next()).collect();
+                out.push(char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?);
+            }
+            'u' => {
+                if chars.next()? != '{' {
+                    return None;
+                }
+                let hex: String = chars.by_ref().take_while(|&c| c != '}').filter(|&c| c != '_').collect();
+                out.push(char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?);
+            }
+            _ => return None,
+        }
+    }
+    Some(out)
+}
+
+// Writes `text` as the contents of a literal quoted with `quoting`, escaping
+// only what has to be; None if a raw literal can't hold it.
+fn encode(text: &str, quoting: Quoting) -> Option<String> {
+    match quoting {
+        Quoting::PythonTriple { quote, raw } => {
+            let quote = quote as char;
+            let chars: Vec<char> = text.chars().collect();
+            let mut out = String::with_capacity(text.len());
+            let mut i = 0;
+            while i < chars.len() {
+                let c = chars[i];
+                if c == quote {
+                    // A run of three quotes, or one that touches the closing
+                    // quotes, would end the literal.
+                    let run = chars[i..].iter().take_while(|&&c| c == quote).count();
+                    if run >= 3 || i + run == chars.len() {
+                        if raw {
+                            return None;
+                        }
+                        for _ in 0..run {
+                            out.push('\\');
+                            out.push(quote);
+                        }
+                    } else {
+                        out.extend(&chars[i..i + run]);
+                    }
+                    i += run;
+                    continue;
+                }
+                if c == '\\' && !raw {
+                    out.push('\\');
+                }
+                out.push(c);
+                i += 1;
+            }
+            Some(out)
+        }
+        Quoting::RustEscaped => {
+            let mut out = String::with_capacity(text.len());
+            for c in text.chars() {
+                match c {
+                    '\\' => out.push_str("\\\\"),
+                    '"' => out.push_str("\\\""),
+                    '\r' => out.push_str("\\r"),
+                    '\0' => out.push_str("\\0"),
+                    c => out.push(c),
+                }
+            }
+            Some(out)
+        }
+        Quoting::RustRaw(hashes) => {
+            let close = format!("\"{}", "#".repeat(hashes));
+            (!text.contains(&close)).then(|| text.to_string())
+        }
+        Quoting::GoRaw => (!text.contains('`')).then(|| text.to_string()),
+    }
+}
+
+// A string literal found in a program; `quoting` is None for the kinds that
+// are never formatted (one-line, byte and format strings).
+struct Literal {
+    start: usize,
+    content: Range<usize>,
+    quoting: Option<Quoting>,
+}
+
+fn is_ident_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_' || b >= 0x80
+}
+
+// End of a quoted run starting after its opening quote at `from`, closed by
+// `close` and with backslash escapes: (content end, literal end).
+fn scan_escaped(source: &str, from: usize, close: &str, one_line: bool) -> (usize, usize) {
+    let bytes = source.as_bytes();
+    let mut i = from;
+    while i < bytes.len() {
+        if bytes[i] == b'\\' {
+            i += 2;
+        } else if bytes[i..].starts_with(close.as_bytes()) {
+            return (i, i + close.len());
+        } else if one_line && bytes[i] == b'\n' {
+            return (i, i);
+        } else {
+            i += 1;
+        }
+    }
+    (source.len(), source.len())
+}
+
+// The string literals of `source`, skipping comments.
+fn literals(source: &str, language: Language) -> Vec<Literal> {
+    let bytes = source.as_bytes();
+    let mut found = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let start = i;
+        let rest = &source[i..];
+        match (language, bytes[i]) {
+            (Language::Python, b'#') => i += rest.find('\n').unwrap_or(rest.len()),
+            (Language::Rust | Language::Go, b'/') if rest.starts_with("//") => {
+                i += rest.find('\n').unwrap_or(rest.len());
+            }
+            (Language::Rust | Language::Go, b'/') if rest.starts_with("/*") => {
+                // Rust block comments nest; Go ones don't.
+                let mut depth = 0;
+                while i < bytes.len() {
+                    if bytes[i..].starts_with(b"/*") {
+                        depth += 1;
+                        i += 2;
+                    } else if bytes[i..].starts_with(b"*/") {
+                        i += 2;
+                        depth -= 1;
+                        if depth == 0 || language == Language::Go {
+                            break;
+                        }
+                    } else {
+                        i += 1;
+                    }
+                }
+            }
+            (_, b) if is_ident_byte(b) => {
+                let len = rest.bytes().take_while(|&b| is_ident_byte(b)).count();
+                let word = &rest[..len];
+                let next = bytes.get(i + len).copied();
+                i += len;
+                if bytes[start].is_ascii_digit() {
+                    continue;
+                }
+                match language {
+                    Language::Python
+                        if len <= 2
+                            && matches!(next, Some(b'"' | b'\''))
+                            && word.bytes().all(|b| b"rRbBuUfF".contains(&b)) =>
+                    {
+                        let lower = word.to_ascii_lowercase();
+                        let plain = !lower.contains('b') && !lower.contains('f');
+                        let (content, end, triple) = python_string(source, i);
+                        let quoting = (triple && plain)
+                            .then(|| Quoting::PythonTriple { quote: bytes[i], raw: lower.contains('r') });
+                        found.push(Literal { start, content, quoting });
+                        i = end;
+                    }
+                    Language::Rust if matches!(word, "r" | "br" | "cr") && matches!(next, Some(b'"' | b'#')) => {
+                        let hashes = source[i..].bytes().take_while(|&b| b == b'#').count();
+                        if bytes.get(i + hashes) != Some(&b'"') {
+                            continue;
+                        }
+                        let open = i + hashes + 1;
+                        let close = format!("\"{}", "#".repeat(hashes));
+                        let content_end = source[open..].find(&close).map_or(source.len(), |p| open + p);
+                        let quoting = (word == "r").then_some(Quoting::RustRaw(hashes));
+                        found.push(Literal { start, content: open..content_end, quoting });
+                        i = (content_end + close.len()).min(source.len());
+                    }
+                    Language::Rust if matches!(word, "b" | "c") && next == Some(b'"') => {
+                        let (content_end, end) = scan_escaped(source, i + 1, "\"", false);
+                        found.push(Literal { start, content: i + 1..content_end, quoting: None });
+                        i = end;
+                    }
+                    _ => {}
+                }
+            }
+            (Language::Python, b'"' | b'\'') => {
+                let (content, end, triple) = python_string(source, i);
+                let quoting = triple.then_some(Quoting::PythonTriple { quote: bytes[i], raw: false });
+                found.push(Literal { start, content, quoting });
+                i = end;
+            }
+            (Language::Rust, b'"') => {
+                let (content_end, end) = scan_escaped(source, i + 1, "\"", false);
+                found.push(Literal { start, content: i + 1..content_end, quoting: Some(Quoting::RustEscaped) });
+                i = end;
+            }
+            (Language::Rust, b'\'') => {
+                // A char literal, or else a lifetime or label.
+                let after = rest[1..].chars().next().map_or(0, char::len_utf8);
+                if rest[1..].starts_with('\\') {
+                    i = scan_escaped(source, i + 1, "'", true).1;
+                } else if rest.as_bytes().get(1 + after) == Some(&b'\'') {
+                    i += after + 2;
+                } else {
+                    i += 1;
+                }
+            }
+            (Language::Go, b'`') => {
+                let content_end = rest[1..].find('`').map_or(source.len(), |p| i + 1 + p);
+                found.push(Literal { start, content: i + 1..content_end, quoting: Some(Quoting::GoRaw) });
+                i = (content_end + 1).min(source.len());
+            }
+            (Language::Go, b'"' | b'\'') => {
+                let close = if bytes[i] == b'"' { "\"" } else { "'" };
+                i = scan_escaped(source, i + 1, close, true).1.max(i + 1);
+            }
+            _ => i += 1,
+        }
+    }
+    found
+}
+
+// The Python string whose opening quote is at `quote`: (content, end,
+// whether it is triple-quoted).
+fn python_string(source: &str, quote: usize) -> (Range<usize>, usize, bool) {
+    let q = &source[quote..quote + 1];
+    let triple = q.repeat(3);
+    if source[quote..].starts_with(&triple) {
+        let open = quote + 3;
+        let (content_end, end) = scan_escaped(source, open, &triple, false);
+        (open..content_end, end, true)
+    } else {
+        let (content_end, end) = scan_escaped(source, quote + 1, q, true);
+        (quote + 1..content_end, end.max(quote + 1), false)
+    }
+}
+
+// Whether the literal starting at `start` is the first string argument of a
+// call to one of `language`'s call sites, such as `cursor.execute("""...`
+// or `sqlx::query_as!(User, "...`.
+fn at_call_site(source: &str, start: usize, language: Language) -> bool {
+    // Other arguments before it: names, paths, references and commas.
+    let before = source[..start]
+        .trim_end_matches(|c: char| c.is_whitespace() || c.is_alphanumeric() || "_:&.<>,*".contains(c));
+    let Some(callee) = before.strip_suffix('(') else {
+        return false;
+    };
+    let callee = callee.trim_end();
+    let callee = match language {
+        Language::Rust => callee.strip_suffix('!').unwrap_or(callee),
+        Language::Python | Language::Go => callee,
+    };
+    let name_start = callee.trim_end_matches(|c: char| c.is_alphanumeric() || c == '_').len();
+    language.call_sites().contains(&&callee[name_start..])
+}
+
+// Whether the literal's contents start with a `-- sql` comment.
+fn has_marker(content: &str) -> bool {
+    let Some(rest) = content.trim_start().strip_prefix("--") else {
+        return false;
+    };
+    let rest = rest.trim_start_matches([' ', '\t']);
+    rest.get(..3).is_some_and(|word| word.eq_ignore_ascii_case("sql"))
+        && !rest[3..].chars().next().is_some_and(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Finds the string literals in a `language` program that hold SQL: those
+/// passed first to a known call such as Python's `cursor.execute`, sqlx's
+/// `query!` or Go's `db.Query`, and those starting with a `-- sql` comment.
+/// Only literals that can span lines are considered: Python triple-quoted
+/// strings (not byte or f-strings), Rust string and raw string literals, and
+/// Go raw strings.
+pub fn sql_strings(source: &str, language: Language) -> Vec<SqlString> {
+    literals(source, language)
+        .into_iter()
+        .filter_map(|literal| {
+            let quoting = literal.quoting?;
+            let content = &source[literal.content.clone()];
+            if !has_marker(content) && !at_call_site(source, literal.start, language) {
+                return None;
+            }
+            let line_start = source[..literal.start].rfind('\n').map_or(0, |i| i + 1);
+            let line = &source[line_start..];
+            let indent = &line[..line.len() - line.trim_start_matches([' ', '\t']).len()];
+            Some(SqlString {
+                line: source[..literal.start].matches('\n').count() + 1,
+                content: literal.content,
+                indent: indent.to_string(),
+                quoting,
+                unit: language.indent_unit(),
+            })
+        })
+        .collect()
+}
+
+/// Replaces the SQL of each string found by [`sql_strings`] with what
+/// `format` returns for its code, laid out by [`SqlString::literal`].
+/// Strings for which it returns None, or that can't be written back, are
+/// left alone, as is everything else.
+pub fn format_strings(
+    source: &str,
+    language: Language,
+    mut format: impl FnMut(&SqlString, &str) -> Option<String>,
+) -> String {
+    let mut out = String::with_capacity(source.len());
+    let mut copied = 0;
+    for string in sql_strings(source, language) {
+        let Some(code) = string.code(source) else {
+            continue;
+        };
+        if code.trim().is_empty() {
+            continue;
+        }
+        let Some(literal) = format(&string, &code).and_then(|formatted| string.literal(source, &formatted)) else {
+            continue;
+        };
+        out.push_str(&source[copied..string.content.start]);
+        out.push_str(&literal);
+        copied = string.content.end;
+    }
+    out.push_str(&source[copied..]);
+    out
+}
+
+/// Beautifies the SQL strings of a `language` program with `opts`.
+pub fn beautify_embedded(source: &str, language: Language, dialect: Dialect, opts: &FormatOptions) -> String {
+    format_strings(source, language, |_, code| Some(beautify_with(&tokenize_with(code, dialect), opts)))
+}
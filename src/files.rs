@@ -0,0 +1,84 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(|n| n.starts_with('.') && n != "." && n != "..")
+}
+
+/// SQL files: `*.sql`, in any case.
+pub fn has_sql_extension(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("sql"))
+}
+
+/// Markdown files (`*.md`, `*.markdown`) have their SQL code blocks
+/// formatted rather than being formatted as SQL.
+pub fn is_markdown(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("md") || ext.eq_ignore_ascii_case("markdown"))
+}
+
+/// Expands the given paths into the files to format: files are taken as-is,
+/// directories are searched recursively for `*.sql` (skipping hidden
+/// entries). Directory contents are returned in sorted order.
+pub fn collect_sql_files(paths: &[PathBuf]) -> io::Result<Vec<PathBuf>> {
+    collect_files(paths, has_sql_extension)
+}
+
+/// Like [`collect_sql_files`], but files found in directories are kept when
+/// `keep` returns true instead of by extension.
+pub fn collect_files(paths: &[PathBuf], mut keep: impl FnMut(&Path) -> bool) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            walk(path, &mut keep, &mut files)?;
+        } else {
+            files.push(path.clone());
+        }
+    }
+    Ok(files)
+}
+
+fn walk(dir: &Path, keep: &mut impl FnMut(&Path) -> bool, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<io::Result<_>>()?;
+    entries.sort();
+    for path in entries {
+        if is_hidden(&path) {
+            continue;
+        }
+        if path.is_dir() {
+            walk(&path, keep, files)?;
+        } else if keep(&path) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Replaces `path` with `contents` by writing a temporary file next to it and
+/// renaming it over the original, so readers never see a partial file.
+pub fn write_atomic(path: &Path, contents: &str) -> io::Result<()> {
+    let dir = match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    };
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("sqlfmt");
+    let tmp = dir.join(format!(".{name}.sqlfmt-{}.tmp", std::process::id()));
+
+    let result = (|| {
+        let mut file = fs::File::create(&tmp)?;
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()?;
+        if let Ok(meta) = fs::metadata(path) {
+            fs::set_permissions(&tmp, meta.permissions())?;
+        }
+        fs::rename(&tmp, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
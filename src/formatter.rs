@@ -1,10 +1,15 @@
-use crate::tokenizer::Token;
+use crate::dialect::Dialect;
+use crate::splitter::statement_range;
+use crate::tokenizer::{tokenize_with, Token, TokenRef};
+
+use std::borrow::Cow;
+use std::ops::Range;
 
 fn is_clause_keyword(kw: &str) -> bool {
     matches!(
         kw,
         "SELECT" | "FROM" | "WHERE" | "HAVING" | "LIMIT" | "SET" | "VALUES"
-            | "UNION" | "EXCEPT" | "INTERSECT" | "JOIN" | "ON"
+            | "UNION" | "EXCEPT" | "INTERSECT" | "JOIN" | "ON" | "QUALIFY"
     )
 }
 
@@ -29,12 +34,233 @@ fn is_ddl_modifier(kw: &str) -> bool {
     matches!(kw, "OR" | "REPLACE" | "TEMPORARY" | "TEMP" | "UNIQUE" | "IF" | "NOT" | "EXISTS")
 }
 
-fn indent_str(level: usize) -> String {
-    "  ".repeat(level)
+// How a Jinja `{% ... %}` tag affects the nesting of the tags around it.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum TemplateBlock {
+    // `{% if %}`, `{% for %}`, `{% macro %}`, ...
+    Start,
+    // `{% elif %}`, `{% else %}`
+    Middle,
+    // `{% endif %}`, `{% endfor %}`, ...
+    End,
+}
+
+fn template_block(tag: &str) -> Option<TemplateBlock> {
+    let body = tag.strip_prefix("{%")?.trim_start_matches(['-', '+']).trim_start();
+    let word = &body[..body.find(|c: char| !c.is_alphanumeric() && c != '_').unwrap_or(body.len())];
+    match word {
+        "if" | "for" | "macro" | "call" | "filter" | "block" | "with" | "raw" | "materialization" | "test"
+        | "snapshot" | "docs" => Some(TemplateBlock::Start),
+        // `{% set x %}...{% endset %}` captures a block; `{% set x = 1 %}` doesn't.
+        "set" if !body.contains('=') => Some(TemplateBlock::Start),
+        "elif" | "else" => Some(TemplateBlock::Middle),
+        _ if word.starts_with("end") => Some(TemplateBlock::End),
+        _ => None,
+    }
+}
+
+// Statement and comment tags go on lines of their own; `{{ ... }}`
+// expressions are values.
+fn is_template_line(tag: &str) -> bool {
+    tag.starts_with("{%") || tag.starts_with("{#")
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum KeywordCase {
+    #[default]
+    Upper,
+    Lower,
+    Preserve,
+}
+
+impl KeywordCase {
+    pub const NAMES: &'static [&'static str] = &["upper", "lower", "preserve"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "upper" => Some(KeywordCase::Upper),
+            "lower" => Some(KeywordCase::Lower),
+            "preserve" => Some(KeywordCase::Preserve),
+            _ => None,
+        }
+    }
+}
+
+// Where the comma goes when a list is broken one item per line.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum CommaStyle {
+    #[default]
+    Trailing,
+    Leading,
+}
+
+impl CommaStyle {
+    pub const NAMES: &'static [&'static str] = &["trailing", "leading"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "trailing" => Some(CommaStyle::Trailing),
+            "leading" => Some(CommaStyle::Leading),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct FormatOptions {
+    /// Spaces per indentation level (ignored when `use_tabs` is set).
+    pub indent_width: usize,
+    /// Indent with one tab per level instead of spaces.
+    pub use_tabs: bool,
+    pub keyword_case: KeywordCase,
+    /// Blank lines emitted between consecutive statements.
+    pub lines_between_statements: usize,
+    /// Target line width. When set, clauses that fit stay on one line and
+    /// inline parenthesized groups that don't fit wrap one item per line.
+    pub max_line_width: Option<usize>,
+    pub comma_style: CommaStyle,
+    /// Reformat dollar-quoted function bodies of `LANGUAGE sql` functions
+    /// instead of emitting them verbatim.
+    pub format_sql_bodies: bool,
+    /// Blank lines kept from the input within a statement, at most this many
+    /// in a row (0 drops them all).
+    pub max_blank_lines: usize,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            indent_width: 2,
+            use_tabs: false,
+            keyword_case: KeywordCase::Upper,
+            lines_between_statements: 1,
+            max_line_width: None,
+            comma_style: CommaStyle::Trailing,
+            format_sql_bodies: false,
+            max_blank_lines: 1,
+        }
+    }
+}
+
+// `kw` in upper case, copied only if it isn't already.
+fn uppercase(kw: &str) -> Cow<'_, str> {
+    if kw.chars().any(char::is_lowercase) {
+        Cow::Owned(kw.to_uppercase())
+    } else {
+        Cow::Borrowed(kw)
+    }
 }
 
-fn filter_tokens(tokens: &[Token]) -> Vec<&Token> {
-    tokens.iter().filter(|t| !matches!(t, Token::Whitespace(_))).collect()
+// Indentation is sliced from these instead of allocated for every line.
+const SPACES: &str = "                                                                ";
+const TABS: &str = "\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t\t";
+
+impl FormatOptions {
+    fn indent(&self, level: usize) -> Cow<'static, str> {
+        let (unit, width) = if self.use_tabs { (TABS, 1) } else { (SPACES, self.indent_width) };
+        match unit.get(..level * width) {
+            Some(indent) => Cow::Borrowed(indent),
+            None => Cow::Owned(unit[..1].repeat(level * width)),
+        }
+    }
+
+    fn keyword<'k>(&self, kw: &'k str) -> Cow<'k, str> {
+        match self.keyword_case {
+            KeywordCase::Upper => uppercase(kw),
+            KeywordCase::Lower if kw.chars().any(char::is_uppercase) => Cow::Owned(kw.to_lowercase()),
+            KeywordCase::Lower | KeywordCase::Preserve => Cow::Borrowed(kw),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Directive {
+    Off,
+    On,
+}
+
+// `-- sqlfmt: off`, `/* sqlfmt: on */` and the like.
+pub(crate) fn directive(comment: &str) -> Option<Directive> {
+    let body = match comment.strip_prefix("/*") {
+        Some(body) => body.strip_suffix("*/").unwrap_or(body),
+        None => comment.trim_start_matches(['-', '#']),
+    };
+    let (name, value) = body.trim().split_once(':')?;
+    if !name.trim().eq_ignore_ascii_case("sqlfmt") {
+        return None;
+    }
+    match value.trim().to_ascii_lowercase().as_str() {
+        "off" => Some(Directive::Off),
+        "on" => Some(Directive::On),
+        _ => None,
+    }
+}
+
+// Token ranges to leave untouched: from a `sqlfmt: off` comment through the
+// matching `sqlfmt: on` comment, or through the last non-whitespace token if
+// formatting is never turned back on.
+fn verbatim_ranges(tokens: &[Token]) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut start = None;
+    for (i, token) in tokens.iter().enumerate() {
+        let Token::Comment(c) = token else { continue };
+        match (directive(c), start) {
+            (Some(Directive::Off), None) => start = Some(i),
+            (Some(Directive::On), Some(s)) => {
+                ranges.push(s..i + 1);
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        let end = tokens.iter().rposition(|t| !t.is_trivia() || matches!(t, Token::Comment(_)));
+        ranges.push(s..end.map_or(tokens.len(), |e| e + 1));
+    }
+    ranges
+}
+
+// A region that runs to the end of the input may end in an unterminated
+// token that swallowed the final newline; layout adds its own.
+fn verbatim_text(tokens: &[Token]) -> String {
+    let text: String = tokens.iter().map(Token::text).collect();
+    text.trim_end().to_string()
+}
+
+// Drops whitespace, returning the remaining tokens along with how many line
+// breaks preceded each one in the input. Each verbatim region is replaced by
+// its original text as a single comment-like token from `regions`, so it is
+// laid out like a comment but emitted exactly as written.
+fn filter_tokens<'t>(
+    tokens: &'t [Token],
+    ranges: &[Range<usize>],
+    regions: &'t [Token],
+) -> (Vec<&'t Token>, Vec<usize>) {
+    let mut filtered = Vec::with_capacity(tokens.len());
+    let mut newlines = Vec::with_capacity(tokens.len());
+    let mut pending_newlines = 0;
+    let mut i = 0;
+    let mut next_region = 0;
+    while i < tokens.len() {
+        if let Some(range) = ranges.get(next_region).filter(|r| r.start == i) {
+            filtered.push(&regions[next_region]);
+            newlines.push(pending_newlines);
+            pending_newlines = 0;
+            next_region += 1;
+            i = range.end;
+            continue;
+        }
+        match &tokens[i] {
+            Token::Whitespace(ws) => pending_newlines += ws.bytes().filter(|&b| b == b'\n').count(),
+            token => {
+                filtered.push(token);
+                newlines.push(pending_newlines);
+                pending_newlines = 0;
+            }
+        }
+        i += 1;
+    }
+    (filtered, newlines)
 }
 
 fn next_significant_token(tokens: &[&Token], from: usize) -> Option<usize> {
@@ -50,6 +276,32 @@ fn ends_in_word_like(s: &str) -> bool {
     s.chars().last().is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '`' || c == '"' || c == ']')
 }
 
+// `.` (qualified names) and `::` (PostgreSQL casts) bind without spaces.
+fn is_accessor(op: &str) -> bool {
+    op == "." || op == "::"
+}
+
+// Pairs that would read back as a different token if written without a
+// space: a number next to a `.` (`1.` or `.5`), a `:name` placeholder
+// after a `::` cast (`:::name`), a modulo before `s` or `(` (`%s`) and a
+// `sqlfmt: off` region after a `-`.
+fn would_merge(prev: Option<&Token>, token: &Token) -> bool {
+    match (prev, token) {
+        (Some(Token::Operator(op)), Token::Comment(c)) => {
+            (op.ends_with('-') && c.starts_with('-')) || (op.ends_with('/') && c.starts_with('*'))
+        }
+        (Some(Token::NumberLiteral(_)), Token::Operator(op)) => op == ".",
+        (Some(Token::Operator(op)), Token::NumberLiteral(_)) => op == ".",
+        (Some(Token::Operator(op)), Token::Placeholder(p)) => op == "::" && p.starts_with(':'),
+        (Some(Token::Operator(op)), t) => op == "%" && t.text().starts_with(['s', '(']),
+        _ => false,
+    }
+}
+
+fn ends_with_accessor(s: &str) -> bool {
+    s.ends_with('.') || s.ends_with("::")
+}
+
 fn paren_contains_subquery(tokens: &[&Token], start: usize) -> bool {
     if let Some(j) = next_significant_token(tokens, start) {
         if let Token::Keyword(kw) = tokens[j] {
@@ -62,6 +314,11 @@ fn paren_contains_subquery(tokens: &[&Token], start: usize) -> bool {
 #[derive(Clone, Copy, PartialEq, Debug)]
 enum ParenMode {
     Inline,
+    // An inline group too long for `max_line_width`: one item per line.
+    Wrapped,
+    // A too-long window spec (PARTITION BY ... ORDER BY ...): one line per
+    // `BY` clause.
+    WrappedClauses,
     Subquery,
     DefList,
 }
@@ -76,384 +333,1027 @@ fn innermost_mode(stack: &[ParenCtx]) -> Option<ParenMode> {
     stack.last().map(|c| c.mode)
 }
 
+fn matching_close_paren(tokens: &[&Token], open: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    for (j, tok) in tokens.iter().enumerate().skip(open) {
+        match tok {
+            Token::OpenParen => depth += 1,
+            Token::CloseParen => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(j);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+// True if the keyword at `j` begins a new clause line (SELECT, GROUP BY,
+// LEFT JOIN, ...).
+fn starts_clause(tokens: &[&Token], j: usize) -> bool {
+    let Token::Keyword(kw) = tokens[j] else {
+        return false;
+    };
+    let upper = kw.to_uppercase();
+    if is_clause_keyword(&upper) || is_statement_starter(&upper) {
+        return true;
+    }
+    let next_upper = match next_significant_token(tokens, j).map(|n| tokens[n]) {
+        Some(Token::Keyword(next_kw)) => next_kw.to_uppercase(),
+        _ => return false,
+    };
+    (is_compound_first(&upper) && is_compound_second(&next_upper))
+        || (is_join_modifier(&upper) && next_upper == "JOIN")
+}
+
+// True for the first keyword of `PARTITION BY`, `ORDER BY` and `GROUP BY`.
+fn starts_by_clause(tokens: &[&Token], j: usize) -> bool {
+    let Token::Keyword(kw) = tokens[j] else {
+        return false;
+    };
+    matches!(kw.to_uppercase().as_str(), "PARTITION" | "ORDER" | "GROUP")
+        && matches!(
+            next_significant_token(tokens, j).map(|n| tokens[n]),
+            Some(Token::Keyword(next_kw)) if next_kw.eq_ignore_ascii_case("BY")
+        )
+}
+
+// End (exclusive) of the clause content starting at `start`, or None if the
+// content can't be laid out on one line (comments, subqueries).
+fn clause_content_end(tokens: &[&Token], start: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut j = start;
+    while j < tokens.len() {
+        match tokens[j] {
+            Token::Comment(_) => return None,
+            Token::Template(t) if is_template_line(t) => return None,
+            Token::OpenParen => {
+                if paren_contains_subquery(tokens, j) {
+                    return None;
+                }
+                depth += 1;
+            }
+            Token::CloseParen => {
+                if depth == 0 {
+                    break;
+                }
+                depth -= 1;
+            }
+            Token::Semicolon if depth == 0 => break,
+            Token::Keyword(_) if depth == 0 && starts_clause(tokens, j) => break,
+            _ => {}
+        }
+        j += 1;
+    }
+    Some(j)
+}
+
+// Renders tokens on a single line using the same spacing rules as inline
+// parens, so the result can be measured before anything is committed.
+fn render_flat(tokens: &[&Token], opts: &FormatOptions) -> String {
+    let mut out = String::new();
+    let mut last_was_keyword = false;
+    let mut prev_was_value = false;
+    let mut attach_next = false;
+    let mut prev: Option<&Token> = None;
+
+    for &token in tokens {
+        if would_merge(prev, token) {
+            out.push(' ');
+        }
+        prev = Some(token);
+        match token {
+            Token::Keyword(kw) => {
+                let upper = kw.to_uppercase();
+                if !out.is_empty() && !ends_with_accessor(&out) {
+                    out.push(' ');
+                }
+                prev_was_value = ends_with_accessor(&out)
+                    || matches!(upper.as_str(), "TRUE" | "FALSE" | "NULL" | "UNBOUNDED");
+                last_was_keyword = !ends_with_accessor(&out);
+                out.push_str(&opts.keyword(kw));
+                attach_next = false;
+                continue;
+            }
+            Token::Comma => {
+                out.push(',');
+                prev_was_value = false;
+            }
+            Token::OpenParen => {
+                if !out.is_empty() && !last_was_keyword && !ends_in_word_like(&out) {
+                    out.push(' ');
+                }
+                out.push('(');
+                prev_was_value = false;
+            }
+            Token::CloseParen => {
+                out.push(')');
+                prev_was_value = true;
+            }
+            Token::Operator(op) if is_accessor(op) => {
+                out.push_str(op);
+                last_was_keyword = false;
+                attach_next = false;
+                continue;
+            }
+            Token::Operator(op) => {
+                let is_unary = matches!(op.as_str(), "-" | "+") && !prev_was_value;
+                if !out.is_empty() && !out.ends_with('(') {
+                    out.push(' ');
+                }
+                out.push_str(op);
+                last_was_keyword = false;
+                attach_next = is_unary;
+                prev_was_value = false;
+                continue;
+            }
+            Token::Other(s) if !out.is_empty() && (s == "[" || s == "]") => {
+                out.push_str(s);
+                prev_was_value = s == "]";
+                last_was_keyword = false;
+                attach_next = s == "[";
+                continue;
+            }
+            Token::Identifier(s)
+            | Token::StringLiteral(s)
+            | Token::NumberLiteral(s)
+            | Token::Placeholder(s)
+            | Token::Template(s)
+            | Token::Other(s) => {
+                if !attach_next && !out.is_empty() && !out.ends_with('(') && !ends_with_accessor(&out) {
+                    out.push(' ');
+                }
+                out.push_str(s);
+                prev_was_value = true;
+            }
+            Token::Semicolon => {
+                out.push(';');
+                prev_was_value = false;
+            }
+            Token::Comment(c) | Token::Whitespace(c) => out.push_str(c),
+        }
+        last_was_keyword = false;
+        attach_next = false;
+    }
+    out
+}
+
 pub fn beautify(tokens: &[Token]) -> String {
-    let filtered = filter_tokens(tokens);
+    beautify_with(tokens, &FormatOptions::default())
+}
+
+/// Formats `tokens` with `opts`. Regions between `-- sqlfmt: off` and
+/// `-- sqlfmt: on` comments (or `/* ... */` ones) are copied verbatim.
+pub fn beautify_with(tokens: &[Token], opts: &FormatOptions) -> String {
+    let ranges = verbatim_ranges(tokens);
+    let regions: Vec<Token> = ranges.iter().map(|r| Token::Comment(verbatim_text(&tokens[r.clone()]))).collect();
+    let (filtered, newlines) = filter_tokens(tokens, &ranges, &regions);
     if filtered.is_empty() {
         return String::new();
     }
+    Beautifier::new(filtered, newlines, &regions, opts).run()
+}
 
-    let mut out = String::new();
-    let mut base_indent: usize = 0;
-    let mut line_started = false;
-    let mut in_clause_content = false;
-    let mut need_blank_line = false;
-    let mut paren_stack: Vec<ParenCtx> = Vec::new();
-    let mut last_was_keyword = false;
+/// Beautifies only the statements that the byte range `range` of `input`
+/// touches (see [`statement_range`]); the rest of `input` is left byte for
+/// byte as it was.
+pub fn beautify_range(input: &str, range: Range<usize>, dialect: Dialect, opts: &FormatOptions) -> String {
+    let Some(span) = statement_range(input, range, dialect) else {
+        return input.to_string();
+    };
+    let formatted = beautify_with(&tokenize_with(&input[span.clone()], dialect), opts);
+    format!("{}{}{}", &input[..span.start], formatted.trim_end(), &input[span.end..])
+}
+
+struct Beautifier<'t, 'o> {
+    tokens: Vec<&'t Token>,
+    // Line breaks before each token in the input.
+    newlines: Vec<usize>,
+    // Verbatim `sqlfmt: off` regions; `tokens` points into this.
+    regions: &'t [Token],
+    opts: &'o FormatOptions,
+    out: String,
+    base_indent: usize,
+    line_started: bool,
+    in_clause_content: bool,
+    need_blank_line: bool,
+    paren_stack: Vec<ParenCtx>,
+    last_was_keyword: bool,
 
     // DDL context: mark the next top-level `(` after CREATE/ALTER TABLE as a
     // column definition list (one item per line).
-    let mut saw_create_alter = false;
-    let mut expect_def_list_paren = false;
+    saw_create_alter: bool,
+    expect_def_list_paren: bool,
 
     // Unary sign detection: when `-` or `+` appears without a value on its
     // left, attach the next value directly (DEFAULT -1, not DEFAULT - 1).
-    let mut prev_was_value = false;
-    let mut attach_next = false;
+    prev_was_value: bool,
+    attach_next: bool,
 
-    let mut i = 0;
-    while i < filtered.len() {
-        let token = filtered[i];
-        let in_inline = innermost_mode(&paren_stack) == Some(ParenMode::Inline);
+    // Where in `out` the current run of comments began, so punctuation that
+    // follows them can be put back on the code line before them, and whether
+    // the run is only trailing comments.
+    comments_start: Option<(usize, bool)>,
+    // The last token that was put back before comments; a comment right
+    // after it would otherwise trail a line that already ends in one.
+    attached: Option<usize>,
+    // Indent of each open Jinja block's opening tag, which its `{% else %}`
+    // and `{% end... %}` tags line up with.
+    template_blocks: Vec<usize>,
+    // A leading comma held back until the comments before its item are out.
+    leading_comma: bool,
+}
 
-        match token {
-            Token::Comment(c) => {
-                if line_started {
-                    out.push(' ');
-                } else {
-                    if need_blank_line {
-                        out.push('\n');
-                        need_blank_line = false;
+impl<'t, 'o> Beautifier<'t, 'o> {
+    fn new(tokens: Vec<&'t Token>, newlines: Vec<usize>, regions: &'t [Token], opts: &'o FormatOptions) -> Self {
+        Self {
+            tokens,
+            newlines,
+            regions,
+            opts,
+            out: String::new(),
+            base_indent: 0,
+            line_started: false,
+            in_clause_content: false,
+            need_blank_line: false,
+            paren_stack: Vec::new(),
+            last_was_keyword: false,
+            saw_create_alter: false,
+            expect_def_list_paren: false,
+            prev_was_value: false,
+            attach_next: false,
+            comments_start: None,
+            attached: None,
+            template_blocks: Vec::new(),
+            leading_comma: false,
+        }
+    }
+
+    fn run(mut self) -> String {
+        let mut i = 0;
+        while i < self.tokens.len() {
+            // Separate statements: every statement starts on a fresh line, so
+            // the gap is emitted before whatever token begins the next one.
+            // A comment trailing the `;` still belongs to the previous line.
+            // The statement before `{% else %}` or `{% endif %}` is still in
+            // the block; the gap comes after the tag, or after the `;` that
+            // follows it.
+            let closes_block = match self.tokens[i] {
+                Token::Template(t) => matches!(template_block(t), Some(TemplateBlock::Middle | TemplateBlock::End)),
+                Token::Semicolon => self.follows_template_line(i),
+                _ => false,
+            };
+            if self.is_trailing_comment(i) || closes_block {
+            } else if self.need_blank_line {
+                for _ in 0..self.opts.lines_between_statements {
+                    self.out.push('\n');
+                }
+                self.need_blank_line = false;
+            } else if self.newlines[i] > 1 && self.opts.max_blank_lines > 0 {
+                // Keep blank lines the author left inside a statement, also
+                // before a clause or comment that ends the line it follows.
+                if self.line_started && self.starts_own_line(i) {
+                    self.out.push('\n');
+                    self.line_started = false;
+                }
+                if !self.line_started && self.out.ends_with('\n') {
+                    let blank_lines = (self.newlines[i] - 1).min(self.opts.max_blank_lines);
+                    for _ in 0..blank_lines {
+                        self.out.push('\n');
                     }
-                    out.push_str(&indent_str(base_indent));
                 }
-                out.push_str(c);
-                out.push('\n');
-                line_started = false;
-                last_was_keyword = false;
-                attach_next = false;
-                prev_was_value = false;
-                i += 1;
             }
+
+            if self.leading_comma && (!matches!(self.tokens[i], Token::Comment(_)) || self.is_verbatim(i)) {
+                self.out.push_str(&self.opts.indent(self.line_indent()));
+                self.out.push(',');
+                self.line_started = true;
+                self.leading_comma = false;
+            }
+
+            let start = i;
+            i = match self.tokens[i] {
+                Token::Comment(c) if self.is_verbatim(i) => self.verbatim(c, i),
+                Token::Comment(c) => self.comment(c, i),
+                Token::Keyword(kw) => self.keyword(kw, i),
+                Token::Comma => self.comma(i),
+                Token::Semicolon => self.semicolon(i),
+                Token::OpenParen => self.open_paren(i),
+                Token::CloseParen => self.close_paren(i),
+                Token::Operator(op) => self.operator(op, i),
+                Token::Other(s) if s == "[" || s == "]" => self.subscript(s, i),
+                Token::Template(t) if is_template_line(t) => self.template_tag(t, i),
+                Token::Identifier(s)
+                | Token::StringLiteral(s)
+                | Token::NumberLiteral(s)
+                | Token::Placeholder(s)
+                | Token::Template(s)
+                | Token::Other(s) => self.value(s, i),
+                Token::Whitespace(_) => i + 1,
+            };
+            if !matches!(self.tokens[start], Token::Comment(_)) || self.is_verbatim(start) {
+                self.comments_start = None;
+            }
+        }
+        self.out
+    }
+
+    // A token that always starts a line of its own: a clause keyword, `AND`
+    // or `OR`, or a comment on its own line (outside inline parens).
+    fn starts_own_line(&self, i: usize) -> bool {
+        if self.in_mode(ParenMode::Inline) || self.in_mode(ParenMode::Wrapped) || self.in_mode(ParenMode::WrappedClauses) {
+            return false;
+        }
+        match self.tokens[i] {
+            // A one-line `sqlfmt: off` region sits inline like a value.
+            Token::Comment(c) => !self.is_verbatim(i) || c.contains('\n'),
             Token::Keyword(kw) => {
-                let upper = kw.to_uppercase();
-                attach_next = false;
+                starts_clause(&self.tokens, i) || kw.eq_ignore_ascii_case("AND") || kw.eq_ignore_ascii_case("OR")
+            }
+            _ => false,
+        }
+    }
 
-                // After a dot (e.g., t.count), emit as-is without spacing.
-                // The qualified name is value-like for unary/binary detection.
-                if out.ends_with('.') {
-                    out.push_str(&upper);
-                    line_started = true;
-                    last_was_keyword = false;
-                    prev_was_value = true;
-                    i += 1;
-                    continue;
+    // Puts `text` at the end of the code line preceding the comments just
+    // emitted (`b, -- note` rather than a `,` below the comment). Returns
+    // false when there are no such comments. A `;` only moves past trailing
+    // comments: after it, an own-line comment would start the next statement.
+    fn attach_before_comments(&mut self, text: &str, i: usize) -> bool {
+        match self.comments_start {
+            Some((pos, trailing)) if trailing || text != ";" => {
+                // Blank lines kept for this token no longer precede anything.
+                while self.out.ends_with("\n\n") {
+                    self.out.pop();
                 }
+                self.out.insert_str(pos, text);
+                self.attached = Some(i);
+                self.comments_start = Some((pos + text.len(), trailing));
+                true
+            }
+            _ => false,
+        }
+    }
 
-                // Value-like keywords (NULL, TRUE, FALSE, etc.) are treated as
-                // values so a following `-` is binary.
-                prev_was_value = matches!(upper.as_str(), "TRUE" | "FALSE" | "NULL" | "UNBOUNDED");
+    fn in_mode(&self, mode: ParenMode) -> bool {
+        innermost_mode(&self.paren_stack) == Some(mode)
+    }
 
-                // Inside inline parens, keywords are just inline
-                if in_inline {
-                    if line_started {
-                        out.push(' ');
-                    }
-                    out.push_str(&upper);
-                    line_started = true;
-                    last_was_keyword = true;
-                    i += 1;
-                    continue;
-                }
+    // Indent level of a line that starts at the current position.
+    fn line_indent(&self) -> usize {
+        if self.in_clause_content {
+            self.base_indent + 1
+        } else {
+            self.base_indent
+        }
+    }
 
-                // DDL state tracking (only at top level outside any paren)
-                if paren_stack.is_empty() {
-                    if matches!(upper.as_str(), "CREATE" | "ALTER") {
-                        saw_create_alter = true;
-                    } else if upper == "TABLE" && saw_create_alter {
-                        expect_def_list_paren = true;
-                        saw_create_alter = false;
-                    } else if !is_ddl_modifier(&upper) {
-                        saw_create_alter = false;
-                    }
-                }
+    fn column(&self) -> usize {
+        let line = self.out.rsplit('\n').next().unwrap_or("");
+        line.chars()
+            .map(|c| if c == '\t' { self.opts.indent_width } else { 1 })
+            .sum()
+    }
 
-                // Check for join modifier + JOIN compound
-                if is_join_modifier(&upper) {
-                    if let Some(j) = next_significant_token(&filtered, i) {
-                        if let Token::Keyword(next_kw) = filtered[j] {
-                            if next_kw.to_uppercase() == "JOIN" {
-                                emit_clause_line(&mut out, &mut line_started, &mut need_blank_line, &mut in_clause_content, base_indent, &format!("{} {}", upper, next_kw.to_uppercase()));
-                                in_clause_content = true;
-                                last_was_keyword = true;
-                                i = j + 1;
-                                continue;
-                            }
-                        }
-                    }
-                    emit_inline_keyword(&mut out, &mut line_started, &mut last_was_keyword, base_indent, in_clause_content, &upper);
-                    i += 1;
-                    continue;
-                }
+    // A comment that followed code on the same line in the input.
+    fn is_trailing_comment(&self, i: usize) -> bool {
+        matches!(self.tokens[i], Token::Comment(c) if !self.is_verbatim(i) || c.contains('\n'))
+            && i > 0
+            && self.newlines[i] == 0
+            && self.attached != Some(i - 1)
+    }
+
+    fn comment(&mut self, c: &str, i: usize) -> usize {
+        // `(1, /* two */ 2)`: a block comment between code on one line stays
+        // where it is.
+        let code_follows = self.newlines.get(i + 1) == Some(&0);
+        if self.line_started && self.is_trailing_comment(i) && code_follows && c.starts_with("/*") {
+            self.out.push(' ');
+            self.out.push_str(c);
+            return i + 1;
+        }
+        // Only a comment run right after a value can have punctuation moved
+        // in front of it.
+        let after_value = i > 0
+            && matches!(
+                self.tokens[i - 1],
+                Token::Identifier(_)
+                    | Token::StringLiteral(_)
+                    | Token::NumberLiteral(_)
+                    | Token::Placeholder(_)
+                    | Token::Template(_)
+                    | Token::CloseParen
+            );
+        if after_value && !self.is_verbatim(i) {
+            let trailing = self.is_trailing_comment(i);
+            let code_end = if self.line_started || trailing {
+                self.out.trim_end_matches('\n').len()
+            } else {
+                self.out.trim_end().len()
+            };
+            self.comments_start = Some((code_end, trailing));
+        }
+        if self.is_trailing_comment(i) {
+            // Stay on the line of the code it annotates, even if that line
+            // has already been ended (`SELECT -- ...`, `a, -- ...`).
+            if !self.line_started && self.out.ends_with('\n') {
+                self.out.pop();
+            }
+            self.out.push(' ');
+        } else {
+            if let Some((_, trailing)) = &mut self.comments_start {
+                *trailing = false;
+            }
+            if self.line_started {
+                self.out.push('\n');
+            }
+            // A comment heading a clause lines up with the clause keyword;
+            // one among the clause's items lines up with them.
+            // A `,` that will move up in front of this comment doesn't count.
+            let next = next_significant_token(&self.tokens, i).and_then(|j| {
+                let moves_up = self.comments_start.is_some()
+                    && self.opts.comma_style == CommaStyle::Trailing
+                    && !self.in_mode(ParenMode::Inline)
+                    && !self.in_mode(ParenMode::WrappedClauses)
+                    && *self.tokens[j] == Token::Comma;
+                if moves_up { next_significant_token(&self.tokens, j) } else { Some(j) }
+            });
+            let heads_clause = next
+                .is_none_or(|j| starts_clause(&self.tokens, j) || self.need_blank_line);
+            let indent = if heads_clause { self.base_indent } else { self.line_indent() };
+            self.out.push_str(&self.opts.indent(indent));
+        }
+        // An unterminated block comment runs to the end of the input and
+        // would otherwise take the final newline in with it on every pass.
+        let unterminated = c.starts_with("/*") && !c.ends_with("*/");
+        self.out.push_str(if unterminated { c.trim_end() } else { c });
+        self.out.push('\n');
+        self.line_started = false;
+        self.last_was_keyword = false;
+        self.attach_next = false;
+        self.prev_was_value = false;
+        i + 1
+    }
+
+    // A `{% ... %}` or `{# ... #}` tag, on a line of its own and indented one
+    // level deeper for each block it is in. Inside inline parens it stays
+    // inline.
+    fn template_tag(&mut self, tag: &str, i: usize) -> usize {
+        if self.in_mode(ParenMode::Inline) || self.in_mode(ParenMode::Wrapped) || self.in_mode(ParenMode::WrappedClauses) {
+            return self.value(tag, i);
+        }
+        let block = template_block(tag);
+        if matches!(block, Some(TemplateBlock::Middle | TemplateBlock::End)) {
+            self.base_indent = self.base_indent.saturating_sub(1);
+        }
+        // `{% else %}` after a statement starts the other branch, not a new
+        // statement.
+        if block == Some(TemplateBlock::Middle) {
+            self.need_blank_line = false;
+        }
+        if self.line_started {
+            self.out.push('\n');
+        }
+        let heads_clause = next_significant_token(&self.tokens, i)
+            .is_none_or(|j| starts_clause(&self.tokens, j) || self.need_blank_line);
+        let indent = match block {
+            Some(TemplateBlock::Middle) => self.template_blocks.last().copied(),
+            Some(TemplateBlock::End) => self.template_blocks.pop(),
+            _ => None,
+        };
+        let indent = indent.unwrap_or(if heads_clause { self.base_indent } else { self.line_indent() });
+        self.out.push_str(&self.opts.indent(indent));
+        self.out.push_str(tag);
+        if i + 1 < self.tokens.len() {
+            self.out.push('\n');
+        }
+        if block == Some(TemplateBlock::Start) {
+            self.template_blocks.push(indent);
+        }
+        if matches!(block, Some(TemplateBlock::Start | TemplateBlock::Middle)) {
+            self.base_indent += 1;
+        }
+        self.line_started = false;
+        self.last_was_keyword = false;
+        self.attach_next = false;
+        self.prev_was_value = false;
+        i + 1
+    }
+
+    fn is_verbatim(&self, i: usize) -> bool {
+        self.regions.iter().any(|r| std::ptr::eq(r, self.tokens[i]))
+    }
+
+    // A `sqlfmt: off` region. One that fits on a line sits in it like a
+    // value; anything longer is laid out like a comment.
+    fn verbatim(&mut self, text: &str, i: usize) -> usize {
+        if !text.contains('\n') {
+            return self.value(text, i);
+        }
+        let next = self.comment(text, i);
+        let tokens = crate::tokenizer::tokenize(text);
+        if tokens.iter().rev().find(|t| !t.is_trivia()) == Some(&Token::Semicolon) {
+            // The region finished the statement.
+            self.in_clause_content = false;
+            self.need_blank_line = true;
+            self.saw_create_alter = false;
+            self.expect_def_list_paren = false;
+        }
+        next
+    }
+
+    fn keyword(&mut self, kw: &str, i: usize) -> usize {
+        let opts = self.opts;
+        let upper = uppercase(kw);
+        self.attach_next = false;
+
+        // After a dot (e.g., t.count), emit as-is without spacing.
+        // The qualified name is value-like for unary/binary detection.
+        if ends_with_accessor(&self.out) {
+            self.out.push_str(&opts.keyword(kw));
+            self.line_started = true;
+            self.last_was_keyword = false;
+            self.prev_was_value = true;
+            return i + 1;
+        }
+
+        // `{{ config(...) }}` heads a dbt model; the statement after it
+        // starts a line of its own.
+        let after_template = i > 0 && matches!(self.tokens[i - 1], Token::Template(t) if !is_template_line(t));
+        if after_template && self.paren_stack.is_empty() && self.line_started && is_statement_starter(&upper) {
+            self.out.push('\n');
+            self.line_started = false;
+        }
+
+        // Value-like keywords (NULL, TRUE, FALSE, etc.) are treated as
+        // values so a following `-` is binary.
+        self.prev_was_value = matches!(&*upper, "TRUE" | "FALSE" | "NULL" | "UNBOUNDED");
 
-                // Check for compound keywords: GROUP BY, ORDER BY, INSERT INTO, DELETE FROM
-                if is_compound_first(&upper) {
-                    if let Some(j) = next_significant_token(&filtered, i) {
-                        if let Token::Keyword(next_kw) = filtered[j] {
-                            let next_upper = next_kw.to_uppercase();
-                            if is_compound_second(&next_upper) {
-                                emit_clause_line(&mut out, &mut line_started, &mut need_blank_line, &mut in_clause_content, base_indent, &format!("{} {}", upper, next_upper));
-                                in_clause_content = true;
-                                last_was_keyword = true;
-                                i = j + 1;
-                                continue;
-                            }
-                        }
+        // Inside inline (or wrapped) parens, keywords are just inline
+        if self.in_mode(ParenMode::WrappedClauses) && starts_by_clause(&self.tokens, i) {
+            if self.line_started {
+                self.out.push('\n');
+            }
+            self.line_started = false;
+        }
+        if self.in_mode(ParenMode::Inline) || self.in_mode(ParenMode::Wrapped) || self.in_mode(ParenMode::WrappedClauses) {
+            if self.line_started {
+                self.out.push(' ');
+            } else {
+                self.out.push_str(&opts.indent(self.line_indent()));
+            }
+            self.out.push_str(&opts.keyword(kw));
+            self.line_started = true;
+            self.last_was_keyword = true;
+            return i + 1;
+        }
+
+        // DDL state tracking (only at top level outside any paren)
+        if self.paren_stack.is_empty() {
+            if matches!(&*upper, "CREATE" | "ALTER") {
+                self.saw_create_alter = true;
+            } else if upper == "TABLE" && self.saw_create_alter {
+                self.expect_def_list_paren = true;
+                self.saw_create_alter = false;
+            } else if !is_ddl_modifier(&upper) {
+                self.saw_create_alter = false;
+            }
+        }
+
+        // Check for join modifier + JOIN compound
+        if is_join_modifier(&upper) {
+            if let Some(j) = next_significant_token(&self.tokens, i) {
+                if let Token::Keyword(next_kw) = self.tokens[j] {
+                    if next_kw.to_uppercase() == "JOIN" {
+                        return self.clause(&format!("{} {}", opts.keyword(kw), opts.keyword(next_kw)), j + 1);
                     }
                 }
+            }
+            self.inline_keyword(&opts.keyword(kw));
+            return i + 1;
+        }
 
-                // Major clause keywords
-                if is_clause_keyword(&upper) {
-                    emit_clause_line(&mut out, &mut line_started, &mut need_blank_line, &mut in_clause_content, base_indent, &upper);
-                    in_clause_content = true;
-                    last_was_keyword = true;
-                    i += 1;
-                } else if upper == "AND" || upper == "OR" {
-                    if line_started {
-                        out.push('\n');
+        // Check for compound keywords: GROUP BY, ORDER BY, INSERT INTO, DELETE FROM
+        if is_compound_first(&upper) {
+            if let Some(j) = next_significant_token(&self.tokens, i) {
+                if let Token::Keyword(next_kw) = self.tokens[j] {
+                    if is_compound_second(&next_kw.to_uppercase()) {
+                        return self.clause(&format!("{} {}", opts.keyword(kw), opts.keyword(next_kw)), j + 1);
                     }
-                    out.push_str(&indent_str(base_indent + 1));
-                    out.push_str(&upper);
-                    line_started = true;
-                    last_was_keyword = true;
-                    i += 1;
-                } else {
-                    emit_inline_keyword(&mut out, &mut line_started, &mut last_was_keyword, base_indent, in_clause_content, &upper);
-                    i += 1;
                 }
             }
-            Token::Comma => {
-                if in_inline {
-                    out.push(',');
-                    line_started = true;
-                } else {
-                    // Clause-level or DefList: comma at end, newline, next item re-indents
-                    out.push(',');
-                    out.push('\n');
-                    line_started = false;
-                }
-                last_was_keyword = false;
-                prev_was_value = false;
-                attach_next = false;
-                i += 1;
+        }
+
+        // Major clause keywords
+        if is_clause_keyword(&upper) {
+            self.clause(&opts.keyword(kw), i + 1)
+        } else if upper == "AND" || upper == "OR" {
+            if self.line_started {
+                self.out.push('\n');
             }
-            Token::Semicolon => {
-                out.push(';');
-                out.push('\n');
-                line_started = false;
-                in_clause_content = false;
-                need_blank_line = true;
-                last_was_keyword = false;
-                saw_create_alter = false;
-                expect_def_list_paren = false;
-                prev_was_value = false;
-                attach_next = false;
-                i += 1;
+            self.out.push_str(&opts.indent(self.base_indent + 1));
+            self.out.push_str(&opts.keyword(kw));
+            self.line_started = true;
+            self.last_was_keyword = true;
+            i + 1
+        } else {
+            self.inline_keyword(&opts.keyword(kw));
+            i + 1
+        }
+    }
+
+    // Emits a clause keyword line. With `max_line_width`, a clause whose
+    // content fits stays on the keyword's line; returns the next index.
+    fn clause(&mut self, clause_text: &str, next: usize) -> usize {
+        if self.line_started {
+            self.out.push('\n');
+        }
+        self.out.push_str(&self.opts.indent(self.base_indent));
+        self.out.push_str(clause_text);
+        self.in_clause_content = true;
+        self.last_was_keyword = true;
+
+        if let Some(end) = self.single_line_clause_end(next) {
+            let flat = render_flat(&self.tokens[next..end], self.opts);
+            self.out.push(' ');
+            self.out.push_str(&flat);
+            self.line_started = true;
+            self.last_was_keyword = false;
+            self.prev_was_value = true;
+            self.attach_next = false;
+            return end;
+        }
+
+        self.out.push('\n');
+        self.line_started = false;
+        next
+    }
+
+    fn single_line_clause_end(&self, start: usize) -> Option<usize> {
+        let max_width = self.opts.max_line_width?;
+        let end = clause_content_end(&self.tokens, start)?;
+        if end == start {
+            return None;
+        }
+        let flat = render_flat(&self.tokens[start..end], self.opts);
+        let trailing = usize::from(matches!(self.tokens.get(end), Some(Token::Semicolon)));
+        let width = self.column() + 1 + flat.chars().count() + trailing;
+        (width <= max_width).then_some(end)
+    }
+
+    fn inline_keyword(&mut self, text: &str) {
+        if !self.line_started {
+            self.out.push_str(&self.opts.indent(self.line_indent()));
+        } else {
+            self.out.push(' ');
+        }
+        self.out.push_str(text);
+        self.line_started = true;
+        self.last_was_keyword = true;
+    }
+
+    fn comma(&mut self, i: usize) -> usize {
+        let mut next = i + 1;
+        if self.in_mode(ParenMode::Inline) || self.in_mode(ParenMode::WrappedClauses) {
+            self.out.push(',');
+            self.line_started = true;
+        } else if self.opts.comma_style == CommaStyle::Leading {
+            // Clause-level, DefList or Wrapped: comma starts the next item's
+            // line (`  , name`). A comment after the comma annotates the item
+            // before it, so it stays on that item's line.
+            while self.line_started
+                && next < self.tokens.len()
+                && self.is_trailing_comment(next)
+                && !self.tokens[next].text().contains('\n')
+            {
+                self.out.push(' ');
+                self.out.push_str(self.tokens[next].text());
+                next += 1;
             }
-            Token::OpenParen => {
-                let mode = if paren_contains_subquery(&filtered, i) {
-                    ParenMode::Subquery
-                } else if expect_def_list_paren && paren_stack.is_empty() {
-                    ParenMode::DefList
+            if self.line_started {
+                self.out.push('\n');
+            }
+            // Comments on the lines before the next item head it; the comma
+            // waits for them.
+            if matches!(self.tokens.get(next), Some(Token::Comment(_))) && !self.is_verbatim(next) {
+                self.line_started = false;
+                self.leading_comma = true;
+            } else {
+                self.out.push_str(&self.opts.indent(self.line_indent()));
+                self.out.push(',');
+                self.line_started = true;
+            }
+        } else if self.attach_before_comments(",", i) {
+            self.line_started = false;
+        } else {
+            // Clause-level, DefList or Wrapped: comma at end, newline, next
+            // item re-indents. After a `{% ... %}` tag it starts a line of
+            // its own, at the block's indent.
+            if !self.line_started {
+                self.out.push_str(&self.opts.indent(self.line_indent()));
+            }
+            self.out.push(',');
+            self.out.push('\n');
+            self.line_started = false;
+        }
+        self.last_was_keyword = false;
+        self.prev_was_value = false;
+        self.attach_next = false;
+        next
+    }
+
+    // Whether token `i` comes right after a `{% ... %}` tag on its own line.
+    fn follows_template_line(&self, i: usize) -> bool {
+        i > 0 && matches!(self.tokens[i - 1], Token::Template(t) if is_template_line(t))
+    }
+
+    fn semicolon(&mut self, i: usize) -> usize {
+        // `{% endif %};` closes the statement the block is part of.
+        if self.follows_template_line(i) && !self.line_started && self.out.ends_with('\n') {
+            self.out.pop();
+        }
+        if !self.attach_before_comments(";", i) {
+            self.out.push(';');
+            self.out.push('\n');
+        }
+        self.line_started = false;
+        self.in_clause_content = false;
+        self.need_blank_line = true;
+        self.last_was_keyword = false;
+        self.saw_create_alter = false;
+        self.expect_def_list_paren = false;
+        self.prev_was_value = false;
+        self.attach_next = false;
+        i + 1
+    }
+
+    fn push_paren(&mut self, mode: ParenMode) {
+        self.paren_stack.push(ParenCtx {
+            saved_base_indent: self.base_indent,
+            saved_in_clause_content: self.in_clause_content,
+            mode,
+        });
+    }
+
+    fn open_paren(&mut self, i: usize) -> usize {
+        let opts = self.opts;
+        let mode = if paren_contains_subquery(&self.tokens, i) {
+            ParenMode::Subquery
+        } else if self.expect_def_list_paren && self.paren_stack.is_empty() {
+            ParenMode::DefList
+        } else if self.inline_group_overflows(i) {
+            let close = matching_close_paren(&self.tokens, i).unwrap_or(i);
+            if (i + 1..close).any(|j| starts_by_clause(&self.tokens, j)) {
+                ParenMode::WrappedClauses
+            } else {
+                ParenMode::Wrapped
+            }
+        } else {
+            ParenMode::Inline
+        };
+        self.expect_def_list_paren = false;
+
+        match mode {
+            ParenMode::Subquery => {
+                let paren_indent = self.line_indent();
+                if !self.line_started {
+                    self.out.push_str(&opts.indent(paren_indent));
                 } else {
-                    ParenMode::Inline
-                };
-                expect_def_list_paren = false;
-
-                match mode {
-                    ParenMode::Subquery => {
-                        let paren_indent = if in_clause_content { base_indent + 1 } else { base_indent };
-                        if !line_started {
-                            out.push_str(&indent_str(paren_indent));
-                        } else {
-                            out.push(' ');
-                        }
-                        out.push('(');
-                        out.push('\n');
-                        paren_stack.push(ParenCtx {
-                            saved_base_indent: base_indent,
-                            saved_in_clause_content: in_clause_content,
-                            mode,
-                        });
-                        base_indent = paren_indent + 1;
-                        in_clause_content = false;
-                        line_started = false;
-                    }
-                    ParenMode::DefList => {
-                        if !line_started {
-                            out.push_str(&indent_str(base_indent));
-                        } else {
-                            out.push(' ');
-                        }
-                        out.push('(');
-                        out.push('\n');
-                        paren_stack.push(ParenCtx {
-                            saved_base_indent: base_indent,
-                            saved_in_clause_content: in_clause_content,
-                            mode,
-                        });
-                        base_indent += 1;
-                        in_clause_content = false;
-                        line_started = false;
-                    }
-                    ParenMode::Inline => {
-                        if !line_started {
-                            out.push_str(&indent_str(if in_clause_content { base_indent + 1 } else { base_indent }));
-                        } else if !last_was_keyword && !ends_in_word_like(&out) {
-                            out.push(' ');
-                        }
-                        out.push('(');
-                        paren_stack.push(ParenCtx {
-                            saved_base_indent: base_indent,
-                            saved_in_clause_content: in_clause_content,
-                            mode,
-                        });
-                        line_started = true;
-                    }
+                    self.out.push(' ');
                 }
-                last_was_keyword = false;
-                prev_was_value = false;
-                attach_next = false;
-                i += 1;
+                self.out.push('(');
+                self.out.push('\n');
+                self.push_paren(mode);
+                self.base_indent = paren_indent + 1;
+                self.in_clause_content = false;
+                self.line_started = false;
             }
-            Token::CloseParen => {
-                match paren_stack.pop() {
-                    Some(ctx) => match ctx.mode {
-                        ParenMode::Inline => {
-                            out.push(')');
-                            line_started = true;
-                        }
-                        ParenMode::Subquery => {
-                            if line_started {
-                                out.push('\n');
-                            }
-                            out.push_str(&indent_str(ctx.saved_base_indent + 1));
-                            out.push(')');
-                            base_indent = ctx.saved_base_indent;
-                            in_clause_content = ctx.saved_in_clause_content;
-                            line_started = true;
-                        }
-                        ParenMode::DefList => {
-                            if line_started {
-                                out.push('\n');
-                            }
-                            out.push_str(&indent_str(ctx.saved_base_indent));
-                            out.push(')');
-                            base_indent = ctx.saved_base_indent;
-                            in_clause_content = ctx.saved_in_clause_content;
-                            line_started = true;
-                        }
-                    },
-                    None => {
-                        out.push(')');
-                        line_started = true;
-                    }
+            ParenMode::DefList => {
+                if !self.line_started {
+                    self.out.push_str(&opts.indent(self.base_indent));
+                } else {
+                    self.out.push(' ');
                 }
-                last_was_keyword = false;
-                prev_was_value = true;
-                attach_next = false;
-                i += 1;
+                self.out.push('(');
+                self.out.push('\n');
+                self.push_paren(mode);
+                self.base_indent += 1;
+                self.in_clause_content = false;
+                self.line_started = false;
             }
-            Token::Operator(op) => {
-                if op == "." {
-                    out.push('.');
-                    line_started = true;
-                    last_was_keyword = false;
-                    attach_next = false;
-                    // prev_was_value stays as-is so the next identifier after
-                    // `.` is emitted without a leading space (handled by the
-                    // `out.ends_with('.')` check).
-                    i += 1;
-                    continue;
+            ParenMode::Inline | ParenMode::Wrapped | ParenMode::WrappedClauses => {
+                let line_indent = self.line_indent();
+                if !self.line_started {
+                    self.out.push_str(&opts.indent(line_indent));
+                } else if !self.last_was_keyword && !ends_in_word_like(&self.out) {
+                    self.out.push(' ');
                 }
+                self.out.push('(');
+                self.push_paren(mode);
+                if mode != ParenMode::Inline {
+                    self.out.push('\n');
+                    self.base_indent = line_indent + 1;
+                    self.in_clause_content = false;
+                    self.line_started = false;
+                } else {
+                    self.line_started = true;
+                }
+            }
+        }
+        self.last_was_keyword = false;
+        self.prev_was_value = false;
+        self.attach_next = false;
+        i + 1
+    }
 
-                let is_unary = matches!(op.as_str(), "-" | "+") && !prev_was_value;
+    // With `max_line_width`, checks whether the inline group opening at `i`
+    // would run past the limit if kept on the current line.
+    fn inline_group_overflows(&self, i: usize) -> bool {
+        let Some(max_width) = self.opts.max_line_width else {
+            return false;
+        };
+        let Some(close) = matching_close_paren(&self.tokens, i) else {
+            return false;
+        };
+        let group = &self.tokens[i..=close];
+        if close == i + 1 || group.iter().any(|t| matches!(t, Token::Comment(_))) {
+            return false;
+        }
+        if (i + 1..close).any(|j| matches!(self.tokens[j], Token::OpenParen) && paren_contains_subquery(&self.tokens, j)) {
+            return false;
+        }
 
-                if in_inline {
-                    if !out.ends_with('(') {
-                        out.push(' ');
-                    }
-                } else if line_started {
-                    out.push(' ');
-                } else if in_clause_content {
-                    out.push_str(&indent_str(base_indent + 1));
-                } else {
-                    out.push_str(&indent_str(base_indent));
+        let start = if !self.line_started {
+            self.opts.indent(self.line_indent()).chars().count()
+        } else if !self.last_was_keyword && !ends_in_word_like(&self.out) {
+            self.column() + 1
+        } else {
+            self.column()
+        };
+        let trailing = usize::from(matches!(self.tokens.get(close + 1), Some(Token::Comma | Token::Semicolon)));
+        start + render_flat(group, self.opts).chars().count() + trailing > max_width
+    }
+
+    fn close_paren(&mut self, i: usize) -> usize {
+        let opts = self.opts;
+        match self.paren_stack.pop() {
+            Some(ctx) => match ctx.mode {
+                ParenMode::Inline => {
+                    self.out.push(')');
                 }
-                out.push_str(op);
-                line_started = true;
-                last_was_keyword = false;
-                attach_next = is_unary;
-                prev_was_value = false;
-                i += 1;
-            }
-            _ => {
-                let text = match token {
-                    Token::Identifier(s) | Token::StringLiteral(s) | Token::NumberLiteral(s) | Token::Other(s) => s.as_str(),
-                    _ => unreachable!(),
-                };
-
-                if attach_next {
-                    out.push_str(text);
-                    line_started = true;
-                } else if in_inline {
-                    if line_started && !out.ends_with('(') && !out.ends_with('.') {
-                        out.push(' ');
+                ParenMode::Subquery => {
+                    if self.line_started {
+                        self.out.push('\n');
                     }
-                    out.push_str(text);
-                    line_started = true;
-                } else if !line_started {
-                    if in_clause_content {
-                        out.push_str(&indent_str(base_indent + 1));
-                    } else {
-                        out.push_str(&indent_str(base_indent));
-                    }
-                    out.push_str(text);
-                    line_started = true;
-                } else {
-                    if !out.ends_with('.') {
-                        out.push(' ');
+                    self.out.push_str(&opts.indent(ctx.saved_base_indent + 1));
+                    self.out.push(')');
+                    self.base_indent = ctx.saved_base_indent;
+                    self.in_clause_content = ctx.saved_in_clause_content;
+                }
+                ParenMode::DefList | ParenMode::Wrapped | ParenMode::WrappedClauses => {
+                    if self.line_started {
+                        self.out.push('\n');
                     }
-                    out.push_str(text);
+                    self.base_indent = ctx.saved_base_indent;
+                    self.in_clause_content = ctx.saved_in_clause_content;
+                    let close_indent = if ctx.mode == ParenMode::DefList { self.base_indent } else { self.line_indent() };
+                    self.out.push_str(&opts.indent(close_indent));
+                    self.out.push(')');
                 }
-                last_was_keyword = false;
-                prev_was_value = true;
-                attach_next = false;
-                i += 1;
+            },
+            None => {
+                self.out.push(')');
             }
         }
+        self.line_started = true;
+        self.last_was_keyword = false;
+        self.prev_was_value = true;
+        self.attach_next = false;
+        i + 1
     }
 
-    out
-}
+    fn operator(&mut self, op: &str, i: usize) -> usize {
+        if is_accessor(op) {
+            if i > 0 && would_merge(Some(self.tokens[i - 1]), self.tokens[i]) {
+                self.out.push(' ');
+            }
+            self.out.push_str(op);
+            self.line_started = true;
+            self.last_was_keyword = false;
+            self.attach_next = false;
+            // prev_was_value stays as-is so the next identifier after
+            // `.` is emitted without a leading space (handled by the
+            // `ends_with_accessor(&out)` check).
+            return i + 1;
+        }
 
-fn emit_clause_line(out: &mut String, line_started: &mut bool, need_blank_line: &mut bool, in_clause_content: &mut bool, base_indent: usize, clause_text: &str) {
-    if *need_blank_line {
-        out.push('\n');
-        *need_blank_line = false;
+        let is_unary = matches!(op, "-" | "+") && !self.prev_was_value;
+
+        if self.in_mode(ParenMode::Inline) {
+            if !self.out.ends_with('(') {
+                self.out.push(' ');
+            }
+        } else if self.line_started {
+            self.out.push(' ');
+        } else {
+            self.out.push_str(&self.opts.indent(self.line_indent()));
+        }
+        self.out.push_str(op);
+        self.line_started = true;
+        self.last_was_keyword = false;
+        self.attach_next = is_unary;
+        self.prev_was_value = false;
+        i + 1
+    }
+
+    // Array subscripts (`arr[1]`) where `[` doesn't quote an identifier:
+    // no space around the brackets.
+    fn subscript(&mut self, bracket: &str, i: usize) -> usize {
+        if !self.line_started {
+            return self.value(bracket, i);
+        }
+        self.out.push_str(bracket);
+        self.last_was_keyword = false;
+        self.prev_was_value = bracket == "]";
+        self.attach_next = bracket == "[";
+        i + 1
     }
-    if *line_started {
+
+    // True if the statement around token `i` declares `LANGUAGE sql`.
+    fn statement_language_is_sql(&self, i: usize) -> bool {
+        let start = self.tokens[..i].iter().rposition(|t| matches!(t, Token::Semicolon)).map_or(0, |p| p + 1);
+        let end = self.tokens[i..].iter().position(|t| matches!(t, Token::Semicolon)).map_or(self.tokens.len(), |p| i + p);
+        let statement = &self.tokens[start..end];
+        statement.windows(2).any(|pair| {
+            pair[0].text().eq_ignore_ascii_case("LANGUAGE")
+                && pair[1].text().trim_matches('\'').eq_ignore_ascii_case("sql")
+        })
+    }
+
+    // A formatted `$$ ... $$` body: the tags stay on the current line and
+    // the body is indented one level below it.
+    fn formatted_sql_body(&self, text: &str) -> Option<String> {
+        let tag_len = text[1..].find('$')? + 2;
+        if text.len() < 2 * tag_len || !text.ends_with(&text[..tag_len]) {
+            return None;
+        }
+        let tag = &text[..tag_len];
+        let body = &text[tag_len..text.len() - tag_len];
+        let formatted = beautify_with(&crate::tokenizer::tokenize(body), self.opts);
+        let line_indent = self.line_indent();
+        let mut out = String::from(tag);
         out.push('\n');
+        for line in formatted.trim_end().lines() {
+            if !line.is_empty() {
+                out.push_str(&self.opts.indent(line_indent + 1));
+                out.push_str(line);
+            }
+            out.push('\n');
+        }
+        out.push_str(&self.opts.indent(line_indent));
+        out.push_str(tag);
+        Some(out)
     }
-    out.push_str(&indent_str(base_indent));
-    out.push_str(clause_text);
-    out.push('\n');
-    *line_started = false;
-    *in_clause_content = false;
-}
 
-fn emit_inline_keyword(out: &mut String, line_started: &mut bool, last_was_keyword: &mut bool, base_indent: usize, in_clause_content: bool, upper: &str) {
-    if !*line_started {
-        if in_clause_content {
-            out.push_str(&indent_str(base_indent + 1));
+    fn value(&mut self, text: &str, i: usize) -> usize {
+        let formatted_body = if self.opts.format_sql_bodies
+            && text.starts_with('$')
+            && matches!(self.tokens[i], Token::StringLiteral(_))
+            && self.statement_language_is_sql(i)
+        {
+            self.formatted_sql_body(text)
         } else {
-            out.push_str(&indent_str(base_indent));
+            None
+        };
+        let text = formatted_body.as_deref().unwrap_or(text);
+        let merges = i > 0 && would_merge(Some(self.tokens[i - 1]), self.tokens[i]);
+
+        if self.attach_next {
+            if merges {
+                self.out.push(' ');
+            }
+            self.out.push_str(text);
+        } else if self.in_mode(ParenMode::Inline) {
+            if self.line_started && !self.out.ends_with('(') && (!ends_with_accessor(&self.out) || merges) {
+                self.out.push(' ');
+            }
+            self.out.push_str(text);
+        } else if !self.line_started {
+            self.out.push_str(&self.opts.indent(self.line_indent()));
+            self.out.push_str(text);
+        } else {
+            if !ends_with_accessor(&self.out) || merges {
+                self.out.push(' ');
+            }
+            self.out.push_str(text);
         }
-    } else {
-        out.push(' ');
+        self.line_started = true;
+        self.last_was_keyword = false;
+        self.prev_was_value = true;
+        self.attach_next = false;
+        i + 1
     }
-    out.push_str(upper);
-    *line_started = true;
-    *last_was_keyword = true;
 }
 
 #[derive(Clone, Copy, PartialEq)]
@@ -473,19 +1373,29 @@ fn needs_space(prev: PrevToken, token: &Token) -> bool {
     if prev == PrevToken::Dot {
         return false;
     }
+    if matches!(token, Token::Other(o) if o == "[" || o == "]") {
+        return false;
+    }
     match token {
         Token::Keyword(_) => matches!(
             prev,
             PrevToken::Keyword | PrevToken::Word | PrevToken::Operator | PrevToken::CloseParen
         ),
-        Token::Identifier(_) | Token::StringLiteral(_) | Token::NumberLiteral(_) | Token::Other(_) => {
+        Token::Identifier(_)
+        | Token::StringLiteral(_)
+        | Token::NumberLiteral(_)
+        | Token::Placeholder(_)
+        | Token::Template(_)
+        | Token::Other(_) => {
             matches!(
                 prev,
                 PrevToken::Keyword | PrevToken::Word | PrevToken::CloseParen
             )
         }
-        Token::Operator(op) if op == "." => false,
-        Token::Operator(_) => prev == PrevToken::Keyword,
+        Token::Operator(op) if is_accessor(op) => false,
+        // `- -1` and `/ *` must not run together into a comment (or into a
+        // different operator).
+        Token::Operator(_) => matches!(prev, PrevToken::Keyword | PrevToken::Operator),
         _ => false,
     }
 }
@@ -497,6 +1407,8 @@ pub struct Palette {
     pub number: &'static str,
     pub operator: &'static str,
     pub comment: &'static str,
+    pub placeholder: &'static str,
+    pub template: &'static str,
     pub punct: &'static str,
     pub reset: &'static str,
 }
@@ -510,6 +1422,8 @@ impl Palette {
             number: "\x1b[33m",
             operator: "",
             comment: "\x1b[2m",
+            placeholder: "\x1b[35m",
+            template: "\x1b[34m",
             punct: "",
             reset: "\x1b[0m",
         }
@@ -523,6 +1437,8 @@ impl Palette {
             number: "",
             operator: "",
             comment: "",
+            placeholder: "",
+            template: "",
             punct: "",
             reset: "",
         }
@@ -530,96 +1446,140 @@ impl Palette {
 }
 
 pub fn colorize(formatted: &str, palette: &Palette) -> String {
-    let tokens = crate::tokenizer::tokenize(formatted);
+    colorize_with(formatted, palette, Dialect::Generic)
+}
+
+/// Like `colorize`, re-tokenizing the formatted text with `dialect`.
+pub fn colorize_with(formatted: &str, palette: &Palette, dialect: Dialect) -> String {
     let mut out = String::with_capacity(formatted.len());
-    for token in tokens {
+    for token in crate::tokenizer::tokens_with(formatted, dialect) {
         match token {
-            Token::Keyword(kw) => {
+            TokenRef::Keyword(kw) => {
                 out.push_str(palette.keyword);
-                out.push_str(&kw);
+                out.push_str(kw);
                 out.push_str(palette.reset);
             }
-            Token::Identifier(id) => {
+            TokenRef::Identifier(id) => {
                 out.push_str(palette.identifier);
-                out.push_str(&id);
+                out.push_str(id);
                 if !palette.identifier.is_empty() {
                     out.push_str(palette.reset);
                 }
             }
-            Token::StringLiteral(s) => {
+            TokenRef::StringLiteral(s) => {
                 out.push_str(palette.string);
-                out.push_str(&s);
+                out.push_str(s);
                 out.push_str(palette.reset);
             }
-            Token::NumberLiteral(n) => {
+            TokenRef::NumberLiteral(n) => {
                 out.push_str(palette.number);
-                out.push_str(&n);
+                out.push_str(n);
                 out.push_str(palette.reset);
             }
-            Token::Operator(op) => {
+            TokenRef::Operator(op) => {
                 out.push_str(palette.operator);
-                out.push_str(&op);
+                out.push_str(op);
                 if !palette.operator.is_empty() {
                     out.push_str(palette.reset);
                 }
             }
-            Token::Comment(c) => {
+            TokenRef::Comment(c) => {
                 out.push_str(palette.comment);
-                out.push_str(&c);
+                out.push_str(c);
                 out.push_str(palette.reset);
             }
-            Token::Comma => {
+            TokenRef::Comma => {
                 out.push_str(palette.punct);
                 out.push(',');
                 if !palette.punct.is_empty() {
                     out.push_str(palette.reset);
                 }
             }
-            Token::Semicolon => {
+            TokenRef::Semicolon => {
                 out.push_str(palette.punct);
                 out.push(';');
                 if !palette.punct.is_empty() {
                     out.push_str(palette.reset);
                 }
             }
-            Token::OpenParen => {
+            TokenRef::OpenParen => {
                 out.push_str(palette.punct);
                 out.push('(');
                 if !palette.punct.is_empty() {
                     out.push_str(palette.reset);
                 }
             }
-            Token::CloseParen => {
+            TokenRef::CloseParen => {
                 out.push_str(palette.punct);
                 out.push(')');
                 if !palette.punct.is_empty() {
                     out.push_str(palette.reset);
                 }
             }
-            Token::Whitespace(ws) => {
-                out.push_str(&ws);
+            TokenRef::Placeholder(p) => {
+                out.push_str(palette.placeholder);
+                out.push_str(p);
+                if !palette.placeholder.is_empty() {
+                    out.push_str(palette.reset);
+                }
             }
-            Token::Other(o) => {
-                out.push_str(&o);
+            TokenRef::Template(t) => {
+                out.push_str(palette.template);
+                out.push_str(t);
+                if !palette.template.is_empty() {
+                    out.push_str(palette.reset);
+                }
+            }
+            TokenRef::Whitespace(ws) => {
+                out.push_str(ws);
+            }
+            TokenRef::Other(o) => {
+                out.push_str(o);
             }
         }
     }
     out
 }
 
+/// Minifies `tokens`, except that `sqlfmt: off` regions are copied verbatim.
 pub fn minify(tokens: &[Token]) -> String {
     let mut out = String::new();
     let mut prev = PrevToken::None;
+    let mut prev_token: Option<&Token> = None;
+    let ranges = verbatim_ranges(tokens);
+    let mut next_region = 0;
+    let mut i = 0;
 
-    for token in tokens {
+    while i < tokens.len() {
+        if let Some(range) = ranges.get(next_region).filter(|r| r.start == i) {
+            if !out.is_empty() {
+                out.push(' ');
+            }
+            let region = &tokens[range.clone()];
+            out.push_str(&verbatim_text(region));
+            // A trailing line comment would swallow whatever comes next.
+            prev = match region.last() {
+                Some(Token::Comment(c)) if !c.starts_with("/*") => {
+                    out.push('\n');
+                    PrevToken::None
+                }
+                _ => PrevToken::Word,
+            };
+            next_region += 1;
+            i = range.end;
+            continue;
+        }
+        let token = &tokens[i];
+        i += 1;
         match token {
             Token::Whitespace(_) | Token::Comment(_) => continue,
             _ => {}
         }
 
-        if needs_space(prev, token) {
+        if needs_space(prev, token) || would_merge(prev_token, token) {
             out.push(' ');
         }
+        prev_token = Some(token);
 
         match token {
             Token::Keyword(kw) => {
@@ -638,9 +1598,17 @@ pub fn minify(tokens: &[Token]) -> String {
                 out.push_str(n);
                 prev = PrevToken::Word;
             }
+            Token::Placeholder(p) => {
+                out.push_str(p);
+                prev = PrevToken::Word;
+            }
+            Token::Template(t) => {
+                out.push_str(t);
+                prev = PrevToken::Word;
+            }
             Token::Operator(op) => {
                 out.push_str(op);
-                prev = if op == "." { PrevToken::Dot } else { PrevToken::Operator };
+                prev = if is_accessor(op) { PrevToken::Dot } else { PrevToken::Operator };
             }
             Token::Comma => {
                 out.push(',');
@@ -660,7 +1628,7 @@ pub fn minify(tokens: &[Token]) -> String {
             }
             Token::Other(o) => {
                 out.push_str(o);
-                prev = PrevToken::Word;
+                prev = if o == "[" { PrevToken::OpenParen } else { PrevToken::Word };
             }
             Token::Whitespace(_) | Token::Comment(_) => unreachable!(),
         }
@@ -1,2 +1,14 @@
+pub mod classify;
+pub mod config;
+pub mod dialect;
+pub mod diff;
+pub mod embedded;
+pub mod files;
 pub mod formatter;
+pub mod json;
+pub mod lsp;
+pub mod markdown;
+pub mod parser;
+pub mod splitter;
 pub mod tokenizer;
+pub mod verify;
@@ -0,0 +1,429 @@
+use crate::config::Config;
+use crate::dialect::Dialect;
+use crate::formatter::{beautify_with, FormatOptions};
+use crate::json::{self, Value};
+use crate::splitter::{split_statements_with, statement_range};
+use crate::tokenizer::{token_errors, tokenize_with, tokens_with, TokenRef};
+use crate::verify::verify_format;
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+// Semantic token types, in the order of the legend sent to the client.
+const TOKEN_TYPES: &[&str] = &["keyword", "variable", "string", "number", "operator", "comment", "parameter", "macro"];
+
+// JSON-RPC error codes.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const REQUEST_FAILED: i64 = -32803;
+
+type Reply = Result<Value, (i64, String)>;
+
+/// Runs a language server over `input` and `output` (stdin and stdout for
+/// `sqlfmt lsp`) until the client sends `exit` or closes `input`.
+/// `config_for` gives the settings for documents in a directory, and
+/// `verify` checks every edit as `--verify` does. Returns whether the client
+/// asked the server to shut down first, which decides the exit code.
+pub fn serve<R, W, F>(mut input: R, output: W, verify: bool, config_for: F) -> io::Result<bool>
+where
+    R: BufRead,
+    W: Write,
+    F: FnMut(&Path) -> Result<Config, String>,
+{
+    let mut server = Server { output, verify, config_for, documents: HashMap::new(), shutdown: false };
+    while let Some(body) = read_message(&mut input)? {
+        match json::parse(&body) {
+            Ok(message) => {
+                if !server.handle(&message)? {
+                    break;
+                }
+            }
+            Err(e) => server.send(&response(Value::Null, Err((PARSE_ERROR, e))))?,
+        }
+    }
+    Ok(server.shutdown)
+}
+
+// Reads one `Content-Length` framed message; None at the end of input.
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut length = None;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let length = length.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing Content-Length header"))?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    Ok(Some(String::from_utf8_lossy(&body).into_owned()))
+}
+
+struct Document {
+    text: String,
+    version: Option<Value>,
+}
+
+struct Server<W, F> {
+    output: W,
+    verify: bool,
+    config_for: F,
+    // Open documents by URI.
+    documents: HashMap<String, Document>,
+    shutdown: bool,
+}
+
+impl<W: Write, F: FnMut(&Path) -> Result<Config, String>> Server<W, F> {
+    fn send(&mut self, message: &Value) -> io::Result<()> {
+        let body = message.to_string();
+        write!(self.output, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+        self.output.flush()
+    }
+
+    // Handles one message; false once the client has sent `exit`.
+    fn handle(&mut self, message: &Value) -> io::Result<bool> {
+        let params = message.get("params").unwrap_or(&Value::Null);
+        match (message.get("method").and_then(Value::as_str), message.get("id")) {
+            (Some("exit"), _) => return Ok(false),
+            (Some(method), Some(id)) => {
+                let reply = if self.shutdown {
+                    Err((INVALID_REQUEST, "the server is shutting down".to_string()))
+                } else {
+                    self.request(method, params)
+                };
+                self.send(&response(id.clone(), reply))?;
+            }
+            (Some(method), None) => self.notification(method, params)?,
+            // A response; the server sends no requests.
+            (None, _) => {}
+        }
+        Ok(true)
+    }
+
+    fn request(&mut self, method: &str, params: &Value) -> Reply {
+        match method {
+            "initialize" => Ok(initialize_result()),
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Value::Null)
+            }
+            "textDocument/formatting" => self.formatting(params, None),
+            "textDocument/rangeFormatting" => {
+                let range = params.get("range").ok_or_else(|| missing("range"))?;
+                self.formatting(params, Some(range))
+            }
+            "textDocument/onTypeFormatting" => self.on_type_formatting(params),
+            "textDocument/semanticTokens/full" => {
+                let (uri, text) = self.document(params)?;
+                let data = semantic_tokens(&text, self.dialect(&uri));
+                Ok(Value::object([("data", Value::Array(data))]))
+            }
+            _ => Err((METHOD_NOT_FOUND, format!("unknown method '{method}'"))),
+        }
+    }
+
+    fn notification(&mut self, method: &str, params: &Value) -> io::Result<()> {
+        let Some(uri) = params.pointer(&["textDocument", "uri"]).and_then(Value::as_str) else {
+            return Ok(());
+        };
+        let version = params.pointer(&["textDocument", "version"]).cloned();
+        match method {
+            "textDocument/didOpen" => {
+                let text = params.pointer(&["textDocument", "text"]).and_then(Value::as_str).unwrap_or_default();
+                self.documents.insert(uri.to_string(), Document { text: text.to_string(), version });
+                self.publish_diagnostics(uri)
+            }
+            "textDocument/didChange" => {
+                let Some(document) = self.documents.get_mut(uri) else {
+                    return Ok(());
+                };
+                for change in params.get("contentChanges").and_then(Value::as_array).unwrap_or_default() {
+                    let new_text = change.get("text").and_then(Value::as_str).unwrap_or_default();
+                    match change.get("range") {
+                        Some(range) => {
+                            let start = offset_at(&document.text, range.get("start"));
+                            let end = offset_at(&document.text, range.get("end")).max(start);
+                            document.text.replace_range(start..end, new_text);
+                        }
+                        None => document.text = new_text.to_string(),
+                    }
+                }
+                document.version = version;
+                self.publish_diagnostics(uri)
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+                let params = Value::object([("uri", uri.into()), ("diagnostics", Value::Array(Vec::new()))]);
+                self.send(&notification("textDocument/publishDiagnostics", params))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // The URI and a copy of the text of the document named in `params`.
+    fn document(&self, params: &Value) -> Result<(String, String), (i64, String)> {
+        let uri = params.pointer(&["textDocument", "uri"]).and_then(Value::as_str).ok_or_else(|| missing("textDocument.uri"))?;
+        let document = self.documents.get(uri).ok_or_else(|| (INVALID_PARAMS, format!("'{uri}' is not open")))?;
+        Ok((uri.to_string(), document.text.clone()))
+    }
+
+    fn config(&mut self, uri: &str) -> Result<Config, String> {
+        (self.config_for)(&document_dir(uri))
+    }
+
+    // Tokenizing goes on with the default dialect if the config is broken;
+    // formatting reports the error instead.
+    fn dialect(&mut self, uri: &str) -> Dialect {
+        self.config(uri).ok().and_then(|config| config.dialect).unwrap_or_default()
+    }
+
+    fn publish_diagnostics(&mut self, uri: &str) -> io::Result<()> {
+        let dialect = self.dialect(uri);
+        let Some(document) = self.documents.get(uri) else {
+            return Ok(());
+        };
+        let diagnostics = token_errors(&document.text, dialect)
+            .into_iter()
+            .map(|e| {
+                Value::object([
+                    ("range", range(&document.text, e.span.start, e.span.end)),
+                    ("severity", 1usize.into()),
+                    ("source", "sqlfmt".into()),
+                    ("message", e.message.into()),
+                ])
+            })
+            .collect();
+        let mut params = vec![("uri", Value::from(uri))];
+        if let Some(version) = &document.version {
+            params.push(("version", version.clone()));
+        }
+        params.push(("diagnostics", Value::Array(diagnostics)));
+        self.send(&notification("textDocument/publishDiagnostics", Value::object(params)))
+    }
+
+    // Formats the whole document, or the statements overlapping `range`.
+    fn formatting(&mut self, params: &Value, range: Option<&Value>) -> Reply {
+        let (uri, text) = self.document(params)?;
+        let config = self.config(&uri).map_err(|e| (REQUEST_FAILED, e))?;
+        let opts = format_options(&config, params.get("options"));
+        let dialect = config.dialect.unwrap_or_default();
+        let Some(range) = range else {
+            return self.edit(&text, 0, text.len(), dialect, &opts);
+        };
+        let start = offset_at(&text, range.get("start"));
+        let end = offset_at(&text, range.get("end")).max(start);
+        match statement_range(&text, start..end, dialect) {
+            Some(span) => self.edit(&text, span.start, span.end, dialect, &opts),
+            None => Ok(Value::Array(Vec::new())),
+        }
+    }
+
+    // Formats the statement closed by a `;` just typed.
+    fn on_type_formatting(&mut self, params: &Value) -> Reply {
+        let (uri, text) = self.document(params)?;
+        if params.get("ch").and_then(Value::as_str) != Some(";") {
+            return Ok(Value::Array(Vec::new()));
+        }
+        let config = self.config(&uri).map_err(|e| (REQUEST_FAILED, e))?;
+        let opts = format_options(&config, params.get("options"));
+        let dialect = config.dialect.unwrap_or_default();
+        let offset = offset_at(&text, params.get("position"));
+        // A `;` inside a string or a `BEGIN ... END` block closes nothing.
+        match split_statements_with(&text, dialect).iter().find(|s| s.span.end == offset && s.text.ends_with(';')) {
+            Some(statement) => self.edit(&text, statement.span.start, statement.span.end, dialect, &opts),
+            None => Ok(Value::Array(Vec::new())),
+        }
+    }
+
+    // The edits replacing `text[start..end]` with its formatted version:
+    // none if it is formatted already.
+    fn edit(&self, text: &str, start: usize, end: usize, dialect: Dialect, opts: &FormatOptions) -> Reply {
+        let original = &text[start..end];
+        let formatted = if self.verify {
+            verify_format(original, dialect, opts).map_err(|e| (REQUEST_FAILED, e.to_string()))?
+        } else {
+            beautify_with(&tokenize_with(original, dialect), opts)
+        };
+        // A whole document ends with one newline, as files do on the command
+        // line; a part of one is replaced by exactly its statements.
+        let mut formatted = formatted.trim_end().to_string();
+        if start == 0 && end == text.len() && !formatted.is_empty() {
+            formatted.push('\n');
+        }
+        if formatted == original {
+            return Ok(Value::Array(Vec::new()));
+        }
+        let edit = Value::object([("range", range(text, start, end)), ("newText", formatted.into())]);
+        Ok(Value::Array(vec![edit]))
+    }
+}
+
+fn missing(field: &str) -> (i64, String) {
+    (INVALID_PARAMS, format!("missing '{field}'"))
+}
+
+fn response(id: Value, reply: Reply) -> Value {
+    let outcome = match reply {
+        Ok(result) => ("result", result),
+        Err((code, message)) => ("error", Value::object([("code", code.into()), ("message", message.into())])),
+    };
+    Value::object([("jsonrpc", "2.0".into()), ("id", id), outcome])
+}
+
+fn notification(method: &str, params: Value) -> Value {
+    Value::object([("jsonrpc", "2.0".into()), ("method", method.into()), ("params", params)])
+}
+
+fn initialize_result() -> Value {
+    let legend = Value::object([
+        ("tokenTypes", Value::Array(TOKEN_TYPES.iter().map(|&t| t.into()).collect())),
+        ("tokenModifiers", Value::Array(Vec::new())),
+    ]);
+    let capabilities = Value::object([
+        // Full sync; changes with a range are applied as well.
+        ("textDocumentSync", 1usize.into()),
+        ("documentFormattingProvider", true.into()),
+        ("documentRangeFormattingProvider", true.into()),
+        ("documentOnTypeFormattingProvider", Value::object([("firstTriggerCharacter", ";".into())])),
+        ("semanticTokensProvider", Value::object([("legend", legend), ("full", true.into())])),
+    ]);
+    let server_info = Value::object([("name", "sqlfmt".into()), ("version", env!("CARGO_PKG_VERSION").into())]);
+    Value::object([("capabilities", capabilities), ("serverInfo", server_info)])
+}
+
+// The config's style, with the editor's indentation where the config
+// doesn't choose one.
+fn format_options(config: &Config, editor: Option<&Value>) -> FormatOptions {
+    let mut config = config.clone();
+    if let Some(editor) = editor {
+        config.indent_width = config.indent_width.or(editor.get("tabSize").and_then(Value::as_u64).map(|n| n as usize));
+        config.use_tabs = config.use_tabs.or(editor.get("insertSpaces").and_then(Value::as_bool).map(|spaces| !spaces));
+    }
+    config.format_options()
+}
+
+// The directory whose config applies to a document: the one holding a
+// `file://` document, or the working directory for anything else (such as
+// an unsaved buffer).
+fn document_dir(uri: &str) -> PathBuf {
+    let Some(path) = uri.strip_prefix("file://") else {
+        return PathBuf::from(".");
+    };
+    let path = percent_decode(path);
+    // `file:///C:/dir/x.sql`
+    let path = match path.strip_prefix('/') {
+        Some(rest) if cfg!(windows) => rest.to_string(),
+        _ => path,
+    };
+    Path::new(&path).parent().map_or_else(|| PathBuf::from("."), Path::to_path_buf)
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = s.get(i + 1..i + 3).and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                out.push(byte);
+                i += 3;
+            }
+            (b, _) => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+// The byte offset of an LSP position, whose column counts UTF-16 code units.
+// Positions past the end of a line or of the text are clamped to it.
+fn offset_at(text: &str, position: Option<&Value>) -> usize {
+    let field = |name| position.and_then(|p| p.get(name)).and_then(Value::as_u64).unwrap_or(0) as usize;
+    let (line, character) = (field("line"), field("character"));
+    let mut start = 0;
+    for _ in 0..line {
+        match text[start..].find('\n') {
+            Some(newline) => start += newline + 1,
+            None => return text.len(),
+        }
+    }
+    let line_text = &text[start..];
+    let line_text = &line_text[..line_text.find('\n').unwrap_or(line_text.len())];
+    let mut units = 0;
+    for (i, ch) in line_text.char_indices() {
+        if units >= character {
+            return start + i;
+        }
+        units += ch.len_utf16();
+    }
+    start + line_text.len()
+}
+
+fn position_at(text: &str, offset: usize) -> Value {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    let line = before.matches('\n').count();
+    let character = before[line_start..].encode_utf16().count();
+    Value::object([("line", line.into()), ("character", character.into())])
+}
+
+fn range(text: &str, start: usize, end: usize) -> Value {
+    Value::object([("start", position_at(text, start)), ("end", position_at(text, end))])
+}
+
+// Index of the semantic token type for a token, if it gets one.
+fn token_type(token: TokenRef) -> Option<usize> {
+    match token {
+        TokenRef::Keyword(_) => Some(0),
+        TokenRef::Identifier(_) => Some(1),
+        TokenRef::StringLiteral(_) => Some(2),
+        TokenRef::NumberLiteral(_) => Some(3),
+        TokenRef::Operator(_) => Some(4),
+        TokenRef::Comment(_) => Some(5),
+        TokenRef::Placeholder(_) => Some(6),
+        TokenRef::Template(_) => Some(7),
+        _ => None,
+    }
+}
+
+// Semantic tokens in the relative encoding of the protocol: five numbers per
+// token (line and column deltas, length, type, modifiers). Multi-line
+// tokens are sent one line at a time, as not every client takes them whole.
+fn semantic_tokens(text: &str, dialect: Dialect) -> Vec<Value> {
+    let mut data = Vec::new();
+    let (mut line, mut column) = (0, 0);
+    let (mut prev_line, mut prev_column) = (0, 0);
+    for token in tokens_with(text, dialect) {
+        let kind = token_type(token);
+        for (i, piece) in token.text().split('\n').enumerate() {
+            if i > 0 {
+                line += 1;
+                column = 0;
+            }
+            let length: usize = piece.trim_end_matches('\r').encode_utf16().count();
+            if let Some(kind) = kind.filter(|_| length > 0) {
+                let delta_column = if line == prev_line { column - prev_column } else { column };
+                data.extend([line - prev_line, delta_column, length, kind, 0].map(Value::from));
+                (prev_line, prev_column) = (line, column);
+            }
+            column += piece.encode_utf16().count();
+        }
+    }
+    data
+}
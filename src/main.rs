@@ -1,24 +1,94 @@
-use sqlfmt::formatter;
+use sqlfmt::config::{ColorWhen, Config};
+use sqlfmt::dialect::Dialect;
+use sqlfmt::diff;
+use sqlfmt::embedded::{self, Language};
+use sqlfmt::files;
+use sqlfmt::formatter::{self, CommaStyle, KeywordCase};
+use sqlfmt::lsp;
+use sqlfmt::markdown;
+use sqlfmt::splitter::{self, Chunk, StatementReader};
 use sqlfmt::tokenizer;
+use sqlfmt::verify::{self, VerifyError};
 
-use std::io::{self, IsTerminal, Read};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, IsTerminal, Read, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 use std::process;
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
-enum ColorWhen {
-    Auto,
-    Always,
-    Never,
+enum ConfigSource {
+    // Look for sqlfmt.toml next to each input and in its ancestors.
+    Discover,
+    File(PathBuf),
+    Disabled,
 }
 
 struct Cli {
-    sql: Option<String>,
+    // Either one SQL string, or files and directories to format.
+    inputs: Vec<String>,
     minify: bool,
-    color: ColorWhen,
+    write: bool,
+    check: bool,
+    diff: bool,
+    verify: bool,
+    // Format stdin statement by statement as it is read.
+    stream: bool,
+    // Run a language server on stdin and stdout (`sqlfmt lsp`).
+    lsp: bool,
+    // Format only the statements in this part of the input.
+    range: Option<RangeSpec>,
+    // Format the SQL strings in Python, Rust and Go files.
+    embedded: bool,
+    config: ConfigSource,
+    // Settings given as flags; they win over the config file.
+    overrides: Config,
+}
+
+#[derive(Clone, Copy)]
+enum RangeSpec {
+    // 1-based and inclusive.
+    Lines(usize, usize),
+    // 0-based, end exclusive.
+    Bytes(usize, usize),
+}
+
+impl RangeSpec {
+    // `[lines=|bytes=]START[:END]`; lines unless `bytes=` is given.
+    fn parse(value: &str) -> Result<RangeSpec, String> {
+        let invalid = || format!("invalid value '{value}' for '--range': expected [lines=|bytes=]START[:END]");
+        let (bytes, spec) = match value.split_once('=') {
+            Some(("lines", spec)) => (false, spec),
+            Some(("bytes", spec)) => (true, spec),
+            Some(_) => return Err(invalid()),
+            None => (false, value),
+        };
+        let (start, end) = spec.split_once(':').unwrap_or((spec, spec));
+        let start: usize = start.parse().map_err(|_| invalid())?;
+        let end: usize = end.parse().map_err(|_| invalid())?;
+        if end < start || (!bytes && start == 0) {
+            return Err(invalid());
+        }
+        Ok(if bytes { RangeSpec::Bytes(start, end) } else { RangeSpec::Lines(start, end) })
+    }
+
+    // The byte range of `input` this covers.
+    fn bytes(self, input: &str) -> Range<usize> {
+        match self {
+            RangeSpec::Bytes(start, end) => start.min(input.len())..end.min(input.len()),
+            RangeSpec::Lines(start, end) => {
+                let line_start = |line: usize| match line {
+                    1 => 0,
+                    _ => input.match_indices('\n').nth(line - 2).map_or(input.len(), |(i, _)| i + 1),
+                };
+                line_start(start)..line_start(end + 1)
+            }
+        }
+    }
 }
 
 enum Action {
-    Run(Cli),
+    Run(Box<Cli>),
     Help,
     Version,
 }
@@ -29,15 +99,40 @@ Format and beautify SQL
 
 USAGE:
     sqlfmt [OPTIONS] [SQL]
+    sqlfmt [OPTIONS] <PATH>...
+    sqlfmt lsp [OPTIONS]
+
+COMMANDS:
+    lsp          Run a language server (LSP) over stdin and stdout
 
 ARGS:
-    [SQL]    SQL string to format (reads from stdin if omitted)
+    [SQL]        SQL string to format (reads from stdin if omitted)
+    <PATH>...    Files to format; directories are searched for *.sql
+                 (and *.py, *.rs, *.go with --embedded)
 
 OPTIONS:
-    -m, --minify          Minify SQL instead of beautifying
-        --color <WHEN>    When to use ANSI color output [auto|always|never] (default: auto)
-    -h, --help            Print help
-    -V, --version         Print version
+    -m, --minify                Minify SQL instead of beautifying
+    -w, --write                 Rewrite files in place instead of printing them
+        --check                 Print inputs that are not formatted and exit 1 if there are any
+        --diff                  Print a unified diff of the changes instead of the output
+        --verify                Refuse to output a result that changes the query or is not stable
+        --stream                Format stdin one statement at a time as it is read
+        --range <RANGE>         Format only the statements in lines START:END (or bytes=START:END)
+        --embedded              Format SQL strings in Python, Rust and Go files
+        --color <WHEN>          When to use ANSI color output [auto|always|never] (default: auto)
+        --indent <N>            Spaces per indentation level (default: 2)
+        --tabs                  Indent with tabs instead of spaces
+        --keyword-case <CASE>   Keyword case [upper|lower|preserve] (default: upper)
+        --max-line-width <N>    Keep clauses on one line and wrap long groups to fit N columns
+        --commas <STYLE>        Comma position in broken lists [trailing|leading] (default: trailing)
+        --format-sql-bodies     Format dollar-quoted bodies of LANGUAGE sql functions
+        --max-blank-lines <N>   Blank lines kept in a row inside a statement (default: 1)
+        --dialect <NAME>        SQL dialect [generic|ansi|postgresql|mysql|sqlite|tsql|bigquery|snowflake]
+                                (default: generic)
+        --config <PATH>         Use this config file instead of searching for sqlfmt.toml
+        --no-config             Ignore config files
+    -h, --help                  Print help
+    -V, --version               Print version
 ";
 
 fn print_help() {
@@ -50,34 +145,110 @@ fn print_version() {
 
 fn print_usage_error(msg: &str) {
     eprintln!("sqlfmt: {msg}");
-    eprintln!("Usage: sqlfmt [OPTIONS] [SQL]");
+    eprintln!("Usage: sqlfmt [OPTIONS] [SQL | <PATH>...]");
     eprintln!("For more information, try '--help'.");
 }
 
-fn parse_color(value: &str) -> Result<ColorWhen, String> {
-    match value {
-        "auto" => Ok(ColorWhen::Auto),
-        "always" => Ok(ColorWhen::Always),
-        "never" => Ok(ColorWhen::Never),
-        other => Err(format!(
-            "invalid value '{other}' for '--color' [possible values: auto, always, never]"
-        )),
+fn parse_named<T>(
+    flag: &str,
+    value: &str,
+    from_name: fn(&str) -> Option<T>,
+    names: &[&str],
+) -> Result<T, String> {
+    from_name(value).ok_or_else(|| {
+        format!("invalid value '{value}' for '{flag}' [possible values: {}]", names.join(", "))
+    })
+}
+
+fn parse_count(flag: &str, value: &str) -> Result<usize, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{value}' for '{flag}': expected a non-negative integer"))
+}
+
+// Returns the value of `--flag VALUE` or `--flag=VALUE`, or None if `arg` is
+// not `flag` at all.
+fn option_value<I: Iterator<Item = String>>(
+    arg: &str,
+    flag: &str,
+    placeholder: &str,
+    iter: &mut I,
+) -> Option<Result<String, String>> {
+    if arg == flag {
+        return Some(iter.next().ok_or_else(|| {
+            format!("a value is required for '{flag} {placeholder}' but none was supplied")
+        }));
     }
+    arg.strip_prefix(flag)
+        .and_then(|rest| rest.strip_prefix('='))
+        .map(|value| Ok(value.to_string()))
 }
 
 fn parse_args<I: Iterator<Item = String>>(args: I) -> Result<Action, String> {
-    let mut sql: Option<String> = None;
+    let mut inputs: Vec<String> = Vec::new();
     let mut minify = false;
-    let mut color = ColorWhen::Auto;
-    let mut iter = args;
+    let mut write = false;
+    let mut check = false;
+    let mut diff = false;
+    let mut verify = false;
+    let mut stream = false;
+    let mut lsp = false;
+    let mut range = None;
+    let mut embedded = false;
+    let mut config_path: Option<PathBuf> = None;
+    let mut no_config = false;
+    let mut overrides = Config::default();
+    let mut iter = args.peekable();
     let mut positional_only = false;
 
+    // `sqlfmt lsp` runs the language server; `sqlfmt ./lsp` formats a file.
+    if iter.peek().is_some_and(|arg| arg == "lsp") {
+        iter.next();
+        lsp = true;
+    }
+
     while let Some(arg) = iter.next() {
         if positional_only {
-            if sql.is_some() {
-                return Err(format!("unexpected argument '{arg}'"));
-            }
-            sql = Some(arg);
+            inputs.push(arg);
+            continue;
+        }
+
+        if let Some(value) = option_value(&arg, "--color", "<WHEN>", &mut iter) {
+            overrides.color = Some(parse_named("--color", &value?, ColorWhen::from_name, ColorWhen::NAMES)?);
+            continue;
+        }
+        if let Some(value) = option_value(&arg, "--indent", "<N>", &mut iter) {
+            overrides.indent_width = Some(parse_count("--indent", &value?)?);
+            continue;
+        }
+        if let Some(value) = option_value(&arg, "--max-line-width", "<N>", &mut iter) {
+            overrides.max_line_width = Some(parse_count("--max-line-width", &value?)?);
+            continue;
+        }
+        if let Some(value) = option_value(&arg, "--max-blank-lines", "<N>", &mut iter) {
+            overrides.max_blank_lines = Some(parse_count("--max-blank-lines", &value?)?);
+            continue;
+        }
+        if let Some(value) = option_value(&arg, "--commas", "<STYLE>", &mut iter) {
+            overrides.comma_style =
+                Some(parse_named("--commas", &value?, CommaStyle::from_name, CommaStyle::NAMES)?);
+            continue;
+        }
+        if let Some(value) = option_value(&arg, "--dialect", "<NAME>", &mut iter) {
+            overrides.dialect = Some(parse_named("--dialect", &value?, Dialect::from_name, Dialect::NAMES)?);
+            continue;
+        }
+        if let Some(value) = option_value(&arg, "--keyword-case", "<CASE>", &mut iter) {
+            overrides.keyword_case =
+                Some(parse_named("--keyword-case", &value?, KeywordCase::from_name, KeywordCase::NAMES)?);
+            continue;
+        }
+        if let Some(value) = option_value(&arg, "--range", "<RANGE>", &mut iter) {
+            range = Some(RangeSpec::parse(&value?)?);
+            continue;
+        }
+        if let Some(value) = option_value(&arg, "--config", "<PATH>", &mut iter) {
+            config_path = Some(PathBuf::from(value?));
             continue;
         }
 
@@ -86,32 +257,84 @@ fn parse_args<I: Iterator<Item = String>>(args: I) -> Result<Action, String> {
             "-h" | "--help" => return Ok(Action::Help),
             "-V" | "--version" => return Ok(Action::Version),
             "-m" | "--minify" => minify = true,
-            "--color" => {
-                let value = iter.next().ok_or_else(|| {
-                    "a value is required for '--color <WHEN>' but none was supplied".to_string()
-                })?;
-                color = parse_color(&value)?;
-            }
-            s if s.starts_with("--color=") => {
-                color = parse_color(&s["--color=".len()..])?;
-            }
+            "-w" | "--write" => write = true,
+            "--check" => check = true,
+            "--diff" => diff = true,
+            "--verify" => verify = true,
+            "--stream" => stream = true,
+            "--embedded" => embedded = true,
+            "--no-config" => no_config = true,
+            "--tabs" => overrides.use_tabs = Some(true),
+            "--format-sql-bodies" => overrides.format_sql_bodies = Some(true),
             s if s.starts_with("--") || (s.starts_with('-') && s.len() > 1) => {
                 return Err(format!("unexpected argument '{s}'"));
             }
-            _ => {
-                if sql.is_some() {
-                    return Err(format!("unexpected argument '{arg}'"));
-                }
-                sql = Some(arg);
+            _ => inputs.push(arg),
+        }
+    }
+
+    if write && inputs.is_empty() {
+        return Err("'--write' requires at least one file or directory".to_string());
+    }
+    if write && check {
+        return Err("'--write' cannot be used with '--check'".to_string());
+    }
+    if write && diff {
+        return Err("'--write' cannot be used with '--diff'".to_string());
+    }
+    if range.is_some() {
+        if inputs.len() > 1 {
+            return Err("'--range' needs a single input".to_string());
+        }
+        for (used, flag) in [(minify, "--minify"), (stream, "--stream"), (lsp, "lsp"), (embedded, "--embedded")] {
+            if used {
+                return Err(format!("'--range' cannot be used with '{flag}'"));
+            }
+        }
+    }
+    if embedded {
+        for (used, flag) in [(minify, "--minify"), (stream, "--stream"), (lsp, "lsp")] {
+            if used {
+                return Err(format!("'--embedded' cannot be used with '{flag}'"));
+            }
+        }
+        if inputs.is_empty() {
+            return Err("'--embedded' requires at least one file or directory".to_string());
+        }
+    }
+    if lsp {
+        if !inputs.is_empty() {
+            return Err("'lsp' takes no inputs".to_string());
+        }
+        for (used, flag) in [(minify, "--minify"), (write, "--write"), (check, "--check"), (diff, "--diff"), (stream, "--stream")] {
+            if used {
+                return Err(format!("'lsp' cannot be used with '{flag}'"));
             }
         }
     }
+    if stream {
+        if !inputs.is_empty() {
+            return Err("'--stream' only reads from stdin".to_string());
+        }
+        for (used, flag) in [(check, "--check"), (diff, "--diff")] {
+            if used {
+                return Err(format!("'--stream' cannot be used with '{flag}'"));
+            }
+        }
+    }
+
+    let config = match (config_path, no_config) {
+        (Some(_), true) => return Err("'--config' cannot be used with '--no-config'".to_string()),
+        (Some(path), false) => ConfigSource::File(path),
+        (None, true) => ConfigSource::Disabled,
+        (None, false) => ConfigSource::Discover,
+    };
 
-    Ok(Action::Run(Cli { sql, minify, color }))
+    Ok(Action::Run(Box::new(Cli { inputs, minify, write, check, diff, verify, stream, lsp, range, embedded, config, overrides })))
 }
 
-fn should_colorize(when: ColorWhen) -> bool {
-    match when {
+fn should_colorize(when: Option<ColorWhen>) -> bool {
+    match when.unwrap_or_default() {
         ColorWhen::Always => true,
         ColorWhen::Never => false,
         ColorWhen::Auto => {
@@ -120,9 +343,376 @@ fn should_colorize(when: ColorWhen) -> bool {
     }
 }
 
+enum Input {
+    Stdin,
+    Sql(String),
+    Paths(Vec<PathBuf>),
+}
+
+// Whether an argument that names no file was meant to: a single word with a
+// path separator or a `.sql`/`.md` extension. SQL text has spaces in it.
+fn looks_like_path(arg: &str) -> bool {
+    let path = Path::new(arg);
+    !arg.contains(char::is_whitespace)
+        && (arg.contains(['/', '\\']) || files::has_sql_extension(path) || files::is_markdown(path))
+}
+
+// A single argument that isn't an existing path (or something that looks
+// like one) is SQL text, unless files are required; otherwise every argument
+// must be a file or directory.
+fn resolve_input(cli: &Cli) -> Result<Input, String> {
+    match cli.inputs.as_slice() {
+        [] => Ok(Input::Stdin),
+        [sql] if !cli.write && !cli.embedded && !looks_like_path(sql) && !Path::new(sql).exists() => {
+            Ok(Input::Sql(sql.clone()))
+        }
+        args => args
+            .iter()
+            .map(|arg| {
+                let path = PathBuf::from(arg);
+                if path.exists() {
+                    Ok(path)
+                } else {
+                    Err(format!("'{arg}' is not a file or directory"))
+                }
+            })
+            .collect::<Result<_, _>>()
+            .map(Input::Paths),
+    }
+}
+
+// Config files are loaded on first use and reused for later inputs.
+struct Configs<'a> {
+    cli: &'a Cli,
+    loaded: HashMap<Option<PathBuf>, Config>,
+}
+
+impl<'a> Configs<'a> {
+    fn new(cli: &'a Cli) -> Self {
+        Self { cli, loaded: HashMap::new() }
+    }
+
+    // Settings for inputs in `dir`: the config file that applies there with
+    // the command-line flags on top.
+    fn for_dir(&mut self, dir: &Path) -> Result<&Config, String> {
+        let path = match &self.cli.config {
+            ConfigSource::Discover => Config::discover(dir),
+            ConfigSource::File(path) => Some(path.clone()),
+            ConfigSource::Disabled => None,
+        };
+        if !self.loaded.contains_key(&path) {
+            let mut config = match &path {
+                Some(path) => Config::load(path)?,
+                None => Config::default(),
+            };
+            config.merge(&self.cli.overrides);
+            self.loaded.insert(path.clone(), config);
+        }
+        Ok(&self.loaded[&path])
+    }
+
+    fn for_file(&mut self, path: &Path) -> Result<&Config, String> {
+        match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => self.for_dir(dir),
+            _ => self.for_dir(Path::new(".")),
+        }
+    }
+}
+
+fn format_sql(input: &str, cli: &Cli, config: &Config) -> Result<String, VerifyError> {
+    let Some(range) = cli.range else {
+        return format_whole(input, cli, config);
+    };
+    // Only the statements in the range change (as `beautify_range` does,
+    // but with `--verify` applied to them).
+    let dialect = config.dialect.unwrap_or_default();
+    let Some(span) = splitter::statement_range(input, range.bytes(input), dialect) else {
+        return Ok(input.to_string());
+    };
+    let formatted = format_whole(&input[span.clone()], cli, config)?;
+    Ok(format!("{}{}{}", &input[..span.start], formatted.trim_end(), &input[span.end..]))
+}
+
+fn format_whole(input: &str, cli: &Cli, config: &Config) -> Result<String, VerifyError> {
+    let dialect = config.dialect.unwrap_or_default();
+    let opts = config.format_options();
+    if cli.verify {
+        return if cli.minify {
+            verify::verify_minify(input, dialect)
+        } else {
+            verify::verify_format(input, dialect, &opts)
+        };
+    }
+    let tokens = tokenizer::tokenize_with(input, dialect);
+    if cli.minify {
+        Ok(formatter::minify(&tokens))
+    } else {
+        Ok(formatter::beautify_with(&tokens, &opts))
+    }
+}
+
+// Formatted file contents end with exactly one newline (empty stays empty),
+// unless only a range is formatted.
+fn format_file(input: &str, cli: &Cli, config: &Config) -> Result<String, VerifyError> {
+    let formatted = format_sql(input, cli, config)?;
+    if cli.range.is_some() {
+        return Ok(formatted);
+    }
+    let trimmed = formatted.trim_end();
+    if trimmed.is_empty() {
+        Ok(String::new())
+    } else {
+        Ok(format!("{trimmed}\n"))
+    }
+}
+
+// Formats the SQL code blocks of a Markdown file, returning the new text and
+// the lines of the blocks that change. Blocks that fail `--verify` are
+// reported and left alone. A block's dialect tag wins over the config.
+fn format_markdown(input: &str, path: &Path, cli: &Cli, config: &Config, result: &mut RunResult) -> (String, Vec<usize>) {
+    let mut changed = Vec::new();
+    let formatted = markdown::format_blocks(input, |block, code| {
+        let config = Config { dialect: block.dialect.or(config.dialect), ..config.clone() };
+        match format_file(code, cli, &config) {
+            Ok(formatted) => {
+                if formatted != code {
+                    changed.push(block.line);
+                }
+                Some(formatted)
+            }
+            Err(e) => {
+                eprintln!("sqlfmt: {}:{}: {e}", path.display(), block.line);
+                result.failed = true;
+                None
+            }
+        }
+    });
+    (formatted, changed)
+}
+
+// Formats the SQL strings of a Python, Rust or Go file, returning the new
+// text and the lines of the strings that change. Strings that fail
+// `--verify` are reported and left alone.
+fn format_embedded(
+    input: &str,
+    path: &Path,
+    language: Language,
+    cli: &Cli,
+    config: &Config,
+    result: &mut RunResult,
+) -> (String, Vec<usize>) {
+    let mut changed = Vec::new();
+    let formatted = embedded::format_strings(input, language, |string, code| match format_file(code, cli, config) {
+        Ok(formatted) => {
+            if string.literal(input, &formatted).is_some_and(|literal| literal != input[string.content.clone()]) {
+                changed.push(string.line);
+            }
+            Some(formatted)
+        }
+        Err(e) => {
+            eprintln!("sqlfmt: {}:{}: {e}", path.display(), string.line);
+            result.failed = true;
+            None
+        }
+    });
+    (formatted, changed)
+}
+
+fn colorize_for_stdout(formatted: String, config: &Config) -> String {
+    if should_colorize(config.color) {
+        formatter::colorize_with(&formatted, &formatter::Palette::ansi(), config.dialect.unwrap_or_default())
+    } else {
+        formatted
+    }
+}
+
+fn print_diff(original: &str, formatted: &str, label: &str, config: &Config) {
+    let diff = diff::unified(original, formatted, label, label);
+    if should_colorize(config.color) {
+        print!("{}", diff::colorize(&diff));
+    } else {
+        print!("{diff}");
+    }
+}
+
+#[derive(Default)]
+struct RunResult {
+    // Some input could not be read or written.
+    failed: bool,
+    // Some input was not already formatted.
+    changed: bool,
+}
+
+impl RunResult {
+    fn exit_code(&self, cli: &Cli) -> i32 {
+        if self.failed || (cli.check && self.changed) {
+            1
+        } else {
+            0
+        }
+    }
+}
+
+// Formats every file under `paths`: prints it, rewrites it (`--write`) or
+// reports it as unformatted (`--check`) or as a diff (`--diff`). Files found
+// by walking a directory are filtered by the include/exclude globs of their
+// config; files named explicitly are always formatted.
+fn run_files(paths: &[PathBuf], cli: &Cli, configs: &mut Configs) -> Result<RunResult, String> {
+    let mut result = RunResult::default();
+    let mut config_error = None;
+    let collected = files::collect_files(paths, |path| match configs.for_file(path) {
+        Ok(config) if cli.embedded && Language::from_path(path).is_some() => !config.excludes(path),
+        Ok(config) => config.includes(path),
+        Err(e) => {
+            config_error.get_or_insert(e);
+            false
+        }
+    });
+    if let Some(e) = config_error {
+        return Err(e);
+    }
+    let files = match collected {
+        Ok(files) if cli.range.is_some() && files.len() > 1 => {
+            return Err("'--range' needs a single input".to_string());
+        }
+        Ok(files) => files,
+        Err(e) => {
+            eprintln!("sqlfmt: {e}");
+            result.failed = true;
+            return Ok(result);
+        }
+    };
+
+    for path in files {
+        let original = match fs::read_to_string(&path) {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("sqlfmt: {}: {e}", path.display());
+                result.failed = true;
+                continue;
+            }
+        };
+        let config = configs.for_file(&path)?;
+        let is_markdown = files::is_markdown(&path);
+        let language = if cli.embedded { Language::from_path(&path) } else { None };
+        // Markdown and program files are formatted (and reported) a block or
+        // string at a time.
+        let by_block = is_markdown || language.is_some();
+        let (formatted, changed_blocks) = if is_markdown {
+            if cli.range.is_some() {
+                eprintln!("sqlfmt: {}: '--range' cannot be used with Markdown files", path.display());
+                result.failed = true;
+                continue;
+            }
+            format_markdown(&original, &path, cli, config, &mut result)
+        } else if let Some(language) = language {
+            format_embedded(&original, &path, language, cli, config, &mut result)
+        } else {
+            match format_file(&original, cli, config) {
+                Ok(formatted) => (formatted, Vec::new()),
+                Err(e) => {
+                    eprintln!("sqlfmt: {}: {e}", path.display());
+                    result.failed = true;
+                    continue;
+                }
+            }
+        };
+        let changed = formatted != original;
+        result.changed |= changed;
+
+        if cli.diff {
+            print_diff(&original, &formatted, &path.display().to_string(), config);
+        } else if cli.check {
+            if by_block {
+                for line in changed_blocks {
+                    println!("{}:{line}", path.display());
+                }
+            } else if changed {
+                println!("{}", path.display());
+            }
+        } else if cli.write {
+            if changed {
+                if let Err(e) = files::write_atomic(&path, &formatted) {
+                    eprintln!("sqlfmt: {}: {e}", path.display());
+                    result.failed = true;
+                }
+            }
+        } else if by_block {
+            print!("{formatted}");
+        } else {
+            print!("{}", colorize_for_stdout(formatted, config));
+        }
+    }
+    Ok(result)
+}
+
+// Formats stdin one statement at a time (`--stream`), writing each as soon
+// as it has been read. A statement that fails `--verify` is reported and
+// written unchanged.
+fn run_stream(cli: &Cli, config: &Config) -> RunResult {
+    let mut result = RunResult::default();
+    let reader = StatementReader::new(io::stdin().lock(), config.dialect.unwrap_or_default());
+    // Beautified statements are separated as if the whole input had been
+    // formatted at once; minified ones run together.
+    let blank_lines = if cli.minify { String::new() } else { "\n".repeat(config.format_options().lines_between_statements) };
+    let mut out = io::stdout().lock();
+    let mut wrote_statement = false;
+    // The output doesn't end with a newline yet.
+    let mut line_open = false;
+    let mut line = 1;
+
+    for chunk in reader {
+        let text = match chunk {
+            Ok(Chunk::Statement(sql)) => {
+                let leading = &sql[..sql.len() - sql.trim_start().len()];
+                let start_line = line + leading.matches('\n').count();
+                line += sql.matches('\n').count();
+                let formatted = match format_sql(&sql, cli, config) {
+                    Ok(formatted) if cli.minify => formatted,
+                    Ok(formatted) => formatted.trim_end().to_string(),
+                    Err(e) => {
+                        eprintln!("sqlfmt: <stdin>:{start_line}: {e}");
+                        result.failed = true;
+                        sql.trim().to_string()
+                    }
+                };
+                if formatted.trim().is_empty() {
+                    continue;
+                }
+                let newline = if line_open && !cli.minify { "\n" } else { "" };
+                let gap = if wrote_statement { blank_lines.as_str() } else { "" };
+                wrote_statement = true;
+                line_open = !formatted.ends_with('\n');
+                format!("{newline}{gap}{}", colorize_for_stdout(formatted, config))
+            }
+            // COPY data starts on the line after its statement.
+            Ok(Chunk::Data(data)) => {
+                line += 1;
+                let newline = if line_open { "\n" } else { "" };
+                line_open = !data.ends_with('\n');
+                format!("{newline}{data}")
+            }
+            Err(e) => {
+                eprintln!("sqlfmt: <stdin>: {e}");
+                result.failed = true;
+                break;
+            }
+        };
+        if let Err(e) = out.write_all(text.as_bytes()) {
+            eprintln!("sqlfmt: {e}");
+            result.failed = true;
+            return result;
+        }
+    }
+    if line_open {
+        let _ = writeln!(out);
+    }
+    result
+}
+
 fn main() {
     let cli = match parse_args(std::env::args().skip(1)) {
-        Ok(Action::Run(cli)) => cli,
+        Ok(Action::Run(cli)) => *cli,
         Ok(Action::Help) => {
             print_help();
             process::exit(0);
@@ -137,13 +727,36 @@ fn main() {
         }
     };
 
-    let input = match cli.sql {
-        Some(sql) => sql,
-        None => {
+    let mut configs = Configs::new(&cli);
+    if cli.lsp {
+        // Exit with 0 only after `shutdown`, as the protocol asks.
+        let result = lsp::serve(io::stdin().lock(), io::stdout().lock(), cli.verify, |dir| configs.for_dir(dir).cloned());
+        match result {
+            Ok(true) => process::exit(0),
+            Ok(false) => process::exit(1),
+            Err(e) => {
+                eprintln!("sqlfmt: lsp: {e}");
+                process::exit(1);
+            }
+        }
+    }
+    let input = match resolve_input(&cli) {
+        Ok(Input::Sql(sql)) => sql,
+        Ok(Input::Stdin) => {
             if io::stdin().is_terminal() {
                 print_help();
                 process::exit(1);
             }
+            if cli.stream {
+                let config = match configs.for_dir(Path::new(".")) {
+                    Ok(config) => config,
+                    Err(msg) => {
+                        eprintln!("sqlfmt: {msg}");
+                        process::exit(2);
+                    }
+                };
+                process::exit(run_stream(&cli, config).exit_code(&cli));
+            }
             let mut buf = String::new();
             if let Err(e) = io::stdin().read_to_string(&mut buf) {
                 eprintln!("sqlfmt: {e}");
@@ -151,24 +764,52 @@ fn main() {
             }
             buf
         }
+        Ok(Input::Paths(paths)) => match run_files(&paths, &cli, &mut configs) {
+            Ok(result) => process::exit(result.exit_code(&cli)),
+            Err(msg) => {
+                eprintln!("sqlfmt: {msg}");
+                process::exit(2);
+            }
+        },
+        Err(msg) => {
+            print_usage_error(&msg);
+            process::exit(2);
+        }
     };
 
     if input.is_empty() {
         return;
     }
 
-    let tokens = tokenizer::tokenize(&input);
-    let formatted = if cli.minify {
-        formatter::minify(&tokens)
-    } else {
-        formatter::beautify(&tokens)
+    let config = match configs.for_dir(Path::new(".")) {
+        Ok(config) => config,
+        Err(msg) => {
+            eprintln!("sqlfmt: {msg}");
+            process::exit(2);
+        }
     };
-
-    let output = if should_colorize(cli.color) {
-        formatter::colorize(&formatted, &formatter::Palette::ansi())
-    } else {
-        formatted
+    let formatted = match format_sql(&input, &cli, config) {
+        // The input around the range is kept, final newline included.
+        Ok(formatted) if cli.range.is_some() => formatted.strip_suffix('\n').map(str::to_string).unwrap_or(formatted),
+        Ok(formatted) => formatted,
+        Err(e) => {
+            eprintln!("sqlfmt: <stdin>: {e}");
+            process::exit(1);
+        }
     };
-
-    println!("{output}");
+    if cli.check || cli.diff {
+        if formatted.trim_end() != input.trim_end() {
+            if cli.diff {
+                let file = format!("{}\n", formatted.trim_end());
+                print_diff(&input, &file, "<stdin>", config);
+            } else {
+                println!("<stdin>");
+            }
+            if cli.check {
+                process::exit(1);
+            }
+        }
+        return;
+    }
+    println!("{}", colorize_for_stdout(formatted, config));
 }
@@ -0,0 +1,138 @@
+use crate::dialect::Dialect;
+use crate::formatter::{beautify_with, FormatOptions};
+use crate::tokenizer::tokenize_with;
+
+use std::ops::Range;
+
+/// A fenced code block of SQL in a Markdown document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SqlBlock {
+    /// 1-based line of the opening fence.
+    pub line: usize,
+    /// The dialect named by the fence's tag; None for plain `sql`.
+    pub dialect: Option<Dialect>,
+    /// Byte range of the lines between the fences.
+    pub content: Range<usize>,
+    /// Indentation of the opening fence, which the content lines share.
+    pub indent: String,
+}
+
+impl SqlBlock {
+    /// The SQL in the block, with the fence's indentation taken off each
+    /// line.
+    pub fn code(&self, markdown: &str) -> String {
+        markdown[self.content.clone()]
+            .split_inclusive('\n')
+            .map(|line| strip_indent(line, self.indent.len()))
+            .collect()
+    }
+}
+
+// Removes up to `width` leading spaces from `line`.
+fn strip_indent(line: &str, width: usize) -> &str {
+    let spaces = line.len() - line.trim_start_matches(' ').len();
+    &line[spaces.min(width)..]
+}
+
+// The dialect a fence tag asks for: `Some(None)` for plain `sql`, None if
+// the block is not SQL. `psql` blocks are usually terminal sessions and
+// `ansi` blocks colored terminal output, so neither counts.
+fn tag_dialect(tag: &str) -> Option<Option<Dialect>> {
+    match tag.to_ascii_lowercase().as_str() {
+        "sql" => Some(None),
+        "psql" | "ansi" | "generic" => None,
+        name => Dialect::from_name(name).map(Some),
+    }
+}
+
+/// Finds the fenced code blocks (```` ``` ```` or `~~~`) tagged `sql` or
+/// with a dialect name such as `postgresql` or `mysql`. Blocks that are
+/// never closed are left out.
+pub fn sql_blocks(markdown: &str) -> Vec<SqlBlock> {
+    let mut blocks = Vec::new();
+    // The open fence: its character, length and, if it is SQL, its block.
+    let mut open: Option<(char, usize, Option<SqlBlock>)> = None;
+    let mut offset = 0;
+    for (index, line) in markdown.split_inclusive('\n').enumerate() {
+        let start = offset;
+        offset += line.len();
+        let text = line.trim_end_matches(['\n', '\r']);
+        let trimmed = text.trim_start_matches(' ');
+        let indent = &text[..text.len() - trimmed.len()];
+        let fence_char = match trimmed.chars().next() {
+            Some(c @ ('`' | '~')) => c,
+            _ => continue,
+        };
+        let fence_len = trimmed.len() - trimmed.trim_start_matches(fence_char).len();
+        if fence_len < 3 {
+            continue;
+        }
+        let info = trimmed[fence_len..].trim();
+        match open.take() {
+            None => {
+                // A backtick fence's info string can't hold backticks.
+                if fence_char == '`' && info.contains('`') {
+                    continue;
+                }
+                let tag = info.split(|c: char| c.is_whitespace() || c == '{' || c == ',').next().unwrap_or_default();
+                let block = tag_dialect(tag).map(|dialect| SqlBlock {
+                    line: index + 1,
+                    dialect,
+                    content: offset..offset,
+                    indent: indent.to_string(),
+                });
+                open = Some((fence_char, fence_len, block));
+            }
+            // A closing fence is at least as long as the opening one and has
+            // nothing after it.
+            Some((c, len, block)) if c == fence_char && fence_len >= len && info.is_empty() => {
+                if let Some(mut block) = block {
+                    block.content.end = start;
+                    blocks.push(block);
+                }
+            }
+            still_open => open = still_open,
+        }
+    }
+    blocks
+}
+
+/// Replaces the code of each SQL block with what `format` returns for it,
+/// indented like the block's fence; blocks for which it returns None are
+/// left alone, as is everything outside the blocks.
+pub fn format_blocks(markdown: &str, mut format: impl FnMut(&SqlBlock, &str) -> Option<String>) -> String {
+    let mut out = String::with_capacity(markdown.len());
+    let mut copied = 0;
+    for block in sql_blocks(markdown) {
+        let code = block.code(markdown);
+        if code.trim().is_empty() {
+            continue;
+        }
+        let Some(formatted) = format(&block, &code) else {
+            continue;
+        };
+        out.push_str(&markdown[copied..block.content.start]);
+        for line in formatted.split_inclusive('\n') {
+            // Blank lines stay empty; lines of strings keep their spaces.
+            if !line.trim_end_matches(['\n', '\r']).is_empty() {
+                out.push_str(&block.indent);
+            }
+            out.push_str(line);
+        }
+        if !formatted.ends_with('\n') {
+            out.push('\n');
+        }
+        copied = block.content.end;
+    }
+    out.push_str(&markdown[copied..]);
+    out
+}
+
+/// Beautifies the SQL blocks of a Markdown document with `opts`, using each
+/// block's dialect tag or else `dialect`.
+pub fn beautify_markdown(markdown: &str, dialect: Dialect, opts: &FormatOptions) -> String {
+    format_blocks(markdown, |block, code| {
+        let formatted = beautify_with(&tokenize_with(code, block.dialect.unwrap_or(dialect)), opts);
+        Some(format!("{}\n", formatted.trim_end()))
+    })
+}
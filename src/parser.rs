@@ -0,0 +1,439 @@
+use crate::tokenizer::Token;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NodeKind {
+    Script,
+    Statement,
+    // A clause keyword (SELECT, GROUP BY, LEFT JOIN, ...) and its items.
+    Clause,
+    Expression,
+    FunctionCall,
+    Case,
+    // `( SELECT ... )`
+    Subquery,
+    // Any other parenthesized group: argument lists, IN-lists, column lists.
+    Parenthesized,
+    // Tokens that don't fit anywhere, e.g. an unmatched `)`.
+    Error,
+}
+
+/// A significant token with the whitespace and comments around it.
+///
+/// Trivia on the same line after the token is trailing; everything from the
+/// next line break up to the following token is leading trivia of that token.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxToken {
+    pub leading_trivia: Vec<Token>,
+    pub token: Token,
+    pub trailing_trivia: Vec<Token>,
+}
+
+impl SyntaxToken {
+    fn write_text(&self, out: &mut String) {
+        for t in &self.leading_trivia {
+            out.push_str(t.text());
+        }
+        out.push_str(self.token.text());
+        for t in &self.trailing_trivia {
+            out.push_str(t.text());
+        }
+    }
+
+    pub fn comments(&self) -> impl Iterator<Item = &str> {
+        self.leading_trivia
+            .iter()
+            .chain(&self.trailing_trivia)
+            .filter_map(|t| match t {
+                Token::Comment(c) => Some(c.as_str()),
+                _ => None,
+            })
+    }
+
+    fn is_keyword(&self, kw: &str) -> bool {
+        matches!(&self.token, Token::Keyword(k) if k.eq_ignore_ascii_case(kw))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxNode {
+    pub kind: NodeKind,
+    pub children: Vec<SyntaxElement>,
+}
+
+impl SyntaxNode {
+    fn new(kind: NodeKind) -> Self {
+        Self { kind, children: Vec::new() }
+    }
+
+    fn push_token(&mut self, token: SyntaxToken) {
+        self.children.push(SyntaxElement::Token(token));
+    }
+
+    fn push_node(&mut self, node: SyntaxNode) {
+        self.children.push(SyntaxElement::Node(node));
+    }
+
+    /// The exact source text covered by this node, trivia included.
+    pub fn text(&self) -> String {
+        let mut out = String::new();
+        self.write_text(&mut out);
+        out
+    }
+
+    fn write_text(&self, out: &mut String) {
+        for child in &self.children {
+            match child {
+                SyntaxElement::Node(n) => n.write_text(out),
+                SyntaxElement::Token(t) => t.write_text(out),
+            }
+        }
+    }
+
+    /// Direct child nodes.
+    pub fn nodes(&self) -> impl Iterator<Item = &SyntaxNode> {
+        self.children.iter().filter_map(|c| match c {
+            SyntaxElement::Node(n) => Some(n),
+            SyntaxElement::Token(_) => None,
+        })
+    }
+
+    /// All nodes below this one, depth first.
+    pub fn descendants(&self) -> Vec<&SyntaxNode> {
+        let mut found = Vec::new();
+        for node in self.nodes() {
+            found.push(node);
+            found.extend(node.descendants());
+        }
+        found
+    }
+
+    /// All significant tokens below this node, in source order.
+    pub fn tokens(&self) -> Vec<&SyntaxToken> {
+        let mut found = Vec::new();
+        for child in &self.children {
+            match child {
+                SyntaxElement::Node(n) => found.extend(n.tokens()),
+                SyntaxElement::Token(t) => found.push(t),
+            }
+        }
+        found
+    }
+
+    /// For a clause, its keyword(s) uppercased: `SELECT`, `GROUP BY`,
+    /// `LEFT JOIN`. None for other node kinds.
+    pub fn keyword(&self) -> Option<String> {
+        if self.kind != NodeKind::Clause {
+            return None;
+        }
+        let tokens = self.tokens();
+        let len = clause_head_len(&tokens, 0)?;
+        let words: Vec<String> = tokens[..len].iter().map(|t| t.token.text().to_uppercase()).collect();
+        Some(words.join(" "))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxTree {
+    pub root: SyntaxNode,
+    // Trivia after the last significant token's line.
+    pub end_trivia: Vec<Token>,
+}
+
+impl SyntaxTree {
+    /// Reconstructs the original input.
+    pub fn text(&self) -> String {
+        let mut out = self.root.text();
+        for t in &self.end_trivia {
+            out.push_str(t.text());
+        }
+        out
+    }
+
+    pub fn statements(&self) -> impl Iterator<Item = &SyntaxNode> {
+        self.root.nodes().filter(|n| n.kind == NodeKind::Statement)
+    }
+}
+
+fn is_statement_starter(kw: &str) -> bool {
+    matches!(
+        kw,
+        "SELECT" | "INSERT" | "UPDATE" | "DELETE" | "CREATE" | "DROP" | "ALTER" | "WITH"
+    )
+}
+
+fn is_function_keyword(kw: &str) -> bool {
+    matches!(
+        kw,
+        "COUNT" | "SUM" | "AVG" | "MIN" | "MAX" | "COALESCE" | "ROW_NUMBER" | "RANK"
+            | "DENSE_RANK" | "LAG" | "LEAD" | "CAST" | "REPLACE" | "LEFT" | "RIGHT"
+    )
+}
+
+fn keyword_at(tokens: &[&SyntaxToken], i: usize) -> Option<String> {
+    match tokens.get(i).map(|t| &t.token) {
+        Some(Token::Keyword(kw)) => Some(kw.to_uppercase()),
+        _ => None,
+    }
+}
+
+// Number of tokens forming the clause keyword starting at `i`, if any.
+fn clause_head_len(tokens: &[&SyntaxToken], i: usize) -> Option<usize> {
+    let kw = keyword_at(tokens, i)?;
+    let next = keyword_at(tokens, i + 1);
+    let next = next.as_deref();
+    match kw.as_str() {
+        "SELECT" | "FROM" | "WHERE" | "HAVING" | "LIMIT" | "OFFSET" | "SET" | "VALUES"
+        | "JOIN" | "ON" | "RETURNING" | "WINDOW" | "UPDATE" | "CREATE" | "DROP" | "ALTER"
+        | "WITH" | "TRUNCATE" | "GRANT" | "REVOKE" => Some(1),
+        "UNION" | "EXCEPT" | "INTERSECT" => Some(if matches!(next, Some("ALL" | "DISTINCT")) { 2 } else { 1 }),
+        "GROUP" | "ORDER" | "PARTITION" if next == Some("BY") => Some(2),
+        "INSERT" if next == Some("INTO") => Some(2),
+        "DELETE" if next == Some("FROM") => Some(2),
+        "INSERT" | "DELETE" => Some(1),
+        "LEFT" | "RIGHT" | "FULL" if next == Some("OUTER") && keyword_at(tokens, i + 2).as_deref() == Some("JOIN") => Some(3),
+        "LEFT" | "RIGHT" | "INNER" | "OUTER" | "CROSS" | "FULL" | "NATURAL" if next == Some("JOIN") => Some(2),
+        _ => None,
+    }
+}
+
+/// Builds a lossless syntax tree from `tokenize` output.
+///
+/// Never fails: unbalanced parentheses, a missing `END` and other malformed
+/// input end up in the nearest enclosing node (or an `Error` node), so the
+/// tree always reproduces the input exactly via `SyntaxTree::text`.
+pub fn parse(tokens: &[Token]) -> SyntaxTree {
+    let (significant, end_trivia) = attach_trivia(tokens);
+    let refs: Vec<&SyntaxToken> = significant.iter().collect();
+    let mut parser = Parser { tokens: &refs, pos: 0 };
+    let root = parser.script();
+    SyntaxTree { root, end_trivia }
+}
+
+fn attach_trivia(tokens: &[Token]) -> (Vec<SyntaxToken>, Vec<Token>) {
+    let mut significant: Vec<SyntaxToken> = Vec::new();
+    let mut pending: Vec<Token> = Vec::new();
+    // Whether trivia still belongs on the line of the previous token.
+    let mut same_line = false;
+
+    for token in tokens {
+        if token.is_trivia() {
+            let breaks_line = matches!(token, Token::Whitespace(ws) if ws.contains('\n'));
+            if same_line && !breaks_line {
+                if let Some(last) = significant.last_mut() {
+                    last.trailing_trivia.push(token.clone());
+                    // A line comment runs to the end of the line.
+                    if matches!(token, Token::Comment(c) if c.starts_with("--")) {
+                        same_line = false;
+                    }
+                    continue;
+                }
+            }
+            same_line = false;
+            pending.push(token.clone());
+            continue;
+        }
+        significant.push(SyntaxToken {
+            leading_trivia: std::mem::take(&mut pending),
+            token: token.clone(),
+            trailing_trivia: Vec::new(),
+        });
+        same_line = true;
+    }
+    (significant, pending)
+}
+
+struct Parser<'a> {
+    tokens: &'a [&'a SyntaxToken],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|t| &t.token)
+    }
+
+    fn peek_keyword(&self) -> Option<String> {
+        keyword_at(self.tokens, self.pos)
+    }
+
+    fn bump(&mut self) -> SyntaxToken {
+        let token = self.tokens[self.pos].clone();
+        self.pos += 1;
+        token
+    }
+
+    fn at_clause_head(&self) -> bool {
+        clause_head_len(self.tokens, self.pos).is_some()
+    }
+
+    fn script(&mut self) -> SyntaxNode {
+        let mut script = SyntaxNode::new(NodeKind::Script);
+        while self.peek().is_some() {
+            let statement = self.statement();
+            script.push_node(statement);
+        }
+        script
+    }
+
+    fn statement(&mut self) -> SyntaxNode {
+        let mut statement = SyntaxNode::new(NodeKind::Statement);
+        while let Some(token) = self.peek() {
+            match token {
+                Token::Semicolon => {
+                    statement.push_token(self.bump());
+                    break;
+                }
+                Token::CloseParen => {
+                    let mut error = SyntaxNode::new(NodeKind::Error);
+                    error.push_token(self.bump());
+                    statement.push_node(error);
+                }
+                _ => {
+                    let clause = self.clause();
+                    statement.push_node(clause);
+                }
+            }
+        }
+        statement
+    }
+
+    // A clause keyword and its comma-separated items. Tokens before the first
+    // clause keyword of a statement form a headless clause.
+    fn clause(&mut self) -> SyntaxNode {
+        let mut clause = SyntaxNode::new(NodeKind::Clause);
+        if let Some(len) = clause_head_len(self.tokens, self.pos) {
+            for _ in 0..len {
+                clause.push_token(self.bump());
+            }
+        }
+        self.items(&mut clause, &[]);
+        clause
+    }
+
+    // Comma-separated expressions up to `;`, `)`, the next clause keyword or
+    // one of `stop` (used inside CASE).
+    fn items(&mut self, parent: &mut SyntaxNode, stop: &[&str]) {
+        while let Some(token) = self.peek() {
+            match token {
+                Token::Semicolon | Token::CloseParen => break,
+                Token::Comma => parent.push_token(self.bump()),
+                _ if self.at_clause_head() => break,
+                _ if self.peek_keyword().is_some_and(|kw| stop.contains(&kw.as_str())) => break,
+                _ => {
+                    let expr = self.expression(stop);
+                    parent.push_node(expr);
+                }
+            }
+        }
+    }
+
+    fn expression(&mut self, stop: &[&str]) -> SyntaxNode {
+        let mut expr = SyntaxNode::new(NodeKind::Expression);
+        while let Some(token) = self.peek() {
+            match token {
+                Token::Semicolon | Token::CloseParen | Token::Comma => break,
+                Token::OpenParen => {
+                    let group = self.paren_group();
+                    expr.push_node(group);
+                }
+                _ if !expr.children.is_empty() && self.at_clause_head() => break,
+                _ if self.peek_keyword().is_some_and(|kw| stop.contains(&kw.as_str())) => break,
+                _ if self.peek_keyword().as_deref() == Some("CASE") => {
+                    let case = self.case();
+                    expr.push_node(case);
+                }
+                Token::Identifier(_) | Token::Keyword(_) if self.at_call() => {
+                    let call = self.function_call();
+                    expr.push_node(call);
+                }
+                _ => expr.push_token(self.bump()),
+            }
+        }
+        expr
+    }
+
+    fn at_call(&self) -> bool {
+        // `INSERT INTO t (a, b)`, `CREATE TABLE t (...)`: a column list, not a call.
+        let after_table_keyword = self.pos > 0
+            && keyword_at(self.tokens, self.pos - 1).is_some_and(|kw| matches!(kw.as_str(), "INTO" | "TABLE" | "UPDATE" | "JOIN" | "FROM"));
+        let callee = match self.peek() {
+            Some(Token::Identifier(_)) => true,
+            Some(Token::Keyword(kw)) => is_function_keyword(&kw.to_uppercase()),
+            _ => false,
+        };
+        callee && !after_table_keyword && matches!(self.tokens.get(self.pos + 1).map(|t| &t.token), Some(Token::OpenParen))
+    }
+
+    fn function_call(&mut self) -> SyntaxNode {
+        let mut call = SyntaxNode::new(NodeKind::FunctionCall);
+        call.push_token(self.bump());
+        let args = self.paren_group();
+        call.push_node(args);
+        call
+    }
+
+    fn paren_group(&mut self) -> SyntaxNode {
+        let is_subquery = self
+            .tokens
+            .get(self.pos + 1)
+            .is_some_and(|t| matches!(&t.token, Token::Keyword(kw) if is_statement_starter(&kw.to_uppercase())));
+        let mut group = SyntaxNode::new(if is_subquery { NodeKind::Subquery } else { NodeKind::Parenthesized });
+        group.push_token(self.bump());
+
+        if is_subquery {
+            let mut statement = SyntaxNode::new(NodeKind::Statement);
+            while !matches!(self.peek(), None | Some(Token::Semicolon | Token::CloseParen)) {
+                let clause = self.clause();
+                statement.push_node(clause);
+            }
+            group.push_node(statement);
+        } else {
+            while !matches!(self.peek(), None | Some(Token::Semicolon | Token::CloseParen)) {
+                if self.at_clause_head() {
+                    let clause = self.clause();
+                    group.push_node(clause);
+                } else {
+                    self.items(&mut group, &[]);
+                }
+            }
+        }
+
+        // A `;` before the `)` ends the group unclosed; the statement owns it.
+        if matches!(self.peek(), Some(Token::CloseParen)) {
+            group.push_token(self.bump());
+        }
+        group
+    }
+
+    fn case(&mut self) -> SyntaxNode {
+        const ARMS: &[&str] = &["WHEN", "THEN", "ELSE", "END"];
+        let mut case = SyntaxNode::new(NodeKind::Case);
+        case.push_token(self.bump());
+        while let Some(token) = self.peek() {
+            if matches!(token, Token::Semicolon | Token::CloseParen) {
+                break;
+            }
+            if self.tokens[self.pos].is_keyword("END") {
+                case.push_token(self.bump());
+                break;
+            }
+            if self.peek_keyword().is_some_and(|kw| ARMS.contains(&kw.as_str())) {
+                case.push_token(self.bump());
+                continue;
+            }
+            let before = self.pos;
+            self.items(&mut case, ARMS);
+            if self.pos == before {
+                // A clause keyword inside CASE: keep it rather than stall.
+                case.push_token(self.bump());
+            }
+        }
+        case
+    }
+}
@@ -0,0 +1,387 @@
+use crate::classify::classify_with;
+use crate::dialect::Dialect;
+use crate::formatter::{directive, Directive};
+use crate::tokenizer::{tokens_at, tokens_with, unterminated, Span, TokenRef};
+
+use std::io::{self, BufRead};
+use std::mem;
+use std::ops::Range;
+
+/// One statement of a script, as found by [`split_statements`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Statement {
+    /// The statement from its first token through its closing `;` (if it
+    /// has one). Comments before it are not included.
+    pub text: String,
+    /// Where `text` is in the script.
+    pub span: Span,
+    pub kind: StatementKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatementKind {
+    /// Schema changes: `CREATE`, `ALTER`, `DROP`, `TRUNCATE`, ...
+    Ddl,
+    /// Queries and data changes: `SELECT`, `INSERT`, `UPDATE`, `DELETE`, ...
+    Dml,
+    /// Transaction control: `BEGIN`, `COMMIT`, `ROLLBACK`, `SAVEPOINT`, ...
+    Tcl,
+    /// Permissions: `GRANT` and `REVOKE`.
+    Dcl,
+    /// Anything else (`SET`, `SHOW`, `EXPLAIN`, procedural blocks, ...).
+    Other,
+}
+
+/// Splits a script into its statements. Semicolons inside strings, comments,
+/// quoted identifiers, dollar-quoted bodies and `BEGIN ... END` blocks don't
+/// end a statement, and empty statements are skipped.
+pub fn split_statements(sql: &str) -> Vec<Statement> {
+    split_statements_with(sql, Dialect::Generic)
+}
+
+pub fn split_statements_with(sql: &str, dialect: Dialect) -> Vec<Statement> {
+    let mut statements = Vec::new();
+    let mut pos = 0;
+    // Line and column of `pos`.
+    let mut at = (1, 1);
+    while pos < sql.len() {
+        let rest = &sql[pos..];
+        let end = statement_end_at_eof(rest, dialect);
+        if let Some((start, stop)) = code_range(&rest[..end], dialect) {
+            let (line, column) = advance(at, &rest[..start]);
+            let text = &rest[start..stop];
+            let span = Span { start: pos + start, end: pos + stop, line, column };
+            statements.push(Statement { text: text.to_string(), span, kind: classify_with(text, dialect).statement_type.kind() });
+        }
+        at = advance(at, &rest[..end]);
+        pos += end;
+    }
+    statements
+}
+
+/// The byte range of the whole statements that `range` touches (the one
+/// holding it if it is empty), or None if it touches none. If the first of
+/// them is in a `sqlfmt: off` region, the range starts at the directive so
+/// that formatting it keeps the region as it is.
+pub fn statement_range(input: &str, range: Range<usize>, dialect: Dialect) -> Option<Range<usize>> {
+    let (start, end) = (range.start, range.end.max(range.start));
+    let statements = split_statements_with(input, dialect);
+    let mut touched = statements.iter().filter(|s| {
+        if start == end {
+            s.span.start <= start && start <= s.span.end
+        } else {
+            s.span.start < end && start < s.span.end
+        }
+    });
+    let first = touched.next()?;
+    let last = touched.next_back().unwrap_or(first);
+
+    // Where the `sqlfmt: off` in effect at the first statement starts.
+    let (mut offset, mut off) = (0, None);
+    for token in tokens_with(input, dialect) {
+        if offset >= first.span.start {
+            break;
+        }
+        if let TokenRef::Comment(c) = token {
+            match directive(c) {
+                Some(Directive::Off) => off = off.or(Some(offset)),
+                Some(Directive::On) => off = None,
+                None => {}
+            }
+        }
+        offset += token.text().len();
+    }
+    Some(off.unwrap_or(first.span.start)..last.span.end)
+}
+
+// The line and column reached by reading `text` from `at`.
+fn advance((mut line, mut column): (usize, usize), text: &str) -> (usize, usize) {
+    for ch in text.chars() {
+        if ch == '\n' {
+            line += 1;
+            column = 1;
+        } else {
+            column += 1;
+        }
+    }
+    (line, column)
+}
+
+// The byte range of `piece` from its first to its last token, skipping
+// comments and whitespace around it; None if it holds no statement.
+fn code_range(piece: &str, dialect: Dialect) -> Option<(usize, usize)> {
+    let mut range: Option<(usize, usize)> = None;
+    let mut offset = 0;
+    for token in tokens_with(piece, dialect) {
+        let start = offset;
+        offset += token.text().len();
+        if token.is_trivia() || (range.is_none() && token == TokenRef::Semicolon) {
+            continue;
+        }
+        range = Some((range.map_or(start, |(first, _)| first), offset));
+    }
+    range
+}
+
+/// A piece of a script read by [`StatementReader`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Chunk {
+    /// One statement, with the whitespace and comments before it and any
+    /// comment on the line of its closing `;`.
+    Statement(String),
+    /// A line of `COPY ... FROM stdin` data (including the closing `\.`),
+    /// which is not SQL and is passed through as is.
+    Data(String),
+}
+
+/// Reads a script one statement at a time, so only the statement being read
+/// is held in memory. Statements end at a `;` outside of strings, comments,
+/// quoted identifiers, dollar quotes, `BEGIN ... END` blocks and
+/// `sqlfmt: off` regions.
+pub struct StatementReader<R> {
+    reader: R,
+    dialect: Dialect,
+    buf: String,
+    // Where the last scan of `buf` got to.
+    scanner: Scanner,
+    // What closes the string or comment `buf` ends in, if it is still open.
+    closer: Option<String>,
+    // A `;` (or `closer`) was read since `buf` was last scanned, so it may
+    // hold a whole statement.
+    dirty: bool,
+    in_copy_data: bool,
+    eof: bool,
+}
+
+impl<R: BufRead> StatementReader<R> {
+    pub fn new(reader: R, dialect: Dialect) -> Self {
+        Self {
+            reader,
+            dialect,
+            buf: String::new(),
+            scanner: Scanner::default(),
+            closer: None,
+            dirty: false,
+            in_copy_data: false,
+            eof: false,
+        }
+    }
+
+    fn read_line(&mut self) -> io::Result<Option<String>> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            self.eof = true;
+            return Ok(None);
+        }
+        Ok(Some(line))
+    }
+}
+
+impl<R: BufRead> Iterator for StatementReader<R> {
+    type Item = io::Result<Chunk>;
+
+    fn next(&mut self) -> Option<io::Result<Chunk>> {
+        loop {
+            if self.in_copy_data && !self.eof {
+                match self.read_line() {
+                    Ok(Some(line)) => {
+                        self.in_copy_data = line.trim_end_matches(['\n', '\r']) != "\\.";
+                        return Some(Ok(Chunk::Data(line)));
+                    }
+                    Ok(None) => continue,
+                    Err(e) => return Some(Err(e)),
+                }
+            }
+            if self.dirty || self.eof {
+                match self.scanner.scan(&self.buf, self.dialect, self.eof) {
+                    Scan::End(end) => {
+                        let statement: String = self.buf.drain(..end).collect();
+                        self.scanner = Scanner::default();
+                        self.closer = None;
+                        self.in_copy_data = is_copy_from_stdin(&statement, self.dialect);
+                        return Some(Ok(Chunk::Statement(statement)));
+                    }
+                    Scan::NeedMore => {}
+                    Scan::NoEnd => {
+                        self.dirty = false;
+                        let last = tokens_at(&self.buf, self.scanner.offset, self.dialect).next();
+                        self.closer = last.and_then(|t| closer(t, self.dialect));
+                    }
+                }
+            }
+            if self.eof {
+                if self.buf.trim().is_empty() {
+                    return None;
+                }
+                return Some(Ok(Chunk::Statement(mem::take(&mut self.buf))));
+            }
+            match self.read_line() {
+                Ok(Some(line)) => {
+                    // Inside an open string or comment only its closing
+                    // delimiter can change anything.
+                    self.dirty |= match &self.closer {
+                        Some(closer) => line.contains(closer.as_str()),
+                        None => line.contains(';'),
+                    };
+                    self.buf.push_str(&line);
+                }
+                Ok(None) => {}
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+enum Scan {
+    // The first statement in the text ends at this byte offset.
+    End(usize),
+    // There is a `;`, but what follows it on its line hasn't been read yet.
+    NeedMore,
+    NoEnd,
+}
+
+// Words after `BEGIN` that make it start a transaction rather than a block.
+pub(crate) const TRANSACTION_WORDS: &[&str] =
+    &["TRANSACTION", "TRAN", "WORK", "DEFERRED", "IMMEDIATE", "EXCLUSIVE", "ISOLATION", "READ"];
+
+// Words after `END` that close a block we don't count (`END IF`, `END LOOP`).
+const UNCOUNTED_ENDS: &[&str] = &["IF", "LOOP", "WHILE", "REPEAT", "FOR"];
+
+pub(crate) fn is_word(token: &TokenRef, words: &[&str]) -> bool {
+    match token {
+        TokenRef::Keyword(w) | TokenRef::Identifier(w) => words.iter().any(|x| x.eq_ignore_ascii_case(w)),
+        _ => false,
+    }
+}
+
+// What closes `token` if it is a string, quoted identifier, block comment
+// or template tag left open at the end of the text.
+fn closer(token: TokenRef, dialect: Dialect) -> Option<String> {
+    let (_, opener) = unterminated(token, dialect)?;
+    let open = &token.text()[..opener];
+    let closer = match open {
+        _ if matches!(token, TokenRef::Template(_)) => "}",
+        "/*" => "*/",
+        "[" => "]",
+        // A quote or a dollar tag.
+        _ => open,
+    };
+    Some(closer.to_string())
+}
+
+#[derive(Clone, Copy, Default)]
+enum Pending {
+    #[default]
+    None,
+    Begin,
+    End,
+}
+
+// How far a scan for the end of a statement got: the state before the last
+// token it saw, which may still grow as more text is read, so a text that
+// keeps growing is scanned once rather than from the start each time.
+#[derive(Clone, Copy, Default)]
+struct Scanner {
+    offset: usize,
+    depth: usize,
+    pending: Pending,
+    verbatim: bool,
+}
+
+impl Scanner {
+    // Scans `text` from where the last scan of it stopped.
+    fn scan(&mut self, text: &str, dialect: Dialect, at_eof: bool) -> Scan {
+        let mut state = *self;
+        let mut semicolon = false;
+        let mut offset = self.offset;
+
+        for token in tokens_at(text, self.offset, dialect) {
+            let start = offset;
+            offset += token.text().len();
+            if semicolon {
+                match token {
+                    TokenRef::Whitespace(ws) => match ws.find('\n') {
+                        Some(newline) => return Scan::End(start + newline + 1),
+                        None => continue,
+                    },
+                    TokenRef::Comment(c) if c.starts_with("/*") && (c.len() < 4 || !c.ends_with("*/")) && !at_eof => {
+                        return Scan::NeedMore;
+                    }
+                    TokenRef::Comment(_) => continue,
+                    _ => return Scan::End(start),
+                }
+            }
+            *self = Scanner { offset: start, ..state };
+            if let TokenRef::Comment(c) = token {
+                match directive(c) {
+                    Some(Directive::Off) => state.verbatim = true,
+                    Some(Directive::On) => state.verbatim = false,
+                    None => {}
+                }
+            }
+            if token.is_trivia() || state.verbatim {
+                continue;
+            }
+
+            // `BEGIN` and `END` are settled by the word after them.
+            match mem::take(&mut state.pending) {
+                Pending::Begin if !is_word(&token, TRANSACTION_WORDS) && token != TokenRef::Semicolon => {
+                    state.depth += 1
+                }
+                Pending::End if !is_word(&token, UNCOUNTED_ENDS) => {
+                    state.depth = state.depth.saturating_sub(1);
+                    if is_word(&token, &["CASE"]) {
+                        // `END CASE` closes the `CASE` it names.
+                        continue;
+                    }
+                }
+                _ => {}
+            }
+            if is_word(&token, &["BEGIN"]) {
+                state.pending = Pending::Begin;
+            } else if is_word(&token, &["END"]) {
+                state.pending = Pending::End;
+            } else if is_word(&token, &["CASE"]) {
+                state.depth += 1;
+            } else if token == TokenRef::Semicolon && state.depth == 0 {
+                semicolon = true;
+            }
+        }
+        match (semicolon, at_eof) {
+            (true, true) => Scan::End(text.len()),
+            (true, false) => Scan::NeedMore,
+            (false, _) => Scan::NoEnd,
+        }
+    }
+}
+
+// Finds where the first statement of `text` ends: after its `;` and the
+// rest of that line if it holds only comments, or right after the `;` if
+// more code follows on the line. `at_eof` says no more text is coming.
+fn statement_end(text: &str, dialect: Dialect, at_eof: bool) -> Scan {
+    Scanner::default().scan(text, dialect, at_eof)
+}
+
+// Where the first statement of a whole script ends.
+pub(crate) fn statement_end_at_eof(text: &str, dialect: Dialect) -> usize {
+    match statement_end(text, dialect, true) {
+        Scan::End(end) => end,
+        Scan::NeedMore | Scan::NoEnd => text.len(),
+    }
+}
+
+// `COPY ... FROM stdin;` is followed by data lines up to a `\.` line.
+fn is_copy_from_stdin(statement: &str, dialect: Dialect) -> bool {
+    let mut words = tokens_with(statement, dialect).filter(|t| !t.is_trivia());
+    if !words.next().is_some_and(|t| is_word(&t, &["COPY"])) {
+        return false;
+    }
+    let mut prev = None;
+    for token in words {
+        if is_word(&token, &["STDIN"]) && prev.is_some_and(|p: TokenRef| is_word(&p, &["FROM"])) {
+            return true;
+        }
+        prev = Some(token);
+    }
+    false
+}
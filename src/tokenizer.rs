@@ -1,3 +1,5 @@
+use crate::dialect::Dialect;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Token {
     Keyword(String),
@@ -11,199 +13,555 @@ pub enum Token {
     CloseParen,
     Comment(String),
     Whitespace(String),
+    // Bind parameter: `$1`, `?`, `?1`, `:name`, `@param`, `%s`, `%(name)s`
+    Placeholder(String),
+    // Jinja (dbt) tag: `{{ expression }}`, `{% statement %}`, `{# comment #}`,
+    // with any word characters it is glued to (`stg_{{ name }}`)
+    Template(String),
     Other(String),
 }
 
-const KEYWORDS: &[&str] = &[
-    "SELECT", "FROM", "WHERE", "JOIN", "LEFT", "RIGHT", "INNER", "OUTER",
-    "CROSS", "FULL", "ON", "AND", "OR", "NOT", "IN", "IS", "NULL", "AS",
-    "GROUP", "BY", "ORDER", "HAVING", "LIMIT", "OFFSET", "INSERT", "INTO",
-    "VALUES", "UPDATE", "SET", "DELETE", "CREATE", "TABLE", "DROP", "ALTER",
-    "INDEX", "VIEW", "UNION", "ALL", "DISTINCT", "EXCEPT", "INTERSECT",
-    "EXISTS", "BETWEEN", "LIKE", "CASE", "WHEN", "THEN", "ELSE", "END",
-    "ASC", "DESC", "TRUE", "FALSE", "CAST", "WITH", "RECURSIVE", "PRIMARY",
-    "KEY", "FOREIGN", "REFERENCES", "CONSTRAINT", "DEFAULT", "CHECK",
-    "UNIQUE", "IF", "REPLACE", "TEMPORARY", "TEMP", "RETURNING",
-    "NATURAL", "USING", "FETCH", "NEXT", "ROWS", "ONLY", "FIRST",
-    "NULLS", "LAST", "COUNT", "SUM", "AVG", "MIN", "MAX", "COALESCE",
-    "OVER", "PARTITION", "ROW_NUMBER", "RANK", "DENSE_RANK", "LAG", "LEAD",
-    "WINDOW", "RANGE", "UNBOUNDED", "PRECEDING", "FOLLOWING", "CURRENT",
-    "ROW", "GRANT", "REVOKE", "ROLLBACK", "COMMIT", "BEGIN", "TRANSACTION",
-    "SAVEPOINT", "RELEASE", "TRIGGER", "EXECUTE", "PROCEDURE", "FUNCTION",
-    "DECLARE", "CURSOR", "OPEN", "CLOSE",
-];
+impl Token {
+    /// The source text of this token.
+    pub fn text(&self) -> &str {
+        match self {
+            Token::Keyword(s)
+            | Token::Identifier(s)
+            | Token::StringLiteral(s)
+            | Token::NumberLiteral(s)
+            | Token::Operator(s)
+            | Token::Comment(s)
+            | Token::Whitespace(s)
+            | Token::Placeholder(s)
+            | Token::Template(s)
+            | Token::Other(s) => s,
+            Token::Comma => ",",
+            Token::Semicolon => ";",
+            Token::OpenParen => "(",
+            Token::CloseParen => ")",
+        }
+    }
 
-fn is_keyword(word: &str) -> bool {
-    KEYWORDS.contains(&word.to_uppercase().as_str())
+    pub fn is_trivia(&self) -> bool {
+        matches!(self, Token::Whitespace(_) | Token::Comment(_))
+    }
 }
 
-pub fn tokenize(input: &str) -> Vec<Token> {
-    let mut tokens = Vec::new();
-    let chars: Vec<char> = input.chars().collect();
-    let len = chars.len();
-    let mut i = 0;
+/// A token that borrows its text from the input, as produced by [`tokens`].
+/// [`Token`] is the owned equivalent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenRef<'a> {
+    Keyword(&'a str),
+    Identifier(&'a str),
+    StringLiteral(&'a str),
+    NumberLiteral(&'a str),
+    Operator(&'a str),
+    Comma,
+    Semicolon,
+    OpenParen,
+    CloseParen,
+    Comment(&'a str),
+    Whitespace(&'a str),
+    Placeholder(&'a str),
+    Template(&'a str),
+    Other(&'a str),
+}
 
-    while i < len {
-        let ch = chars[i];
+impl<'a> TokenRef<'a> {
+    /// The source text of this token.
+    pub fn text(&self) -> &'a str {
+        match *self {
+            TokenRef::Keyword(s)
+            | TokenRef::Identifier(s)
+            | TokenRef::StringLiteral(s)
+            | TokenRef::NumberLiteral(s)
+            | TokenRef::Operator(s)
+            | TokenRef::Comment(s)
+            | TokenRef::Whitespace(s)
+            | TokenRef::Placeholder(s)
+            | TokenRef::Template(s)
+            | TokenRef::Other(s) => s,
+            TokenRef::Comma => ",",
+            TokenRef::Semicolon => ";",
+            TokenRef::OpenParen => "(",
+            TokenRef::CloseParen => ")",
+        }
+    }
 
-        // Whitespace
-        if ch.is_ascii_whitespace() {
-            let start = i;
-            while i < len && chars[i].is_ascii_whitespace() {
-                i += 1;
-            }
-            tokens.push(Token::Whitespace(chars[start..i].iter().collect()));
-            continue;
+    pub fn is_trivia(&self) -> bool {
+        matches!(self, TokenRef::Whitespace(_) | TokenRef::Comment(_))
+    }
+}
+
+impl From<TokenRef<'_>> for Token {
+    fn from(token: TokenRef<'_>) -> Self {
+        match token {
+            TokenRef::Keyword(s) => Token::Keyword(s.to_string()),
+            TokenRef::Identifier(s) => Token::Identifier(s.to_string()),
+            TokenRef::StringLiteral(s) => Token::StringLiteral(s.to_string()),
+            TokenRef::NumberLiteral(s) => Token::NumberLiteral(s.to_string()),
+            TokenRef::Operator(s) => Token::Operator(s.to_string()),
+            TokenRef::Comma => Token::Comma,
+            TokenRef::Semicolon => Token::Semicolon,
+            TokenRef::OpenParen => Token::OpenParen,
+            TokenRef::CloseParen => Token::CloseParen,
+            TokenRef::Comment(s) => Token::Comment(s.to_string()),
+            TokenRef::Whitespace(s) => Token::Whitespace(s.to_string()),
+            TokenRef::Placeholder(s) => Token::Placeholder(s.to_string()),
+            TokenRef::Template(s) => Token::Template(s.to_string()),
+            TokenRef::Other(s) => Token::Other(s.to_string()),
         }
+    }
+}
 
-        // Line comment
-        if ch == '-' && i + 1 < len && chars[i + 1] == '-' {
-            let start = i;
-            while i < len && chars[i] != '\n' {
-                i += 1;
-            }
-            tokens.push(Token::Comment(chars[start..i].iter().collect()));
-            continue;
+/// Location of a token in the input: a byte range plus the 1-based line and
+/// column (in characters) where it starts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpannedToken {
+    pub token: Token,
+    pub span: Span,
+}
+
+// Sorted, for binary search (checked at compile time below).
+const KEYWORDS: &[&str] = &[
+    "ALL", "ALTER", "AND", "AS", "ASC", "AVG", "BEGIN", "BETWEEN", "BY",
+    "CASE", "CAST", "CHECK", "CLOSE", "COALESCE", "COMMIT", "CONSTRAINT",
+    "COUNT", "CREATE", "CROSS", "CURRENT", "CURSOR", "DECLARE", "DEFAULT",
+    "DELETE", "DENSE_RANK", "DESC", "DISTINCT", "DROP", "ELSE", "END",
+    "EXCEPT", "EXECUTE", "EXISTS", "FALSE", "FETCH", "FIRST", "FOLLOWING",
+    "FOREIGN", "FROM", "FULL", "FUNCTION", "GRANT", "GROUP", "HAVING", "IF",
+    "IN", "INDEX", "INNER", "INSERT", "INTERSECT", "INTO", "IS", "JOIN",
+    "KEY", "LAG", "LAST", "LEAD", "LEFT", "LIKE", "LIMIT", "MAX", "MIN",
+    "NATURAL", "NEXT", "NOT", "NULL", "NULLS", "OFFSET", "ON", "ONLY",
+    "OPEN", "OR", "ORDER", "OUTER", "OVER", "PARTITION", "PRECEDING",
+    "PRIMARY", "PROCEDURE", "RANGE", "RANK", "RECURSIVE", "REFERENCES",
+    "RELEASE", "REPLACE", "RETURNING", "REVOKE", "RIGHT", "ROLLBACK", "ROW",
+    "ROWS", "ROW_NUMBER", "SAVEPOINT", "SELECT", "SET", "SUM", "TABLE",
+    "TEMP", "TEMPORARY", "THEN", "TRANSACTION", "TRIGGER", "TRUE",
+    "UNBOUNDED", "UNION", "UNIQUE", "UPDATE", "USING", "VALUES", "VIEW",
+    "WHEN", "WHERE", "WINDOW", "WITH",
+];
+
+const _: () = assert!(is_sorted(KEYWORDS));
+
+const fn is_sorted(words: &[&str]) -> bool {
+    let mut i = 1;
+    while i < words.len() {
+        let (a, b) = (words[i - 1].as_bytes(), words[i].as_bytes());
+        let mut j = 0;
+        while j < a.len() && j < b.len() && a[j] == b[j] {
+            j += 1;
         }
+        if j < b.len() && (j == a.len() || a[j] < b[j]) {
+            i += 1;
+        } else {
+            return false;
+        }
+    }
+    true
+}
 
-        // Block comment
-        if ch == '/' && i + 1 < len && chars[i + 1] == '*' {
-            let start = i;
-            i += 2;
-            while i + 1 < len && !(chars[i] == '*' && chars[i + 1] == '/') {
-                i += 1;
-            }
-            if i + 1 < len {
-                i += 2; // skip */
-            }
-            tokens.push(Token::Comment(chars[start..i].iter().collect()));
-            continue;
+// Longest word in KEYWORDS or any dialect's extra keywords.
+const MAX_KEYWORD_LEN: usize = 16;
+
+fn is_keyword(word: &str, dialect: Dialect) -> bool {
+    if word.is_ascii() {
+        if word.len() > MAX_KEYWORD_LEN {
+            return false;
         }
+        let mut buf = [0u8; MAX_KEYWORD_LEN];
+        let upper = &mut buf[..word.len()];
+        upper.copy_from_slice(word.as_bytes());
+        upper.make_ascii_uppercase();
+        let upper = std::str::from_utf8(upper).unwrap_or_default();
+        return KEYWORDS.binary_search(&upper).is_ok() || dialect.extra_keywords().contains(&upper);
+    }
+    // Some non-ASCII letters uppercase to ASCII ones (`ſ` to `S`).
+    let upper = word.to_uppercase();
+    KEYWORDS.contains(&upper.as_str()) || dialect.extra_keywords().contains(&upper.as_str())
+}
 
-        // String literal
-        if ch == '\'' {
-            let start = i;
-            i += 1;
-            while i < len {
-                if chars[i] == '\'' {
-                    if i + 1 < len && chars[i + 1] == '\'' {
-                        i += 2; // escaped quote
-                    } else {
-                        i += 1;
-                        break;
-                    }
-                } else {
-                    i += 1;
-                }
-            }
-            tokens.push(Token::StringLiteral(chars[start..i].iter().collect()));
-            continue;
+// Byte length of the prefix of `s` whose chars satisfy `pred`.
+fn run_len(s: &str, pred: impl Fn(char) -> bool) -> usize {
+    let bytes = s.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        // Only decode when the byte isn't ASCII on its own.
+        let c = match bytes[i] {
+            b if b.is_ascii() => b as char,
+            _ => s[i..].chars().next().unwrap_or_default(),
+        };
+        if !pred(c) {
+            break;
         }
+        i += c.len_utf8();
+    }
+    i
+}
 
-        // Quoted identifier (ANSI SQL, PostgreSQL)
-        if ch == '"' {
-            let start = i;
-            i += 1;
-            while i < len && chars[i] != '"' {
-                i += 1;
-            }
-            if i < len {
-                i += 1;
+// Scans a quoted run starting at `start` (the opening quote) up to and
+// including `close`, or to the end of input. Doubled closing quotes and, if
+// `backslash` is set, backslash escapes stay inside the run.
+fn scan_quoted(bytes: &[u8], start: usize, close: u8, backslash: bool) -> usize {
+    scan_quoted_closed(bytes, start, close, backslash).0
+}
+
+// Like `scan_quoted`, also saying whether the closing quote was found.
+fn scan_quoted_closed(bytes: &[u8], start: usize, close: u8, backslash: bool) -> (usize, bool) {
+    let len = bytes.len();
+    let mut i = start + 1;
+    while i < len {
+        if backslash && bytes[i] == b'\\' {
+            // Skip the escaped character; continuation bytes of a multi-byte
+            // one never match a quote.
+            i += 2;
+        } else if bytes[i] == close {
+            if i + 1 < len && bytes[i + 1] == close {
+                i += 2; // escaped quote
+            } else {
+                return (i + 1, true);
             }
-            tokens.push(Token::Identifier(chars[start..i].iter().collect()));
-            continue;
+        } else {
+            i += 1;
         }
+    }
+    (len, false)
+}
 
-        // Backtick-quoted identifier (MySQL, MariaDB, SQLite)
-        if ch == '`' {
-            let start = i;
-            i += 1;
-            while i < len && chars[i] != '`' {
-                i += 1;
-            }
-            if i < len {
-                i += 1;
-            }
-            tokens.push(Token::Identifier(chars[start..i].iter().collect()));
-            continue;
+// Length of the `$$` / `$tag$` delimiter starting at `start`, if any. Tags
+// follow identifier rules, so `$1` is not a delimiter.
+fn dollar_tag_len(input: &str, start: usize) -> Option<usize> {
+    let rest = &input[start + 1..];
+    let tag = if rest.starts_with(|c: char| c.is_alphabetic() || c == '_') { run_len(rest, is_word_char) } else { 0 };
+    rest[tag..].starts_with('$').then_some(tag + 2)
+}
+
+// End of the dollar-quoted string opened by `tag` at `start`, or the end of
+// input if it is never closed.
+fn scan_dollar_quoted(input: &str, start: usize, tag: &str) -> usize {
+    let body = start + tag.len();
+    input[body..].find(tag).map_or(input.len(), |pos| body + pos + tag.len())
+}
+
+// End of the Jinja tag opened at `start` (`{{`, `{%` or `{#`), or the end
+// of input if it is never closed. A `}}` or `%}` inside a quoted string
+// doesn't close the tag.
+fn scan_template(input: &str, start: usize) -> usize {
+    scan_template_closed(input, start).0
+}
+
+// Like `scan_template`, also saying whether the tag was closed.
+fn scan_template_closed(input: &str, start: usize) -> (usize, bool) {
+    let bytes = input.as_bytes();
+    let close: &[u8] = match bytes[start + 1] {
+        b'{' => b"}}",
+        b'%' => b"%}",
+        _ => {
+            return input[start + 2..]
+                .find("#}")
+                .map_or((input.len(), false), |pos| (start + 2 + pos + 2, true));
+        }
+    };
+    let mut i = start + 2;
+    while i < bytes.len() {
+        match bytes[i] {
+            quote @ (b'\'' | b'"') => i = scan_quoted(bytes, i, quote, true),
+            _ if bytes[i..].starts_with(close) => return (i + 2, true),
+            _ => i += 1,
         }
+    }
+    (bytes.len(), false)
+}
 
-        // Bracket-quoted identifier (MSSQL, T-SQL)
-        if ch == '[' {
-            let start = i;
-            i += 1;
-            while i < len && chars[i] != ']' {
-                i += 1;
-            }
-            if i < len {
-                i += 1;
+fn is_template_start(rest: &str) -> bool {
+    rest.starts_with("{{") || rest.starts_with("{%") || rest.starts_with("{#")
+}
+
+// End of a run of word characters and `{{ ... }}` expressions starting at
+// `start`, which Jinja renders into one word (`{{ schema }}_raw`).
+fn scan_templated_word(input: &str, start: usize) -> usize {
+    let mut i = start;
+    loop {
+        let rest = &input[i..];
+        if rest.starts_with("{{") {
+            i = scan_template(input, i);
+        } else {
+            match run_len(rest, is_word_char) {
+                0 => return i,
+                n => i += n,
             }
-            tokens.push(Token::Identifier(chars[start..i].iter().collect()));
-            continue;
         }
+    }
+}
 
-        // Number
-        if ch.is_ascii_digit() || (ch == '.' && i + 1 < len && chars[i + 1].is_ascii_digit()) {
-            let start = i;
-            while i < len && (chars[i].is_ascii_digit() || chars[i] == '.') {
-                i += 1;
-            }
-            tokens.push(Token::NumberLiteral(chars[start..i].iter().collect()));
-            continue;
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+// Length of the bind parameter starting at `i`, if any.
+fn placeholder_len(input: &str, i: usize, dialect: Dialect) -> Option<usize> {
+    let bytes = input.as_bytes();
+    let rest = &input[i..];
+    let next = rest.get(1..).and_then(|r| r.chars().next());
+    match bytes[i] {
+        // $1
+        b'$' if next.is_some_and(|c| c.is_ascii_digit()) => Some(1 + run_len(&rest[1..], |c| c.is_ascii_digit())),
+        // ? and ?1
+        b'?' if dialect.question_mark_placeholders() => Some(1 + run_len(&rest[1..], |c| c.is_ascii_digit())),
+        // :name, but not the second colon of `::`
+        b':' if next.is_some_and(|c| c.is_alphabetic() || c == '_') && (i == 0 || bytes[i - 1] != b':') => {
+            Some(1 + run_len(&rest[1..], is_word_char))
+        }
+        // @param, @@global
+        b'@' if next.is_some_and(|c| c.is_alphabetic() || c == '_' || c == '@') => {
+            let sigil = if next == Some('@') { 2 } else { 1 };
+            Some(sigil + run_len(&rest[sigil..], is_word_char))
         }
+        // %(name)s
+        b'%' if next == Some('(') => {
+            let close = 2 + rest[2..].find(')')?;
+            let name_ok = rest[2..close].chars().all(is_word_char);
+            (name_ok && rest.as_bytes().get(close + 1).is_some_and(|b| b.is_ascii_alphabetic())).then_some(close + 2)
+        }
+        // %s, but not `%sum` (modulo of a column)
+        b'%' if next == Some('s') && !rest[2..].chars().next().is_some_and(is_word_char) => Some(2),
+        _ => None,
+    }
+}
 
-        // Punctuation
-        match ch {
-            '(' => { tokens.push(Token::OpenParen); i += 1; continue; }
-            ')' => { tokens.push(Token::CloseParen); i += 1; continue; }
-            ',' => { tokens.push(Token::Comma); i += 1; continue; }
-            ';' => { tokens.push(Token::Semicolon); i += 1; continue; }
-            _ => {}
-        }
-
-        // Multi-char operators
-        if i + 1 < len {
-            let two: String = chars[i..i+2].iter().collect();
-            if matches!(two.as_str(), "<>" | "<=" | ">=" | "!=" | "||") {
-                tokens.push(Token::Operator(two));
-                i += 2;
-                continue;
-            }
+pub fn tokenize(input: &str) -> Vec<Token> {
+    tokenize_with(input, Dialect::Generic)
+}
+
+pub fn tokenize_with(input: &str, dialect: Dialect) -> Vec<Token> {
+    tokens_with(input, dialect).map(Token::from).collect()
+}
+
+/// Borrowed tokens of `input`, produced lazily and without copying.
+pub fn tokens(input: &str) -> Tokens<'_> {
+    tokens_with(input, Dialect::Generic)
+}
+
+/// Like [`tokens`], with `dialect`'s quoting, comment and operator rules.
+/// The tokens cover the input without gaps, as with [`tokenize_with`].
+pub fn tokens_with(input: &str, dialect: Dialect) -> Tokens<'_> {
+    Tokens { input, pos: 0, dialect }
+}
+
+// The tokens of `input` from `pos`, which must be where a token starts.
+pub(crate) fn tokens_at(input: &str, pos: usize, dialect: Dialect) -> Tokens<'_> {
+    Tokens { input, pos, dialect }
+}
+
+/// Iterator over the [`TokenRef`]s of an input; see [`tokens_with`].
+#[derive(Debug, Clone)]
+pub struct Tokens<'a> {
+    input: &'a str,
+    pos: usize,
+    dialect: Dialect,
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = TokenRef<'a>;
+
+    fn next(&mut self) -> Option<TokenRef<'a>> {
+        let input = self.input;
+        let bytes = input.as_bytes();
+        let dialect = self.dialect;
+        let len = bytes.len();
+        let start = self.pos;
+        if start >= len {
+            return None;
         }
+        let ch = bytes[start];
+        let next = bytes.get(start + 1).copied();
+        let rest = &input[start..];
 
-        // Single-char operators
-        if matches!(ch, '=' | '<' | '>' | '+' | '-' | '*' | '/' | '%' | '&' | '|' | '^' | '~') {
-            tokens.push(Token::Operator(ch.to_string()));
-            i += 1;
-            continue;
+        // Punctuation
+        let punct = match ch {
+            b'(' => Some(TokenRef::OpenParen),
+            b')' => Some(TokenRef::CloseParen),
+            b',' => Some(TokenRef::Comma),
+            b';' => Some(TokenRef::Semicolon),
+            _ => None,
+        };
+        if punct.is_some() {
+            self.pos = start + 1;
+            return punct;
         }
 
-        // Word (keyword or identifier)
-        if ch.is_alphanumeric() || ch == '_' {
-            let start = i;
-            while i < len && (chars[i].is_alphanumeric() || chars[i] == '_') {
-                i += 1;
+        let (token, end): (fn(&'a str) -> TokenRef<'a>, usize) = if ch.is_ascii_whitespace() {
+            // Whitespace
+            (TokenRef::Whitespace, start + run_len(rest, |c| c.is_ascii_whitespace()))
+        } else if (ch == b'-' && next == Some(b'-')) || (ch == b'#' && dialect.hash_comments()) {
+            // Line comment (`#` in MySQL and BigQuery)
+            (TokenRef::Comment, rest.find('\n').map_or(len, |pos| start + pos))
+        } else if ch == b'/' && next == Some(b'*') {
+            // Block comment, or the rest of the input if it is unterminated
+            (TokenRef::Comment, rest[2..].find("*/").map_or(len, |pos| start + 2 + pos + 2))
+        } else if is_template_start(rest) {
+            // Jinja tag (dbt); an expression takes the word it is part of
+            if next == Some(b'{') {
+                (TokenRef::Template, scan_templated_word(input, start))
+            } else {
+                (TokenRef::Template, scan_template(input, start))
             }
-            let word: String = chars[start..i].iter().collect();
-            if is_keyword(&word) {
-                tokens.push(Token::Keyword(word));
+        } else if ch == b'\'' {
+            // String literal
+            (TokenRef::StringLiteral, scan_quoted(bytes, start, b'\'', dialect.backslash_escapes()))
+        } else if let Some(tag_len) = (ch == b'$' && dialect.dollar_quoted_strings())
+            .then(|| dollar_tag_len(input, start))
+            .flatten()
+        {
+            // Dollar-quoted string (PostgreSQL function bodies)
+            (TokenRef::StringLiteral, scan_dollar_quoted(input, start, &rest[..tag_len]))
+        } else if ch == b'"' {
+            // Quoted identifier (ANSI SQL, PostgreSQL); a string in MySQL and BigQuery
+            if dialect.double_quote_is_identifier() {
+                (TokenRef::Identifier, scan_quoted(bytes, start, b'"', false))
             } else {
-                tokens.push(Token::Identifier(word));
+                (TokenRef::StringLiteral, scan_quoted(bytes, start, b'"', dialect.backslash_escapes()))
             }
-            continue;
-        }
+        } else if ch == b'`' && dialect.backtick_identifiers() {
+            // Backtick-quoted identifier (MySQL, MariaDB, SQLite, BigQuery)
+            (TokenRef::Identifier, scan_quoted(bytes, start, b'`', false))
+        } else if ch == b'[' && dialect.bracket_identifiers() {
+            // Bracket-quoted identifier (MSSQL, T-SQL, SQLite)
+            (TokenRef::Identifier, rest.find(']').map_or(len, |pos| start + pos + 1))
+        } else if ch.is_ascii_digit() || (ch == b'.' && next.is_some_and(|b| b.is_ascii_digit())) {
+            // Number
+            (TokenRef::NumberLiteral, start + run_len(rest, |c| c.is_ascii_digit() || c == '.'))
+        } else if let Some(word) = Some(run_len(rest, is_word_char)).filter(|&n| n > 0) {
+            // Word (keyword or identifier); no operator or bind parameter
+            // starts with a word character.
+            if rest[word..].starts_with("{{") {
+                (TokenRef::Template, scan_templated_word(input, start))
+            } else if is_keyword(&rest[..word], dialect) {
+                (TokenRef::Keyword, start + word)
+            } else {
+                (TokenRef::Identifier, start + word)
+            }
+        } else if let Some(op) = dialect.operators().iter().find(|op| rest.starts_with(*op)) {
+            // Multi-char operators
+            (TokenRef::Operator, start + op.len())
+        } else if let Some(n) = placeholder_len(input, start, self.dialect) {
+            // Bind parameters
+            (TokenRef::Placeholder, start + n)
+        } else if matches!(ch, b'=' | b'<' | b'>' | b'+' | b'-' | b'*' | b'/' | b'%' | b'&' | b'|' | b'^' | b'~' | b'?' | b'.') {
+            // Single-char operators and the dot
+            (TokenRef::Operator, start + 1)
+        } else {
+            // Other
+            (TokenRef::Other, start + rest.chars().next().map_or(1, char::len_utf8))
+        };
+        self.pos = end;
+        Some(token(&input[start..end]))
+    }
+}
 
-        // Dot
-        if ch == '.' {
-            tokens.push(Token::Operator(".".to_string()));
-            i += 1;
-            continue;
+/// A token that runs to the end of the input because it is never closed.
+/// Tokenizing never fails, but the input is almost certainly broken.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenError {
+    /// The opening quote or delimiter.
+    pub span: Span,
+    pub message: String,
+}
+
+/// Unterminated strings, quoted identifiers, block comments and dollar
+/// quotes in `input`.
+pub fn token_errors(input: &str, dialect: Dialect) -> Vec<TokenError> {
+    let mut errors = Vec::new();
+    let (mut offset, mut line, mut column) = (0, 1, 1);
+    for token in tokens_with(input, dialect) {
+        let text = token.text();
+        if let Some((what, opener)) = unterminated(token, dialect) {
+            let span = Span { start: offset, end: offset + opener, line, column };
+            errors.push(TokenError { span, message: format!("unterminated {what}") });
+        }
+        for ch in text.chars() {
+            if ch == '\n' {
+                line += 1;
+                column = 1;
+            } else {
+                column += 1;
+            }
         }
+        offset += text.len();
+    }
+    errors
+}
 
-        // Other
-        tokens.push(Token::Other(ch.to_string()));
-        i += 1;
+// What `token` is and the length of its opening delimiter, if it is never
+// closed.
+pub(crate) fn unterminated(token: TokenRef, dialect: Dialect) -> Option<(&'static str, usize)> {
+    let closed = |text: &str, quote: u8, backslash: bool| scan_quoted_closed(text.as_bytes(), 0, quote, backslash).1;
+    match token {
+        TokenRef::Comment(c) if c.starts_with("/*") => (c.len() < 4 || !c.ends_with("*/")).then_some(("block comment", 2)),
+        TokenRef::StringLiteral(s) if s.starts_with('$') => {
+            let tag = &s[..dollar_tag_len(s, 0)?];
+            (s.len() < 2 * tag.len() || !s.ends_with(tag)).then_some(("dollar-quoted string", tag.len()))
+        }
+        TokenRef::StringLiteral(s) => {
+            let quote = *s.as_bytes().first()?;
+            (!closed(s, quote, dialect.backslash_escapes())).then_some(("string literal", 1))
+        }
+        TokenRef::Identifier(s) if s.starts_with(['"', '`']) => {
+            (!closed(s, s.as_bytes()[0], false)).then_some(("quoted identifier", 1))
+        }
+        TokenRef::Identifier(s) if s.starts_with('[') => (!s.ends_with(']')).then_some(("quoted identifier", 1)),
+        TokenRef::Template(s) => {
+            // Only the last tag of a templated word can be open.
+            let mut open = s.find('{')?;
+            loop {
+                let (end, closed) = scan_template_closed(s, open);
+                if !closed {
+                    return Some(("template tag", 2));
+                }
+                open = end + run_len(&s[end..], is_word_char);
+                if !s[open..].starts_with("{{") {
+                    return None;
+                }
+            }
+        }
+        _ => None,
     }
+}
+
+/// Like `tokenize`, but records where each token came from.
+pub fn tokenize_with_spans(input: &str) -> Vec<SpannedToken> {
+    attach_spans(tokenize(input))
+}
 
+/// Computes spans for a complete token stream (e.g. from `tokenize_with`).
+/// Tokens cover the input without gaps, so their texts give the offsets.
+pub fn attach_spans(tokens: Vec<Token>) -> Vec<SpannedToken> {
+    let mut offset = 0;
+    let mut line = 1;
+    let mut column = 1;
     tokens
+        .into_iter()
+        .map(|token| {
+            let text = token.text();
+            let span = Span { start: offset, end: offset + text.len(), line, column };
+            for ch in text.chars() {
+                if ch == '\n' {
+                    line += 1;
+                    column = 1;
+                } else {
+                    column += 1;
+                }
+            }
+            offset = span.end;
+            SpannedToken { token, span }
+        })
+        .collect()
 }
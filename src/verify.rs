@@ -0,0 +1,130 @@
+use crate::dialect::Dialect;
+use crate::formatter::{beautify_with, minify, FormatOptions};
+use crate::tokenizer::{attach_spans, tokenize, tokenize_with, SpannedToken, Token};
+
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifyError {
+    /// The output does not tokenize to the same tokens as the input.
+    /// `line` is the 1-based line of the output where they first differ.
+    TokensChanged { line: usize, expected: String, found: String },
+    /// Formatting the output again changes it, first at this output line.
+    NotIdempotent { line: usize },
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyError::TokensChanged { line, expected, found } => write!(
+                f,
+                "formatting would change the query at output line {line}: expected '{expected}', found '{found}'"
+            ),
+            VerifyError::NotIdempotent { line } => {
+                write!(f, "formatting is not stable: a second pass changes output line {line}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
+/// Beautifies `input` and checks the result before returning it: the output
+/// must tokenize to the same tokens as the input (whitespace aside, keywords
+/// compared case-insensitively) and beautifying it again must not change it.
+pub fn verify_format(input: &str, dialect: Dialect, opts: &FormatOptions) -> Result<String, VerifyError> {
+    verify_with(input, dialect, true, |tokens| beautify_with(tokens, opts))
+}
+
+/// Like [`verify_format`], for [`minify`]. Comments are not compared since
+/// minify drops them.
+pub fn verify_minify(input: &str, dialect: Dialect) -> Result<String, VerifyError> {
+    verify_with(input, dialect, false, minify)
+}
+
+fn verify_with(
+    input: &str,
+    dialect: Dialect,
+    keep_comments: bool,
+    format: impl Fn(&[Token]) -> String,
+) -> Result<String, VerifyError> {
+    let formatted = format(&tokenize_with(input, dialect));
+
+    // Code tokens must match one for one. Comments must all survive in
+    // order, but may move past punctuation (`b -- note` + `,` becomes
+    // `b, -- note`).
+    let before = attach_spans(tokenize_with(input, dialect));
+    let after = attach_spans(tokenize_with(&formatted, dialect));
+    let is_comment = |t: &&SpannedToken| matches!(t.token, Token::Comment(_));
+    let is_code = |t: &&SpannedToken| !t.token.is_trivia();
+    compare(before.iter().filter(is_code), after.iter().filter(is_code))?;
+    if keep_comments {
+        compare(before.iter().filter(is_comment), after.iter().filter(is_comment))?;
+    }
+
+    let again = format(&tokenize_with(&formatted, dialect));
+    if again != formatted {
+        let line = formatted
+            .lines()
+            .zip(again.lines())
+            .position(|(a, b)| a != b)
+            .unwrap_or_else(|| formatted.lines().count().min(again.lines().count()))
+            + 1;
+        return Err(VerifyError::NotIdempotent { line });
+    }
+    Ok(formatted)
+}
+
+fn compare<'a>(
+    before: impl Iterator<Item = &'a SpannedToken>,
+    after: impl Iterator<Item = &'a SpannedToken>,
+) -> Result<(), VerifyError> {
+    let before: Vec<_> = before.collect();
+    let after: Vec<_> = after.collect();
+    for idx in 0..before.len().max(after.len()) {
+        let (expected, found) = (before.get(idx), after.get(idx));
+        let same = match (expected, found) {
+            (Some(a), Some(b)) => equivalent(&a.token, &b.token),
+            _ => false,
+        };
+        if !same {
+            let line = found.or(after.last()).map_or(1, |t| t.span.line);
+            return Err(VerifyError::TokensChanged {
+                line,
+                expected: expected.map_or("end of input", |t| t.token.text()).to_string(),
+                found: found.map_or("end of input", |t| t.token.text()).to_string(),
+            });
+        }
+    }
+    Ok(())
+}
+
+// Tokens that mean the same thing: keywords differ only in case, an
+// unterminated block comment may lose trailing whitespace, and a
+// dollar-quoted body may have been reformatted (`--format-sql-bodies`) as
+// long as its own tokens are unchanged.
+fn equivalent(a: &Token, b: &Token) -> bool {
+    match (a, b) {
+        (Token::Keyword(x), Token::Keyword(y)) => x.to_uppercase() == y.to_uppercase(),
+        (Token::Comment(x), Token::Comment(y)) if x.starts_with("/*") && !x.ends_with("*/") => x.trim_end() == y.trim_end(),
+        (Token::StringLiteral(x), Token::StringLiteral(y)) if x != y => match (dollar_body(x), dollar_body(y)) {
+            (Some(x), Some(y)) => {
+                let x: Vec<Token> = tokenize(x).into_iter().filter(|t| !matches!(t, Token::Whitespace(_))).collect();
+                let y: Vec<Token> = tokenize(y).into_iter().filter(|t| !matches!(t, Token::Whitespace(_))).collect();
+                x.len() == y.len() && x.iter().zip(&y).all(|(a, b)| equivalent(a, b))
+            }
+            _ => false,
+        },
+        _ => a == b,
+    }
+}
+
+// The text between the tags of `$tag$ ... $tag$`.
+fn dollar_body(text: &str) -> Option<&str> {
+    let tag_len = text.get(1..)?.find('$')? + 2;
+    let tag = &text[..tag_len];
+    if !text.starts_with('$') || text.len() < 2 * tag_len || !text.ends_with(tag) {
+        return None;
+    }
+    Some(&text[tag_len..text.len() - tag_len])
+}
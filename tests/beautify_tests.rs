@@ -1,5 +1,6 @@
-use sqlfmt::tokenizer::tokenize;
-use sqlfmt::formatter::beautify;
+use sqlfmt::dialect::Dialect;
+use sqlfmt::tokenizer::{tokenize, tokenize_with};
+use sqlfmt::formatter::{beautify, beautify_range, beautify_with, CommaStyle, FormatOptions, KeywordCase};
 
 #[test]
 fn beautify_simple_select() {
@@ -219,3 +220,545 @@ FROM
 ";
     assert_eq!(beautify(&tokens), expected);
 }
+
+#[test]
+fn beautify_with_default_options_matches_beautify() {
+    let tokens = tokenize("SELECT a, b FROM t WHERE a = 1; SELECT 2;");
+    assert_eq!(beautify_with(&tokens, &FormatOptions::default()), beautify(&tokens));
+}
+
+#[test]
+fn beautify_with_indent_width() {
+    let tokens = tokenize("SELECT * FROM (SELECT id FROM t);");
+    let opts = FormatOptions { indent_width: 4, ..FormatOptions::default() };
+    let expected = "\
+SELECT
+    *
+FROM
+    (
+        SELECT
+            id
+        FROM
+            t
+    );
+";
+    assert_eq!(beautify_with(&tokens, &opts), expected);
+}
+
+#[test]
+fn beautify_with_tabs() {
+    let tokens = tokenize("CREATE TABLE t (a INT, b TEXT);");
+    let opts = FormatOptions { use_tabs: true, ..FormatOptions::default() };
+    let expected = "\
+CREATE TABLE t (
+\ta INT,
+\tb TEXT
+);
+";
+    assert_eq!(beautify_with(&tokens, &opts), expected);
+}
+
+#[test]
+fn beautify_with_lowercase_keywords() {
+    let tokens = tokenize("SELECT id FROM users GROUP BY id;");
+    let opts = FormatOptions { keyword_case: KeywordCase::Lower, ..FormatOptions::default() };
+    let expected = "\
+select
+  id
+from
+  users
+group by
+  id;
+";
+    assert_eq!(beautify_with(&tokens, &opts), expected);
+}
+
+#[test]
+fn beautify_with_preserved_keyword_case() {
+    let tokens = tokenize("Select id From users left Join b On a.x = b.x;");
+    let opts = FormatOptions { keyword_case: KeywordCase::Preserve, ..FormatOptions::default() };
+    let expected = "\
+Select
+  id
+From
+  users
+left Join
+  b
+On
+  a.x = b.x;
+";
+    assert_eq!(beautify_with(&tokens, &opts), expected);
+}
+
+#[test]
+fn beautify_with_lines_between_statements() {
+    let tokens = tokenize("SELECT 1; DROP TABLE t; SELECT 2;");
+    let opts = FormatOptions { lines_between_statements: 2, ..FormatOptions::default() };
+    let expected = "\
+SELECT
+  1;
+
+
+DROP TABLE t;
+
+
+SELECT
+  2;
+";
+    assert_eq!(beautify_with(&tokens, &opts), expected);
+
+    let opts = FormatOptions { lines_between_statements: 0, ..FormatOptions::default() };
+    let expected = "\
+SELECT
+  1;
+DROP TABLE t;
+SELECT
+  2;
+";
+    assert_eq!(beautify_with(&tokens, &opts), expected);
+}
+
+fn with_width(width: usize) -> FormatOptions {
+    FormatOptions { max_line_width: Some(width), ..FormatOptions::default() }
+}
+
+#[test]
+fn beautify_max_width_keeps_short_clauses_on_one_line() {
+    let tokens = tokenize("SELECT id, name FROM users WHERE id = 1 ORDER BY name;");
+    let expected = "\
+SELECT id, name
+FROM users
+WHERE id = 1
+ORDER BY name;
+";
+    assert_eq!(beautify_with(&tokens, &with_width(80)), expected);
+}
+
+#[test]
+fn beautify_max_width_breaks_long_clauses() {
+    let tokens = tokenize("SELECT first_name, last_name, email FROM users;");
+    let expected = "\
+SELECT
+  first_name,
+  last_name,
+  email
+FROM users;
+";
+    assert_eq!(beautify_with(&tokens, &with_width(30)), expected);
+}
+
+#[test]
+fn beautify_max_width_wraps_long_function_arguments() {
+    let tokens = tokenize("SELECT COALESCE(first_name, middle_name, last_name, 'unknown') AS n, id FROM t;");
+    let expected = "\
+SELECT
+  COALESCE(
+    first_name,
+    middle_name,
+    last_name,
+    'unknown'
+  ) AS n,
+  id
+FROM t;
+";
+    assert_eq!(beautify_with(&tokens, &with_width(40)), expected);
+}
+
+#[test]
+fn beautify_max_width_wraps_long_in_list() {
+    let tokens = tokenize("SELECT id FROM t WHERE id IN (100001, 100002, 100003, 100004) AND b = 2;");
+    let expected = "\
+SELECT id
+FROM t
+WHERE
+  id IN(
+    100001,
+    100002,
+    100003,
+    100004
+  )
+  AND b = 2;
+";
+    assert_eq!(beautify_with(&tokens, &with_width(30)), expected);
+}
+
+#[test]
+fn beautify_max_width_wraps_window_spec_by_clause() {
+    let tokens = tokenize("SELECT RANK() OVER (PARTITION BY dept, region ORDER BY salary DESC) AS r FROM emp;");
+    let expected = "\
+SELECT
+  RANK() OVER(
+    PARTITION BY dept, region
+    ORDER BY salary DESC
+  ) AS r
+FROM emp;
+";
+    assert_eq!(beautify_with(&tokens, &with_width(40)), expected);
+}
+
+#[test]
+fn beautify_max_width_subquery_clauses() {
+    let tokens = tokenize("SELECT * FROM (SELECT id FROM t) AS s;");
+    let expected = "\
+SELECT *
+FROM
+  (
+    SELECT id
+    FROM t
+  ) AS s;
+";
+    assert_eq!(beautify_with(&tokens, &with_width(80)), expected);
+}
+
+fn leading_commas() -> FormatOptions {
+    FormatOptions { comma_style: CommaStyle::Leading, ..FormatOptions::default() }
+}
+
+#[test]
+fn beautify_leading_commas_clause_lists() {
+    let tokens = tokenize("SELECT id, name FROM users GROUP BY id, name;");
+    let expected = "\
+SELECT
+  id
+  , name
+FROM
+  users
+GROUP BY
+  id
+  , name;
+";
+    assert_eq!(beautify_with(&tokens, &leading_commas()), expected);
+}
+
+#[test]
+fn beautify_leading_commas_keep_trailing_comments_on_their_item() {
+    let tokens = tokenize("select a, -- note a\n b, c /* c */, d from t");
+    let expected = "\
+SELECT
+  a -- note a
+  , b
+  , c /* c */
+  , d
+FROM
+  t";
+    assert_eq!(beautify_with(&tokens, &leading_commas()), expected);
+}
+
+#[test]
+fn beautify_leading_commas_follow_comments_before_their_item() {
+    let tokens = tokenize("select a,\n-- own line\nb, -- trailing\n/* one */\n/* two */\nc from t");
+    let expected = "\
+SELECT
+  a
+  -- own line
+  , b -- trailing
+  /* one */
+  /* two */
+  , c
+FROM
+  t";
+    assert_eq!(beautify_with(&tokens, &leading_commas()), expected);
+}
+
+#[test]
+fn beautify_leading_commas_def_list() {
+    let tokens = tokenize("CREATE TABLE t (id INTEGER NOT NULL, price DECIMAL(10, 2));");
+    let expected = "\
+CREATE TABLE t (
+  id INTEGER NOT NULL
+  , price DECIMAL(10, 2)
+);
+";
+    assert_eq!(beautify_with(&tokens, &leading_commas()), expected);
+}
+
+#[test]
+fn beautify_leading_commas_subquery_select_list() {
+    let tokens = tokenize("SELECT * FROM (SELECT a, b FROM t);");
+    let expected = "\
+SELECT
+  *
+FROM
+  (
+    SELECT
+      a
+      , b
+    FROM
+      t
+  );
+";
+    assert_eq!(beautify_with(&tokens, &leading_commas()), expected);
+}
+
+#[test]
+fn beautify_postgres_subscripts_and_casts() {
+    let tokens = tokenize_with("SELECT tags[1], id::text FROM t;", Dialect::PostgreSql);
+    let expected = "\
+SELECT
+  tags[1],
+  id::text
+FROM
+  t;
+";
+    assert_eq!(beautify(&tokens), expected);
+}
+
+#[test]
+fn beautify_keeps_dollar_quoted_body_intact() {
+    let input = "CREATE FUNCTION f() RETURNS trigger AS $$\nBEGIN\n  NEW.updated_at := now();\n  RETURN NEW;\nEND;\n$$ LANGUAGE plpgsql;";
+    let tokens = tokenize(input);
+    let expected = "\
+CREATE FUNCTION f() RETURNS TRIGGER AS $$
+BEGIN
+  NEW.updated_at := now();
+  RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+";
+    assert_eq!(beautify(&tokens), expected);
+}
+
+#[test]
+fn beautify_formats_sql_function_body_when_enabled() {
+    let tokens = tokenize("CREATE FUNCTION add(a integer, b integer) RETURNS integer AS $$ select a + b; $$ LANGUAGE sql;");
+    let opts = FormatOptions { format_sql_bodies: true, ..FormatOptions::default() };
+    let expected = "\
+CREATE FUNCTION add(a integer, b integer) RETURNS integer AS $$
+  SELECT
+    a + b;
+$$ LANGUAGE sql;
+";
+    let formatted = beautify_with(&tokens, &opts);
+    assert_eq!(formatted, expected);
+    assert_eq!(beautify_with(&tokenize(&formatted), &opts), expected);
+}
+
+#[test]
+fn beautify_placeholders_unchanged() {
+    let tokens = tokenize("SELECT * FROM t WHERE a = $1 AND b = :name AND c IN (?, @p, %(x)s);");
+    let expected = "\
+SELECT
+  *
+FROM
+  t
+WHERE
+  a = $1
+  AND b = :name
+  AND c IN(?, @p, %(x)s);
+";
+    assert_eq!(beautify(&tokens), expected);
+}
+
+#[test]
+fn beautify_sqlfmt_off_region_is_verbatim() {
+    let sql = "select a from t;\n\n-- sqlfmt: off\nselect  a,\n        bb\nfrom    t ;\n-- sqlfmt: on\nselect c from u;";
+    let expected = "\
+SELECT
+  a
+FROM
+  t;
+
+-- sqlfmt: off
+select  a,
+        bb
+from    t ;
+-- sqlfmt: on
+
+SELECT
+  c
+FROM
+  u;
+";
+    assert_eq!(beautify(&tokenize(sql)), expected);
+}
+
+#[test]
+fn beautify_inline_sqlfmt_off_block_comment() {
+    let sql = "select a, /* sqlfmt: off */ b  +  c /* sqlfmt: on */, d from t";
+    let expected = "\
+SELECT
+  a,
+  /* sqlfmt: off */ b  +  c /* sqlfmt: on */,
+  d
+FROM
+  t";
+    assert_eq!(beautify(&tokenize(sql)), expected);
+}
+
+#[test]
+fn beautify_sqlfmt_off_without_on_runs_to_end() {
+    let sql = "select 1;\n-- sqlfmt: off\nselect   2;\n\n";
+    assert_eq!(beautify(&tokenize(sql)), "SELECT\n  1;\n\n-- sqlfmt: off\nselect   2;\n");
+}
+
+#[test]
+fn beautify_keeps_trailing_comments_on_their_line() {
+    let sql = "select -- columns\n  id, -- primary key\n  name\nfrom users; -- done\nselect 2;";
+    let expected = "\
+SELECT -- columns
+  id, -- primary key
+  name
+FROM
+  users; -- done
+
+SELECT
+  2;
+";
+    assert_eq!(beautify(&tokenize(sql)), expected);
+
+    let sql = "insert into t values (1, /* two */ 2)";
+    assert_eq!(beautify(&tokenize(sql)), "INSERT INTO\n  t\nVALUES\n  (1, /* two */ 2)");
+}
+
+#[test]
+fn beautify_own_line_comments_start_a_line() {
+    let sql = "select a, b -- trailing\n-- about c\n, c from t\n-- filter\nwhere x = 1";
+    let expected = "\
+SELECT
+  a,
+  b, -- trailing
+  -- about c
+  c
+FROM
+  t
+-- filter
+WHERE
+  x = 1";
+    assert_eq!(beautify(&tokenize(sql)), expected);
+}
+
+#[test]
+fn beautify_keeps_blank_lines_up_to_max() {
+    let sql = "select\n  a,\n\n\n\n  b\nfrom t";
+    assert_eq!(beautify(&tokenize(sql)), "SELECT\n  a,\n\n  b\nFROM\n  t");
+
+    let opts = FormatOptions { max_blank_lines: 2, ..FormatOptions::default() };
+    assert_eq!(beautify_with(&tokenize(sql), &opts), "SELECT\n  a,\n\n\n  b\nFROM\n  t");
+
+    let opts = FormatOptions { max_blank_lines: 0, ..FormatOptions::default() };
+    assert_eq!(beautify_with(&tokenize(sql), &opts), "SELECT\n  a,\n  b\nFROM\n  t");
+}
+
+#[test]
+fn beautify_keeps_blank_lines_between_sections() {
+    let sql = "select a\nfrom t\n\nwhere b = 1\n\nand c = 2\n\norder by a";
+    let expected = "SELECT\n  a\nFROM\n  t\n\nWHERE\n  b = 1\n\n  AND c = 2\n\nORDER BY\n  a";
+    assert_eq!(beautify(&tokenize(sql)), expected);
+    assert_eq!(beautify(&tokenize(expected)), expected);
+
+    let sql = "select a from t\n\n-- section two\nwhere x = 1";
+    assert_eq!(beautify(&tokenize(sql)), "SELECT\n  a\nFROM\n  t\n\n-- section two\nWHERE\n  x = 1");
+
+    let opts = FormatOptions { max_blank_lines: 0, ..FormatOptions::default() };
+    assert_eq!(beautify_with(&tokenize(sql), &opts), "SELECT\n  a\nFROM\n  t\n-- section two\nWHERE\n  x = 1");
+}
+
+#[test]
+fn beautify_range_formats_only_the_touched_statements() {
+    let input = "select  1;\n\n-- two\nselect a,\nb from t; -- end\nselect   3;\n";
+    let opts = FormatOptions::default();
+    let at = |needle: &str| input.find(needle).unwrap();
+    let expected = "select  1;\n\n-- two\nSELECT\n  a,\n  b\nFROM\n  t; -- end\nselect   3;\n";
+    assert_eq!(beautify_range(input, at("b from")..at("b from") + 1, Dialect::Generic, &opts), expected);
+    // An empty range formats the statement holding it.
+    assert_eq!(beautify_range(input, at("a,")..at("a,"), Dialect::Generic, &opts), expected);
+    // A range over comments and blank lines alone changes nothing.
+    assert_eq!(beautify_range(input, at("\n\n")..at("select a"), Dialect::Generic, &opts), input);
+    assert_eq!(
+        beautify_range(input, 0..at("select a") + 1, Dialect::Generic, &opts),
+        "SELECT\n  1;\n\n-- two\nSELECT\n  a,\n  b\nFROM\n  t; -- end\nselect   3;\n"
+    );
+}
+
+#[test]
+fn beautify_jinja_templates() {
+    let sql = "\
+{{ config(materialized='incremental') }}
+select id, stg_{{ var('suffix') }}.total,
+{% for c in cols %}
+{{ c }},
+{% endfor %}
+x from {{ ref('orders') }}
+{% if is_incremental() %}
+where ts > (select max(ts) from {{ this }})
+{% endif %}";
+    let expected = "\
+{{ config(materialized='incremental') }}
+SELECT
+  id,
+  stg_{{ var('suffix') }}.total,
+  {% for c in cols %}
+    {{ c }},
+  {% endfor %}
+  x
+FROM
+  {{ ref('orders') }}
+{% if is_incremental() %}
+  WHERE
+    ts > (
+      SELECT
+        MAX(ts)
+      FROM
+        {{ this }}
+    )
+{% endif %}";
+    assert_eq!(beautify(&tokenize(sql)), expected);
+    assert_eq!(beautify(&tokenize(expected)), expected);
+
+    // A CTE after the config call starts its own line.
+    let sql = "{{ config(materialized='table') }}\nwith a as (select 1) select * from a";
+    assert!(beautify(&tokenize(sql)).starts_with("{{ config(materialized='table') }}\nWITH a AS (\n"));
+
+    // A comma inside a block keeps the block's indent, and each block's
+    // tags line up.
+    let sql = "select {% for c in cols %}{{ c }}{% if not loop.last %}, {% endif %}{% endfor %} from t";
+    let expected = "\
+SELECT
+  {% for c in cols %}
+    {{ c }}
+    {% if not loop.last %}
+      ,
+    {% endif %}
+  {% endfor %}
+FROM
+  t";
+    assert_eq!(beautify(&tokenize(sql)), expected);
+    assert_eq!(beautify(&tokenize(expected)), expected);
+}
+
+#[test]
+fn beautify_jinja_blocks_around_statements() {
+    let sql = "{% for t in tables %}\nselect * from {{ t }};\n{% else %}\nselect 1;\n{% endfor %}\nselect 2;";
+    let expected = "\
+{% for t in tables %}
+  SELECT
+    *
+  FROM
+    {{ t }};
+{% else %}
+  SELECT
+    1;
+{% endfor %}
+
+SELECT
+  2;
+";
+    assert_eq!(beautify(&tokenize(sql)), expected);
+
+    // A statement closed after a block tag ends on the tag's line.
+    let sql = "{% if x %} select 1; {% endif %} /* b */ ; select 2";
+    let expected = "\
+{% if x %}
+  SELECT
+    1;
+{% endif %}; /* b */
+
+SELECT
+  2";
+    assert_eq!(beautify(&tokenize(sql)), expected);
+    assert_eq!(beautify(&tokenize(expected)), expected);
+    let sql = "select 1 {% if x %} where a {% endif %}; select 2";
+    let expected = "SELECT\n  1\n{% if x %}\n  WHERE\n    a\n{% endif %};\n\nSELECT\n  2";
+    assert_eq!(beautify(&tokenize(sql)), expected);
+    assert_eq!(beautify(&tokenize(expected)), expected);
+}
@@ -0,0 +1,108 @@
+use sqlfmt::classify::{classify, classify_with, Classification, StatementType};
+use sqlfmt::dialect::Dialect;
+use sqlfmt::splitter::{split_statements, StatementKind};
+
+fn statement_type(sql: &str) -> StatementType {
+    classify(sql).statement_type
+}
+
+#[test]
+fn classify_names_the_statement() {
+    use StatementType::*;
+    let cases = [
+        ("select 1", Select),
+        ("(select 1) union (select 2)", Select),
+        ("values (1), (2)", Select),
+        ("with a as (delete from t returning *) select * from a", Select),
+        ("with a as (select 1) insert into t select * from a", Insert),
+        ("insert into t values (1)", Insert),
+        ("replace into t values (1)", Insert),
+        ("update t set a = 1", Update),
+        ("delete from t", Delete),
+        ("merge into t using s on t.id = s.id when matched then delete", Merge),
+        ("copy t from stdin", Copy),
+        ("create table t (id int)", CreateTable),
+        ("create or replace temporary table t as select 1", CreateTable),
+        ("create index i on t (a)", Create),
+        ("create view v as select 1", Create),
+        ("alter table t add column a int", Alter),
+        ("comment on table t is 'x'", Alter),
+        ("drop table t", Drop),
+        ("truncate table t", Truncate),
+        ("grant select on t to u", Grant),
+        ("revoke select on t from u", Revoke),
+        ("begin", Transaction),
+        ("begin;", Transaction),
+        ("start transaction", Transaction),
+        ("set transaction isolation level serializable", Transaction),
+        ("commit", Transaction),
+        ("rollback to savepoint s", Transaction),
+        ("begin select 1; end;", Other),
+        ("set search_path = x", Other),
+        ("explain select 1", Other),
+        ("-- nothing", Other),
+    ];
+    for (sql, expected) in cases {
+        assert_eq!(statement_type(sql), expected, "{sql}");
+    }
+}
+
+#[test]
+fn classify_flags_destructive_statements() {
+    let destructive = |sql: &str| classify(sql).destructive;
+    assert!(destructive("drop table t"));
+    assert!(destructive("truncate t"));
+    assert!(destructive("delete from t"));
+    assert!(destructive("DELETE FROM t -- where id = 1"));
+    assert!(destructive("delete from t using (select id from s where x) u"));
+    assert!(destructive("update t set a = (select b from s where s.id = 1)"));
+    assert!(destructive("alter table t drop column a"));
+    assert!(destructive("WITH d AS (DELETE FROM t RETURNING *) SELECT * FROM d"));
+    assert!(destructive("with a as (select 1), u as (update t set x = (select 1 where true)) select * from u"));
+    assert!(!destructive("delete from t where id = 1"));
+    assert!(!destructive("update t set a = 1 where id = 1"));
+    assert!(!destructive("alter table t add column a int"));
+    assert!(!destructive("select 'drop table t'"));
+    assert!(!destructive("with d as (delete from t where id = 1 returning *) select * from d"));
+    assert!(!destructive("with l as (select * from t for update) select * from l"));
+}
+
+#[test]
+fn classify_reports_read_only_and_ddl() {
+    assert_eq!(classify("select * from t where id = 1 for update"), Classification {
+        statement_type: StatementType::Select,
+        read_only: true,
+        destructive: false,
+        ddl: false,
+    });
+    assert!(!classify("select * into t2 from t").read_only);
+    assert!(!classify("with a as (delete from t returning *) select * from a").read_only);
+    assert!(!classify("insert into t values (1)").read_only);
+    assert!(classify("create table t (id int)").ddl);
+    assert!(!classify("grant select on t to u").ddl);
+    assert!(classify("{{ config(materialized='view') }}\nselect * from {{ ref('t') }}").read_only);
+}
+
+#[test]
+fn classify_reads_only_the_first_statement() {
+    let sql = "create trigger t after delete on a begin\n  delete from b;\nend;\ndrop table a;";
+    let c = classify_with(sql, Dialect::Sqlite);
+    assert_eq!(c.statement_type, StatementType::Create);
+    assert!(!c.destructive);
+}
+
+#[test]
+fn classify_works_over_split_statements() {
+    let script = "begin; delete from sessions; delete from users where id = 1; drop table old; commit;";
+    let flagged: Vec<String> =
+        split_statements(script).into_iter().filter(|s| classify(&s.text).destructive).map(|s| s.text).collect();
+    assert_eq!(flagged, ["delete from sessions;", "drop table old;"]);
+    let kinds: Vec<StatementKind> = split_statements(script).into_iter().map(|s| s.kind).collect();
+    assert_eq!(kinds, [
+        StatementKind::Tcl,
+        StatementKind::Dml,
+        StatementKind::Dml,
+        StatementKind::Ddl,
+        StatementKind::Tcl
+    ]);
+}
@@ -193,3 +193,429 @@ fn cli_color_always_overrides_no_color_env() {
     let stdout = String::from_utf8(output.stdout).unwrap();
     assert!(stdout.contains("\x1b["));
 }
+
+#[test]
+fn cli_format_options() {
+    let output = sqlfmt()
+        .args(["--indent", "4", "--keyword-case=lower", "SELECT id FROM t;"])
+        .output()
+        .expect("failed to run sqlfmt");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.trim_end(), "select\n    id\nfrom\n    t;");
+}
+
+// Exit code 2 = usage error (POSIX convention).
+#[test]
+fn cli_invalid_keyword_case_exits_2() {
+    let output = sqlfmt()
+        .args(["--keyword-case", "title", "SELECT 1;"])
+        .output()
+        .expect("failed to run sqlfmt");
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn cli_dialect_flag() {
+    let output = sqlfmt()
+        .args(["-m", "--dialect", "mysql", "SELECT 1 # trailing note\n;"])
+        .output()
+        .expect("failed to run sqlfmt");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.trim(), "SELECT 1;");
+}
+
+// Exit code 2 = usage error (POSIX convention).
+#[test]
+fn cli_unknown_dialect_exits_2() {
+    let output = sqlfmt()
+        .args(["--dialect=oracle", "SELECT 1;"])
+        .output()
+        .expect("failed to run sqlfmt");
+    assert_eq!(output.status.code(), Some(2));
+}
+
+fn temp_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("sqlfmt-{}-{name}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn cli_formats_file_to_stdout() {
+    let dir = temp_dir("file-stdout");
+    let file = dir.join("q.sql");
+    std::fs::write(&file, "select 1;").unwrap();
+
+    let output = sqlfmt()
+        .args(["--color=never", file.to_str().unwrap()])
+        .output()
+        .expect("failed to run sqlfmt");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "SELECT\n  1;\n");
+    assert_eq!(std::fs::read_to_string(&file).unwrap(), "select 1;");
+}
+
+#[test]
+fn cli_write_recurses_directories() {
+    let dir = temp_dir("write-recurse");
+    std::fs::create_dir_all(dir.join("nested")).unwrap();
+    std::fs::create_dir_all(dir.join(".hidden")).unwrap();
+    std::fs::write(dir.join("a.sql"), "select 1;").unwrap();
+    std::fs::write(dir.join("nested/b.SQL"), "select 2").unwrap();
+    std::fs::write(dir.join("notes.txt"), "select 3;").unwrap();
+    std::fs::write(dir.join(".hidden/c.sql"), "select 4;").unwrap();
+
+    let output = sqlfmt()
+        .args(["--write", dir.to_str().unwrap()])
+        .output()
+        .expect("failed to run sqlfmt");
+    assert!(output.status.success());
+    assert_eq!(std::fs::read_to_string(dir.join("a.sql")).unwrap(), "SELECT\n  1;\n");
+    assert_eq!(std::fs::read_to_string(dir.join("nested/b.SQL")).unwrap(), "SELECT\n  2\n");
+    assert_eq!(std::fs::read_to_string(dir.join("notes.txt")).unwrap(), "select 3;");
+    assert_eq!(std::fs::read_to_string(dir.join(".hidden/c.sql")).unwrap(), "select 4;");
+}
+
+#[test]
+fn cli_write_leaves_formatted_files_untouched() {
+    use std::time::{Duration, SystemTime};
+
+    let dir = temp_dir("write-untouched");
+    let file = dir.join("q.sql");
+    std::fs::write(&file, "SELECT\n  1;\n").unwrap();
+    let old = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+    std::fs::File::options().write(true).open(&file).unwrap().set_modified(old).unwrap();
+
+    let output = sqlfmt()
+        .args(["-w", file.to_str().unwrap()])
+        .output()
+        .expect("failed to run sqlfmt");
+    assert!(output.status.success());
+    assert_eq!(std::fs::metadata(&file).unwrap().modified().unwrap(), old);
+}
+
+#[test]
+fn cli_multiple_files_in_order() {
+    let dir = temp_dir("multi");
+    std::fs::write(dir.join("one.sql"), "select 1;").unwrap();
+    std::fs::write(dir.join("two.sql"), "select 2;").unwrap();
+
+    let output = sqlfmt()
+        .args(["-m", dir.join("two.sql").to_str().unwrap(), dir.join("one.sql").to_str().unwrap()])
+        .output()
+        .expect("failed to run sqlfmt");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "SELECT 2;\nSELECT 1;\n");
+}
+
+// Exit code 2 = usage error (POSIX convention).
+#[test]
+fn cli_write_without_paths_exits_2() {
+    let output = sqlfmt()
+        .arg("--write")
+        .output()
+        .expect("failed to run sqlfmt");
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn cli_check_reports_unformatted_files() {
+    let dir = temp_dir("check");
+    let clean = dir.join("clean.sql");
+    let dirty = dir.join("dirty.sql");
+    std::fs::write(&clean, "SELECT\n  1;\n").unwrap();
+    std::fs::write(&dirty, "select 2;").unwrap();
+
+    let output = sqlfmt()
+        .args(["--check", dir.to_str().unwrap()])
+        .output()
+        .expect("failed to run sqlfmt");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), format!("{}\n", dirty.display()));
+    assert_eq!(std::fs::read_to_string(&dirty).unwrap(), "select 2;");
+
+    let output = sqlfmt()
+        .args(["--check", clean.to_str().unwrap()])
+        .output()
+        .expect("failed to run sqlfmt");
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn cli_check_stdin() {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let mut child = sqlfmt()
+        .arg("--check")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to run sqlfmt");
+    child.stdin.take().unwrap().write_all(b"select 1;").unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "<stdin>\n");
+}
+
+#[test]
+fn cli_check_with_write_exits_2() {
+    let output = sqlfmt()
+        .args(["--check", "--write", "."])
+        .output()
+        .expect("failed to run sqlfmt");
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn cli_diff_prints_changes_without_writing() {
+    let dir = temp_dir("diff");
+    let file = dir.join("q.sql");
+    std::fs::write(&file, "select 1;\n").unwrap();
+
+    let output = sqlfmt()
+        .args(["--diff", "--color=never", file.to_str().unwrap()])
+        .output()
+        .expect("failed to run sqlfmt");
+    assert!(output.status.success());
+    let name = file.display();
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!("--- {name}\n+++ {name}\n@@ -1 +1,2 @@\n-select 1;\n+SELECT\n+  1;\n")
+    );
+    assert_eq!(std::fs::read_to_string(&file).unwrap(), "select 1;\n");
+
+    let output = sqlfmt()
+        .args(["--diff", "--check", file.to_str().unwrap()])
+        .output()
+        .expect("failed to run sqlfmt");
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn cli_discovers_config_in_parent_directory() {
+    let dir = temp_dir("config-discover");
+    std::fs::create_dir_all(dir.join("queries")).unwrap();
+    std::fs::write(dir.join("sqlfmt.toml"), "indent = 4\nkeyword-case = \"lower\"\n").unwrap();
+    let file = dir.join("queries/q.sql");
+    std::fs::write(&file, "select 1;").unwrap();
+
+    let output = sqlfmt()
+        .args(["--color=never", file.to_str().unwrap()])
+        .output()
+        .expect("failed to run sqlfmt");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "select\n    1;\n");
+
+    // Flags win over the file; --no-config ignores it.
+    let output = sqlfmt()
+        .args(["--color=never", "--indent=1", file.to_str().unwrap()])
+        .output()
+        .expect("failed to run sqlfmt");
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "select\n 1;\n");
+    let output = sqlfmt()
+        .args(["--color=never", "--no-config", file.to_str().unwrap()])
+        .output()
+        .expect("failed to run sqlfmt");
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "SELECT\n  1;\n");
+}
+
+#[test]
+fn cli_config_include_and_exclude() {
+    let dir = temp_dir("config-globs");
+    std::fs::create_dir_all(dir.join("vendor")).unwrap();
+    std::fs::write(
+        dir.join(".sqlfmt.toml"),
+        "include = [\"**/*.sql\", \"**/*.psql\"]\nexclude = [\"vendor/**\"]\n",
+    )
+    .unwrap();
+    std::fs::write(dir.join("a.psql"), "select 1;").unwrap();
+    std::fs::write(dir.join("b.sql"), "select 2;").unwrap();
+    std::fs::write(dir.join("vendor/c.sql"), "select 3;").unwrap();
+
+    let output = sqlfmt()
+        .args(["--check", dir.to_str().unwrap()])
+        .output()
+        .expect("failed to run sqlfmt");
+    assert_eq!(output.status.code(), Some(1));
+    let expected = format!("{}\n{}\n", dir.join("a.psql").display(), dir.join("b.sql").display());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+}
+
+#[test]
+fn cli_explicit_config_and_errors() {
+    let dir = temp_dir("config-explicit");
+    let config = dir.join("custom.toml");
+    std::fs::write(&config, "tabs = true\n").unwrap();
+
+    let output = sqlfmt()
+        .args(["--color=never", "--config", config.to_str().unwrap(), "select 1"])
+        .output()
+        .expect("failed to run sqlfmt");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "SELECT\n\t1\n");
+
+    std::fs::write(&config, "tabs = maybe\n").unwrap();
+    let output = sqlfmt()
+        .args(["--config", config.to_str().unwrap(), "select 1"])
+        .output()
+        .expect("failed to run sqlfmt");
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("custom.toml:1: invalid value 'maybe' for 'tabs'"), "{stderr}");
+
+    let output = sqlfmt()
+        .args(["--config", config.to_str().unwrap(), "--no-config", "select 1"])
+        .output()
+        .expect("failed to run sqlfmt");
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn cli_verify_refuses_to_emit_changed_queries() {
+    let output = sqlfmt()
+        .args(["--verify", "--color=never", "select 1"])
+        .output()
+        .expect("failed to run sqlfmt");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "SELECT\n  1\n");
+
+    // An unterminated `[name` in a verbatim region takes in the newline
+    // that ends the output.
+    let output = sqlfmt()
+        .args(["--verify", "--", "select 1;\n-- sqlfmt: off\nselect [a"])
+        .output()
+        .expect("failed to run sqlfmt");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("formatting would change the query"));
+}
+
+#[test]
+fn cli_stream_formats_each_statement() {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let sql = "select 1; -- one\nselect 2;\n\n-- three\nselect 3;\nCOPY t (a) FROM stdin;\nx;  y\n\\.\nselect 4";
+    let run = |args: &[&str]| {
+        let mut child = sqlfmt()
+            .args(["--no-config", "--color=never"])
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("failed to run sqlfmt");
+        child.stdin.take().unwrap().write_all(sql.as_bytes()).unwrap();
+        let output = child.wait_with_output().unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    assert_eq!(
+        run(&["--stream"]),
+        "SELECT\n  1; -- one\n\nSELECT\n  2;\n\n-- three\nSELECT\n  3;\n\nCOPY t(a)\nFROM\n  stdin;\nx;  y\n\\.\n\nSELECT\n  4\n"
+    );
+    assert_eq!(run(&["--stream", "-m"]), "SELECT 1;SELECT 2;SELECT 3;COPY t(a) FROM stdin;\nx;  y\n\\.\nSELECT 4\n");
+}
+
+#[test]
+fn cli_stream_reads_only_stdin() {
+    let output = sqlfmt().args(["--stream", "select 1"]).output().expect("failed to run sqlfmt");
+    assert_eq!(output.status.code(), Some(2));
+    let output = sqlfmt().args(["--stream", "--check"]).output().expect("failed to run sqlfmt");
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn cli_missing_file_is_an_error_not_sql() {
+    for args in [&["--check", "migrations/typo.sql"][..], &["nonexistent.sql"], &["--diff", "docs\\guide.md"]] {
+        let output = sqlfmt().args(["--no-config"]).args(args).output().expect("failed to run sqlfmt");
+        assert_eq!(output.status.code(), Some(2), "{args:?}");
+        assert!(String::from_utf8(output.stderr).unwrap().contains("is not a file or directory"), "{args:?}");
+    }
+    // SQL with a `/` in it is still SQL.
+    let output = sqlfmt().args(["--no-config", "select a/b from t"]).output().expect("failed to run sqlfmt");
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "SELECT\n  a / b\nFROM\n  t\n");
+}
+
+#[test]
+fn cli_lsp_takes_no_inputs() {
+    for args in [&["lsp", "query.sql"][..], &["lsp", "--write"], &["lsp", "-m"]] {
+        let output = sqlfmt().args(args).output().expect("failed to run sqlfmt");
+        assert_eq!(output.status.code(), Some(2), "{args:?}");
+    }
+}
+
+#[test]
+fn cli_range_formats_only_those_statements() {
+    let dir = temp_dir("range");
+    let path = dir.join("proc.sql");
+    let original = "select  1;\nselect a,\nb from t;\nselect  3;";
+    std::fs::write(&path, original).unwrap();
+
+    let output = sqlfmt().args(["--no-config", "--range", "3"]).arg(&path).output().expect("failed to run sqlfmt");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "select  1;\nSELECT\n  a,\n  b\nFROM\n  t;\nselect  3;");
+
+    let output = sqlfmt().args(["--no-config", "--range", "bytes=0:3", "-w"]).arg(&path).output().expect("failed to run sqlfmt");
+    assert!(output.status.success());
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "SELECT\n  1;\nselect a,\nb from t;\nselect  3;");
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    for args in [&["--range", "0:2", "x"][..], &["--range", "5:2", "x"], &["--range", "rows=1", "x"], &["--range", "1", "-m", "x"]] {
+        let output = sqlfmt().args(args).output().expect("failed to run sqlfmt");
+        assert_eq!(output.status.code(), Some(2), "{args:?}");
+    }
+}
+
+#[test]
+fn cli_formats_markdown_code_blocks() {
+    let dir = temp_dir("markdown");
+    let path = dir.join("guide.md");
+    std::fs::write(&path, "# Guide\n\n```sql\nselect 1;\n```\n\n```mysql\nSELECT\n  `a`;\n```\n\n```sql\nselect 2\n```\n")
+        .unwrap();
+
+    let output = sqlfmt().args(["--no-config", "--check"]).arg(&path).output().expect("failed to run sqlfmt");
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout, format!("{0}:3\n{0}:12\n", path.display()));
+
+    let output = sqlfmt().args(["--no-config", "-w"]).arg(&path).output().expect("failed to run sqlfmt");
+    assert!(output.status.success());
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        "# Guide\n\n```sql\nSELECT\n  1;\n```\n\n```mysql\nSELECT\n  `a`;\n```\n\n```sql\nSELECT\n  2\n```\n"
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn cli_embedded_formats_sql_strings_in_programs() {
+    let dir = temp_dir("embedded");
+    std::fs::write(dir.join("notes.txt"), "select 1").unwrap();
+    std::fs::write(dir.join("query.sql"), "SELECT\n  1;\n").unwrap();
+    let path = dir.join("store.go");
+    std::fs::write(&path, "package store\n\nfunc f() {\n\tdb.Exec(`update t set a = 1`)\n\tdb.Exec(`\n\t\tSELECT\n\t\t  1\n\t`)\n}\n")
+        .unwrap();
+
+    let output = sqlfmt().args(["--no-config", "--embedded", "--check"]).arg(&dir).output().expect("failed to run sqlfmt");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), format!("{}:4\n", path.display()));
+
+    let output = sqlfmt().args(["--no-config", "--embedded", "-w"]).arg(&dir).output().expect("failed to run sqlfmt");
+    assert!(output.status.success());
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        "package store\n\nfunc f() {\n\tdb.Exec(`\n\t\tUPDATE t\n\t\tSET\n\t\t  a = 1\n\t`)\n\tdb.Exec(`\n\t\tSELECT\n\t\t  1\n\t`)\n}\n"
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    for args in [&["--embedded"][..], &["--embedded", "-m", "x.py"], &["--embedded", "--range", "1", "x.py"]] {
+        let output = sqlfmt().args(args).output().expect("failed to run sqlfmt");
+        assert_eq!(output.status.code(), Some(2), "{args:?}");
+    }
+}
@@ -39,3 +39,10 @@ fn colorize_wraps_comments() {
     let colored = colorize(&formatted, &Palette::ansi());
     assert!(colored.contains("\x1b[2m-- a note\x1b[0m"));
 }
+
+#[test]
+fn colorize_wraps_placeholders() {
+    let formatted = beautify(&tokenize("SELECT * FROM t WHERE id = $1;"));
+    let colored = colorize(&formatted, &Palette::ansi());
+    assert!(colored.contains("\x1b[35m$1\x1b[0m"));
+}
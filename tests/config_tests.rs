@@ -0,0 +1,76 @@
+use sqlfmt::config::{glob_match, ColorWhen, Config};
+use sqlfmt::dialect::Dialect;
+use sqlfmt::formatter::{CommaStyle, KeywordCase};
+
+#[test]
+fn parse_all_keys() {
+    let config = Config::parse(
+        r#"
+# project settings
+dialect = "postgres"
+indent = 4
+tabs = false
+keyword-case = 'lower'   # trailing comment
+max-line-width = 100
+commas = "leading"
+format-sql-bodies = true
+lines-between-statements = 2
+color = "never"
+include = ["**/*.sql", "**/*.psql"]
+exclude = [
+  "vendor/**",   # third-party
+  "*.gen.sql",
+]
+"#,
+    )
+    .unwrap();
+    assert_eq!(config.dialect, Some(Dialect::PostgreSql));
+    assert_eq!(config.indent_width, Some(4));
+    assert_eq!(config.use_tabs, Some(false));
+    assert_eq!(config.keyword_case, Some(KeywordCase::Lower));
+    assert_eq!(config.max_line_width, Some(100));
+    assert_eq!(config.comma_style, Some(CommaStyle::Leading));
+    assert_eq!(config.format_sql_bodies, Some(true));
+    assert_eq!(config.lines_between_statements, Some(2));
+    assert_eq!(config.color, Some(ColorWhen::Never));
+    assert_eq!(config.include, Some(vec!["**/*.sql".to_string(), "**/*.psql".to_string()]));
+    assert_eq!(config.exclude, Some(vec!["vendor/**".to_string(), "*.gen.sql".to_string()]));
+}
+
+#[test]
+fn parse_errors_name_the_line() {
+    assert_eq!(Config::parse("indent = 2\nwidth = 80").unwrap_err(), "2: unknown key 'width'");
+    assert_eq!(
+        Config::parse("\nkeyword-case = \"shout\"").unwrap_err(),
+        "2: invalid value 'shout' for 'keyword-case' [possible values: upper, lower, preserve]"
+    );
+    assert_eq!(
+        Config::parse("indent = \"4\"").unwrap_err(),
+        "1: invalid value for 'indent': expected a non-negative integer"
+    );
+    assert!(Config::parse("[format]\nindent = 4").unwrap_err().starts_with("1: tables"));
+    assert!(Config::parse("exclude = [\"a\"").unwrap_err().starts_with("1: unclosed array"));
+}
+
+#[test]
+fn merge_prefers_the_overriding_layer() {
+    let mut base = Config::parse("indent = 4\ndialect = \"mysql\"").unwrap();
+    let flags = Config { indent_width: Some(8), ..Config::default() };
+    base.merge(&flags);
+    assert_eq!(base.indent_width, Some(8));
+    assert_eq!(base.dialect, Some(Dialect::MySql));
+    assert_eq!(base.format_options().indent_width, 8);
+    assert_eq!(base.format_options().keyword_case, KeywordCase::Upper);
+}
+
+#[test]
+fn glob_matching() {
+    assert!(glob_match("**/*.sql", "a.sql"));
+    assert!(glob_match("**/*.sql", "x/y/a.sql"));
+    assert!(glob_match("migrations/*.sql", "migrations/001.sql"));
+    assert!(!glob_match("migrations/*.sql", "migrations/old/001.sql"));
+    assert!(glob_match("vendor/**", "vendor/lib/x.sql"));
+    assert!(glob_match("*.gen.sql", "deep/dir/models.gen.sql"));
+    assert!(glob_match("q?.sql", "q1.sql"));
+    assert!(!glob_match("*.sql", "a.psql"));
+}
@@ -0,0 +1 @@
+- --sqlfmt:off
\ No newline at end of file
@@ -0,0 +1,3 @@
+y--
+
+;
\ No newline at end of file
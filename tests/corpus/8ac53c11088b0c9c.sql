@@ -0,0 +1,2 @@
+--sqlfmt:off
+[
\ No newline at end of file
@@ -0,0 +1,3 @@
+)--
+;/*
+l
\ No newline at end of file
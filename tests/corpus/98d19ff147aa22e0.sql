@@ -0,0 +1 @@
+1 .n
\ No newline at end of file
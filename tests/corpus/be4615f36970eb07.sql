@@ -0,0 +1,2 @@
+SET d
+/**/,
\ No newline at end of file
@@ -0,0 +1,3 @@
+4--
+;--sqlfmt:off
+-
\ No newline at end of file
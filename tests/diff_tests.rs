@@ -0,0 +1,58 @@
+use sqlfmt::diff::{colorize, unified};
+
+#[test]
+fn identical_inputs_have_no_diff() {
+    assert_eq!(unified("SELECT\n  1;\n", "SELECT\n  1;\n", "a", "b"), "");
+}
+
+#[test]
+fn single_line_change() {
+    let diff = unified("select 1;\n", "SELECT\n  1;\n", "q.sql", "q.sql");
+    assert_eq!(diff, "--- q.sql\n+++ q.sql\n@@ -1 +1,2 @@\n-select 1;\n+SELECT\n+  1;\n");
+}
+
+#[test]
+fn distant_changes_get_separate_hunks() {
+    let old: String = (1..=20).map(|n| format!("{n}\n")).collect();
+    let new: String = (1..=20)
+        .map(|n| match n {
+            2 => "two\n".to_string(),
+            18 => "eighteen\n".to_string(),
+            _ => format!("{n}\n"),
+        })
+        .collect();
+    let diff = unified(&old, &new, "a", "b");
+    assert_eq!(diff.matches("@@ -").count(), 2);
+    assert!(diff.contains("@@ -1,5 +1,5 @@\n 1\n-2\n+two\n 3\n 4\n 5\n"));
+    assert!(diff.contains("@@ -15,6 +15,6 @@\n"));
+}
+
+#[test]
+fn missing_final_newline_is_marked() {
+    let diff = unified("select 1;", "SELECT 1;\n", "a", "b");
+    assert_eq!(
+        diff,
+        "--- a\n+++ b\n@@ -1 +1 @@\n-select 1;\n\\ No newline at end of file\n+SELECT 1;\n"
+    );
+}
+
+#[test]
+fn colorize_marks_removed_and_added_lines() {
+    let colored = colorize("--- a\n+++ b\n@@ -1 +1 @@\n-x\n+y\n");
+    assert!(colored.contains("\x1b[31m-x\x1b[0m\n"));
+    assert!(colored.contains("\x1b[32m+y\x1b[0m\n"));
+    assert!(colored.contains("\x1b[36m@@ -1 +1 @@\x1b[0m\n"));
+}
+
+#[test]
+fn large_inputs_diff_in_linear_space() {
+    // Keeping every round of the search would take gigabytes here.
+    let old: String = (0..20_000).map(|n| format!("select {n};\n")).collect();
+    let new: String = (0..20_000)
+        .map(|n| if n % 10 == 0 { format!("SELECT {n};\n") } else { format!("select {n};\n") })
+        .collect();
+    let diff = unified(&old, &new, "a", "b");
+    assert_eq!(diff.matches("\n-select ").count(), 2_000);
+    assert_eq!(diff.matches("\n+SELECT ").count(), 2_000);
+    assert!(diff.contains("@@ -8,7 +8,7 @@\n select 7;\n select 8;\n select 9;\n-select 10;\n+SELECT 10;\n select 11;\n"));
+}
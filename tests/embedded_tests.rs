@@ -0,0 +1,99 @@
+use sqlfmt::dialect::Dialect;
+use sqlfmt::embedded::{beautify_embedded, sql_strings, Language};
+use sqlfmt::formatter::FormatOptions;
+
+use std::path::Path;
+
+fn beautify(source: &str, language: Language) -> String {
+    beautify_embedded(source, language, Dialect::Generic, &FormatOptions::default())
+}
+
+#[test]
+fn language_from_extension() {
+    assert_eq!(Language::from_path(Path::new("app/db.py")), Some(Language::Python));
+    assert_eq!(Language::from_path(Path::new("src/main.RS")), Some(Language::Rust));
+    assert_eq!(Language::from_path(Path::new("store.go")), Some(Language::Go));
+    assert_eq!(Language::from_path(Path::new("query.sql")), None);
+}
+
+#[test]
+fn sql_strings_finds_call_sites_and_markers() {
+    let source = r#"
+# cursor.execute("""select 'comment'""")
+def load(cursor):
+    cursor.execute("""select a from t where id = %s""", (1,))
+    cursor.execute("select 'one line'")
+    cursor.execute(f"""select {column} from t""")
+    print("""not sql""")
+    QUERY = '''--sql
+    select 1'''
+"#;
+    let found: Vec<(usize, String)> =
+        sql_strings(source, Language::Python).into_iter().map(|s| (s.line, s.indent)).collect();
+    assert_eq!(found, [(4, "    ".to_string()), (8, "    ".to_string())]);
+
+    let source = r##"
+let user = sqlx::query_as!(User, "select * from users where id = $1", id);
+let n = conn.execute(r#"delete from "t""#, [])?;
+let label = 'q'; // query!("select 2")
+log::info!("select 3");
+"##;
+    let found: Vec<usize> = sql_strings(source, Language::Rust).iter().map(|s| s.line).collect();
+    assert_eq!(found, [2, 3]);
+
+    let source = "rows, err := db.QueryContext(ctx, `select 1`, id)\nmsg := `select 2`\n";
+    let found: Vec<usize> = sql_strings(source, Language::Go).iter().map(|s| s.line).collect();
+    assert_eq!(found, [1]);
+}
+
+#[test]
+fn beautify_embedded_python_keeps_the_host_indentation() {
+    let source = r#"def load(cursor, id):
+    cursor.execute("""select a, b from t where id = %s""", (id,))
+    cursor.execute(
+        """
+        select a from t
+        where b = 'it''s \\ "x"'
+        """
+    )
+"#;
+    let expected = r#"def load(cursor, id):
+    cursor.execute("""
+        SELECT
+          a,
+          b
+        FROM
+          t
+        WHERE
+          id = %s
+    """, (id,))
+    cursor.execute(
+        """
+        SELECT
+          a
+        FROM
+          t
+        WHERE
+          b = 'it''s \\ "x"'
+        """
+    )
+"#;
+    assert_eq!(beautify(source, Language::Python), expected);
+    assert_eq!(beautify(expected, Language::Python), expected);
+}
+
+#[test]
+fn beautify_embedded_rust_and_go_keep_escaping() {
+    let source = "    let row = sqlx::query!(\"select \\\"id\\\" from t where a = '\\\\n'\", a);\n";
+    let expected = "    let row = sqlx::query!(\"\n        SELECT\n          \\\"id\\\"\n        FROM\n          t\n        WHERE\n          a = '\\\\n'\n    \", a);\n";
+    assert_eq!(beautify(source, Language::Rust), expected);
+    assert_eq!(beautify(expected, Language::Rust), expected);
+
+    // Escapes that can't be written back as they were are left alone.
+    let source = r#"cursor.execute("""select '\N{BULLET}'""")"#;
+    assert_eq!(beautify(source, Language::Python), source);
+
+    let source = "func f() {\n\tdb.Exec(`update t set a = 1`)\n}\n";
+    let expected = "func f() {\n\tdb.Exec(`\n\t\tUPDATE t\n\t\tSET\n\t\t  a = 1\n\t`)\n}\n";
+    assert_eq!(beautify(source, Language::Go), expected);
+}
@@ -0,0 +1,296 @@
+use sqlfmt::json::{self, Value};
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+// A scripted client for `sqlfmt lsp`.
+struct Client {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    stdout: BufReader<ChildStdout>,
+    next_id: usize,
+    // Notifications from the server, oldest first.
+    notifications: Vec<Value>,
+}
+
+impl Client {
+    fn start(args: &[&str]) -> Client {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_sqlfmt"))
+            .arg("lsp")
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("failed to run sqlfmt lsp");
+        let stdin = child.stdin.take();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        let mut client = Client { child, stdin, stdout, next_id: 1, notifications: Vec::new() };
+        let init = client.request("initialize", json(r#"{"processId":null,"rootUri":null,"capabilities":{}}"#));
+        assert!(init.get("result").is_some(), "{init}");
+        client.notify("initialized", json("{}"));
+        client
+    }
+
+    fn send(&mut self, message: Value) {
+        let body = message.to_string();
+        let stdin = self.stdin.as_mut().unwrap();
+        write!(stdin, "Content-Length: {}\r\n\r\n{body}", body.len()).unwrap();
+        stdin.flush().unwrap();
+    }
+
+    fn receive(&mut self) -> Value {
+        let mut length = 0;
+        loop {
+            let mut line = String::new();
+            assert!(self.stdout.read_line(&mut line).unwrap() > 0, "server closed its output");
+            if line.trim_end().is_empty() {
+                break;
+            }
+            if let Some(value) = line.strip_prefix("Content-Length:") {
+                length = value.trim().parse().unwrap();
+            }
+        }
+        let mut body = vec![0; length];
+        self.stdout.read_exact(&mut body).unwrap();
+        json::parse(&String::from_utf8(body).unwrap()).unwrap()
+    }
+
+    // Sends a request and returns its response, keeping any notifications
+    // that come first.
+    fn request(&mut self, method: &str, params: Value) -> Value {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.send(Value::object([
+            ("jsonrpc", "2.0".into()),
+            ("id", id.into()),
+            ("method", method.into()),
+            ("params", params),
+        ]));
+        loop {
+            let message = self.receive();
+            if message.get("id") == Some(&Value::from(id)) {
+                return message;
+            }
+            self.notifications.push(message);
+        }
+    }
+
+    fn notify(&mut self, method: &str, params: Value) {
+        self.send(Value::object([("jsonrpc", "2.0".into()), ("method", method.into()), ("params", params)]));
+    }
+
+    // Waits for the next notification of `method`.
+    fn notification(&mut self, method: &str) -> Value {
+        loop {
+            if let Some(i) = self.notifications.iter().position(|n| n.get("method") == Some(&method.into())) {
+                return self.notifications.remove(i);
+            }
+            let message = self.receive();
+            self.notifications.push(message);
+        }
+    }
+
+    fn open(&mut self, uri: &str, text: &str) {
+        let document = Value::object([
+            ("uri", uri.into()),
+            ("languageId", "sql".into()),
+            ("version", 1usize.into()),
+            ("text", text.into()),
+        ]);
+        self.notify("textDocument/didOpen", Value::object([("textDocument", document)]));
+    }
+
+    // Shuts the server down and returns its exit code.
+    fn exit(mut self) -> i32 {
+        let shutdown = self.request("shutdown", Value::Null);
+        assert_eq!(shutdown.get("result"), Some(&Value::Null));
+        self.notify("exit", Value::Null);
+        self.stdin = None;
+        self.child.wait().unwrap().code().unwrap()
+    }
+}
+
+fn json(text: &str) -> Value {
+    json::parse(text).unwrap()
+}
+
+fn document(uri: &str) -> Value {
+    Value::object([("textDocument", Value::object([("uri", uri.into())]))])
+}
+
+fn with(mut params: Value, field: &str, value: Value) -> Value {
+    if let Value::Object(fields) = &mut params {
+        fields.push((field.to_string(), value));
+    }
+    params
+}
+
+fn position(line: usize, character: usize) -> Value {
+    Value::object([("line", line.into()), ("character", character.into())])
+}
+
+// A text edit as (start, end, new text), positions as (line, character).
+type Edit = ((u64, u64), (u64, u64), String);
+
+// The edits in a formatting response.
+fn edits(response: &Value) -> Vec<Edit> {
+    let pos = |v: &Value, key: &str| {
+        let p = v.get(key).unwrap();
+        (p.get("line").unwrap().as_u64().unwrap(), p.get("character").unwrap().as_u64().unwrap())
+    };
+    response
+        .get("result")
+        .and_then(Value::as_array)
+        .unwrap_or_else(|| panic!("no edits in {response}"))
+        .iter()
+        .map(|edit| {
+            let range = edit.get("range").unwrap();
+            (pos(range, "start"), pos(range, "end"), edit.get("newText").unwrap().as_str().unwrap().to_string())
+        })
+        .collect()
+}
+
+#[test]
+fn lsp_initialize_reports_capabilities() {
+    let mut client = Client::start(&["--no-config"]);
+    let init = client.request("initialize", json("{}"));
+    let capabilities = init.pointer(&["result", "capabilities"]).unwrap();
+    assert_eq!(capabilities.get("documentFormattingProvider"), Some(&Value::Bool(true)));
+    assert_eq!(capabilities.get("documentRangeFormattingProvider"), Some(&Value::Bool(true)));
+    assert_eq!(
+        capabilities.pointer(&["documentOnTypeFormattingProvider", "firstTriggerCharacter"]),
+        Some(&";".into())
+    );
+    let types = capabilities.pointer(&["semanticTokensProvider", "legend", "tokenTypes"]).unwrap();
+    assert_eq!(types.as_array().unwrap()[0], "keyword".into());
+    assert_eq!(client.exit(), 0);
+}
+
+#[test]
+fn lsp_formats_documents() {
+    let mut client = Client::start(&["--no-config"]);
+    client.open("file:///tmp/a.sql", "select a, b from t where x = 1;");
+    let options = json(r#"{"tabSize":4,"insertSpaces":true}"#);
+    let response = client.request("textDocument/formatting", with(document("file:///tmp/a.sql"), "options", options));
+    assert_eq!(edits(&response), [(
+        (0, 0),
+        (0, 31),
+        "SELECT\n    a,\n    b\nFROM\n    t\nWHERE\n    x = 1;\n".to_string()
+    )]);
+
+    // Formatted documents need no edits.
+    client.notify(
+        "textDocument/didChange",
+        json(r#"{"textDocument":{"uri":"file:///tmp/a.sql","version":2},"contentChanges":[{"text":"SELECT\n  1;\n"}]}"#),
+    );
+    let response = client.request("textDocument/formatting", document("file:///tmp/a.sql"));
+    assert_eq!(edits(&response), []);
+
+    let response = client.request("textDocument/formatting", document("file:///tmp/closed.sql"));
+    assert_eq!(response.pointer(&["error", "code"]), Some(&Value::from(-32602i64)));
+    assert_eq!(client.exit(), 0);
+}
+
+#[test]
+fn lsp_range_formatting_covers_whole_statements() {
+    let mut client = Client::start(&["--no-config"]);
+    let text = "select 1;\nselect a,\nb from t;\n  select   2;\n";
+    client.open("untitled:1", text);
+    let range = Value::object([("start", position(2, 1)), ("end", position(2, 3))]);
+    let response = client.request("textDocument/rangeFormatting", with(document("untitled:1"), "range", range));
+    assert_eq!(edits(&response), [((1, 0), (2, 9), "SELECT\n  a,\n  b\nFROM\n  t;".to_string())]);
+    assert_eq!(client.exit(), 0);
+}
+
+#[test]
+fn lsp_on_type_formatting_formats_the_closed_statement() {
+    let mut client = Client::start(&["--no-config"]);
+    client.open("untitled:1", "select 1;\nselect 'a;' from t;");
+    let params = |line, character| {
+        let params = with(document("untitled:1"), "position", position(line, character));
+        with(params, "ch", ";".into())
+    };
+    let response = client.request("textDocument/onTypeFormatting", params(1, 19));
+    assert_eq!(edits(&response), [((1, 0), (1, 19), "SELECT\n  'a;'\nFROM\n  t;".to_string())]);
+    // The `;` inside the string closes nothing.
+    let response = client.request("textDocument/onTypeFormatting", params(1, 10));
+    assert_eq!(edits(&response), []);
+    assert_eq!(client.exit(), 0);
+}
+
+#[test]
+fn lsp_semantic_tokens_follow_token_kinds() {
+    let mut client = Client::start(&["--no-config"]);
+    client.open("untitled:1", "select é, 'x' -- c\n  from t where id = :id");
+    let response = client.request("textDocument/semanticTokens/full", document("untitled:1"));
+    let data: Vec<u64> = response
+        .pointer(&["result", "data"])
+        .and_then(Value::as_array)
+        .unwrap()
+        .iter()
+        .map(|v| v.as_u64().unwrap())
+        .collect();
+    #[rustfmt::skip]
+    assert_eq!(data, [
+        0, 0, 6, 0, 0, // select
+        0, 7, 1, 1, 0, // é
+        0, 3, 3, 2, 0, // 'x'
+        0, 4, 4, 5, 0, // -- c
+        1, 2, 4, 0, 0, // from
+        0, 5, 1, 1, 0, // t
+        0, 2, 5, 0, 0, // where
+        0, 6, 2, 1, 0, // id
+        0, 3, 1, 4, 0, // =
+        0, 2, 3, 6, 0, // :id
+    ]);
+    assert_eq!(client.exit(), 0);
+}
+
+#[test]
+fn lsp_publishes_diagnostics_for_unterminated_tokens() {
+    let mut client = Client::start(&["--no-config"]);
+    client.open("file:///tmp/b.sql", "select 1;\nselect 'oops from t;");
+    let published = client.notification("textDocument/publishDiagnostics");
+    let diagnostics = published.pointer(&["params", "diagnostics"]).and_then(Value::as_array).unwrap();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].get("message"), Some(&"unterminated string literal".into()));
+    assert_eq!(diagnostics[0].pointer(&["range", "start"]), Some(&position(1, 7)));
+    assert_eq!(diagnostics[0].pointer(&["range", "end"]), Some(&position(1, 8)));
+
+    client.notify(
+        "textDocument/didChange",
+        json(r#"{"textDocument":{"uri":"file:///tmp/b.sql","version":2},"contentChanges":[{"range":{"start":{"line":1,"character":12},"end":{"line":1,"character":12}},"text":"'"}]}"#),
+    );
+    let published = client.notification("textDocument/publishDiagnostics");
+    assert_eq!(published.pointer(&["params", "version"]), Some(&Value::from(2usize)));
+    assert_eq!(published.pointer(&["params", "diagnostics"]), Some(&Value::Array(Vec::new())));
+    assert_eq!(client.exit(), 0);
+}
+
+#[test]
+fn lsp_rejects_bad_messages_and_exits_with_1_without_shutdown() {
+    let mut client = Client::start(&["--no-config"]);
+    let stdin = client.stdin.as_mut().unwrap();
+    stdin.write_all(b"Content-Length: 5\r\n\r\n{oops").unwrap();
+    let response = client.receive();
+    assert_eq!(response.pointer(&["error", "code"]), Some(&Value::from(-32700i64)));
+    let response = client.request("workspace/frobnicate", json("{}"));
+    assert_eq!(response.pointer(&["error", "code"]), Some(&Value::from(-32601i64)));
+    client.notify("exit", Value::Null);
+    assert_eq!(client.child.wait().unwrap().code(), Some(1));
+}
+
+#[test]
+fn json_round_trips() {
+    let text = r#"{"a":[1,-2.5,true,null],"b":"q\"\\\n\u00e9\ud83d\ude00","c":{}}"#;
+    let value = json(text);
+    assert_eq!(value.pointer(&["b"]).and_then(Value::as_str), Some("q\"\\\né😀"));
+    assert_eq!(value.to_string(), r#"{"a":[1,-2.5,true,null],"b":"q\"\\\né😀","c":{}}"#);
+    assert_eq!(json::parse(&value.to_string()).unwrap(), value);
+    // A high surrogate without its low half doesn't take the next escape.
+    assert_eq!(json(r#""\ud800\u0041""#), Value::from("\u{fffd}A"));
+    assert_eq!(json(r#""\ud800\ud800\udc00""#), Value::from("\u{fffd}\u{10000}"));
+    assert!(json::parse("[1,]").is_err());
+    assert!(json::parse("\"\\u12\"").is_err());
+    assert!(json::parse(&"[".repeat(1000)).is_err());
+}
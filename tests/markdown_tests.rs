@@ -0,0 +1,85 @@
+use sqlfmt::dialect::Dialect;
+use sqlfmt::formatter::FormatOptions;
+use sqlfmt::markdown::{beautify_markdown, format_blocks, sql_blocks};
+
+#[test]
+fn sql_blocks_finds_sql_and_dialect_fences() {
+    let doc = "\
+```sql
+select 1;
+```
+```rust
+let x = 1;
+```
+  ~~~MySQL {.numberLines}
+  select `a`;
+  ~~~
+```psql
+=> select 1;
+```
+````postgresql
+```
+select 2;
+````
+```sql
+select 3;
+";
+    let blocks = sql_blocks(doc);
+    let found: Vec<(usize, Option<Dialect>, &str, String)> =
+        blocks.iter().map(|b| (b.line, b.dialect, b.indent.as_str(), b.code(doc))).collect();
+    assert_eq!(found, [
+        (1, None, "", "select 1;\n".to_string()),
+        (7, Some(Dialect::MySql), "  ", "select `a`;\n".to_string()),
+        (13, Some(Dialect::PostgreSql), "", "```\nselect 2;\n".to_string()),
+    ]);
+}
+
+#[test]
+fn beautify_markdown_rewrites_only_the_blocks() {
+    let doc = "\
+Intro with `select x` inline.
+
+1. Step:
+
+   ```sql
+   select a, b from t
+   where s = 'x
+     y';
+   ```
+
+Text after.
+";
+    let expected = "\
+Intro with `select x` inline.
+
+1. Step:
+
+   ```sql
+   SELECT
+     a,
+     b
+   FROM
+     t
+   WHERE
+     s = 'x
+     y';
+   ```
+
+Text after.
+";
+    let opts = FormatOptions::default();
+    assert_eq!(beautify_markdown(doc, Dialect::Generic, &opts), expected);
+    assert_eq!(beautify_markdown(expected, Dialect::Generic, &opts), expected);
+}
+
+#[test]
+fn format_blocks_leaves_declined_and_empty_blocks() {
+    let doc = "```sql\n```\n```sql\nselect 1;\n```\n```sql\nselect 2;\n```\n";
+    let mut seen = Vec::new();
+    let out = format_blocks(doc, |block, code| {
+        seen.push(block.line);
+        (block.line == 6).then(|| code.to_uppercase())
+    });
+    assert_eq!(seen, [3, 6]);
+    assert_eq!(out, "```sql\n```\n```sql\nselect 1;\n```\n```sql\nSELECT 2;\n```\n");
+}
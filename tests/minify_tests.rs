@@ -1,4 +1,5 @@
-use sqlfmt::tokenizer::tokenize;
+use sqlfmt::dialect::Dialect;
+use sqlfmt::tokenizer::{tokenize, tokenize_with};
 use sqlfmt::formatter::minify;
 
 #[test]
@@ -42,3 +43,41 @@ fn minify_dot_qualified_keyword_column() {
     let tokens = tokenize("SELECT t.count, t.key FROM t;");
     assert_eq!(minify(&tokens), "SELECT t.COUNT,t.KEY FROM t;");
 }
+
+#[test]
+fn minify_postgres_subscripts_and_casts() {
+    let tokens = tokenize_with("SELECT tags [ 1 ], id :: text FROM t;", Dialect::PostgreSql);
+    assert_eq!(minify(&tokens), "SELECT tags[1],id::text FROM t;");
+}
+
+#[test]
+fn minify_keeps_dollar_quoted_body_intact() {
+    let tokens = tokenize("CREATE FUNCTION f() AS $$\n  SELECT   1;\n$$ LANGUAGE sql;");
+    assert_eq!(minify(&tokens), "CREATE FUNCTION f() AS $$\n  SELECT   1;\n$$ LANGUAGE sql;");
+}
+
+#[test]
+fn minify_placeholders_unchanged() {
+    let tokens = tokenize("SELECT * FROM t WHERE a = $1 AND b = :name AND c = %(x)s;");
+    assert_eq!(minify(&tokens), "SELECT * FROM t WHERE a=$1 AND b=:name AND c=%(x)s;");
+    // A modulo must not run into what follows and read back as `%s`.
+    let tokens = tokenize("SELECT a % s, b % (c) AS x FROM t;");
+    assert_eq!(minify(&tokens), "SELECT a% s,b% (c) AS x FROM t;");
+}
+
+#[test]
+fn minify_keeps_templates_intact() {
+    let tokens = tokenize("select {{ col }} ,  t_{{ n }}.x\nfrom {{ ref('t') }}\n{% if a %} where b {% endif %}");
+    assert_eq!(minify(&tokens), "SELECT {{ col }},t_{{ n }}.x FROM {{ ref('t') }} {% if a %} WHERE b {% endif %}");
+}
+
+#[test]
+fn minify_keeps_sqlfmt_off_region() {
+    let sql = "select a ,  b from t;\n-- sqlfmt: off\nselect  1 ;\n-- sqlfmt: on\nselect   2;";
+    assert_eq!(
+        minify(&tokenize(sql)),
+        "SELECT a,b FROM t; -- sqlfmt: off\nselect  1 ;\n-- sqlfmt: on\nSELECT 2;"
+    );
+    let sql = "select x, /* sqlfmt: off */ a  +  b /* sqlfmt: on */ from t";
+    assert_eq!(minify(&tokenize(sql)), "SELECT x, /* sqlfmt: off */ a  +  b /* sqlfmt: on */ FROM t");
+}
@@ -0,0 +1,104 @@
+use sqlfmt::parser::{parse, NodeKind, SyntaxNode};
+use sqlfmt::tokenizer::{tokenize, Token};
+
+fn kinds(node: &SyntaxNode) -> Vec<NodeKind> {
+    node.descendants().iter().map(|n| n.kind).collect()
+}
+
+#[test]
+fn parse_is_lossless() {
+    let inputs = [
+        "SELECT id, name FROM users WHERE id = 1;",
+        "  -- header\nselect a.id , count( * ) from a\n\tjoin b on a.id = b.id /* note */ ;\n\n",
+        "CREATE TABLE t (id INTEGER, price DECIMAL(10, 2));",
+        "SELECT CASE WHEN a THEN 1 ELSE 2 END FROM t;",
+        "",
+        "   \n-- only a comment\n",
+    ];
+    for input in inputs {
+        assert_eq!(parse(&tokenize(input)).text(), input);
+    }
+}
+
+#[test]
+fn parse_splits_statements_and_clauses() {
+    let tree = parse(&tokenize("SELECT a, b FROM t GROUP BY a; DELETE FROM t WHERE a = 1;"));
+    let statements: Vec<_> = tree.statements().collect();
+    assert_eq!(statements.len(), 2);
+
+    let clauses: Vec<_> = statements[0].nodes().filter_map(|n| n.keyword()).collect();
+    assert_eq!(clauses, vec!["SELECT", "FROM", "GROUP BY"]);
+    let clauses: Vec<_> = statements[1].nodes().filter_map(|n| n.keyword()).collect();
+    assert_eq!(clauses, vec!["DELETE FROM", "WHERE"]);
+}
+
+#[test]
+fn parse_select_items_are_expressions() {
+    let tree = parse(&tokenize("SELECT a + 1, b FROM t;"));
+    let select = tree.statements().next().unwrap().nodes().next().unwrap();
+    assert_eq!(select.keyword().as_deref(), Some("SELECT"));
+    let items: Vec<_> = select.nodes().map(|n| n.text().trim().to_string()).collect();
+    assert_eq!(items, vec!["a + 1", "b"]);
+    assert!(select.nodes().all(|n| n.kind == NodeKind::Expression));
+}
+
+#[test]
+fn parse_function_calls_case_and_subqueries() {
+    let tree = parse(&tokenize(
+        "SELECT COUNT(*), CASE WHEN x > 0 THEN 'p' ELSE 'n' END FROM (SELECT x FROM t) s WHERE y IN (1, 2);",
+    ));
+    let found = kinds(&tree.root);
+    assert!(found.contains(&NodeKind::FunctionCall));
+    assert!(found.contains(&NodeKind::Case));
+    assert!(found.contains(&NodeKind::Subquery));
+    assert!(found.contains(&NodeKind::Parenthesized));
+
+    let subquery = tree.root.descendants().into_iter().find(|n| n.kind == NodeKind::Subquery).unwrap();
+    assert_eq!(subquery.text().trim(), "(SELECT x FROM t)");
+    let case = tree.root.descendants().into_iter().find(|n| n.kind == NodeKind::Case).unwrap();
+    assert_eq!(case.text().trim(), "CASE WHEN x > 0 THEN 'p' ELSE 'n' END");
+}
+
+#[test]
+fn parse_insert_column_list_is_not_a_call() {
+    let tree = parse(&tokenize("INSERT INTO users (id, name) VALUES (1, 'a');"));
+    assert!(!kinds(&tree.root).contains(&NodeKind::FunctionCall));
+}
+
+#[test]
+fn parse_window_spec_clauses() {
+    let tree = parse(&tokenize("SELECT RANK() OVER (PARTITION BY a ORDER BY b) FROM t;"));
+    let keywords: Vec<_> = tree.root.descendants().iter().filter_map(|n| n.keyword()).collect();
+    assert_eq!(keywords, vec!["SELECT", "PARTITION BY", "ORDER BY", "FROM"]);
+}
+
+#[test]
+fn parse_attaches_comments_as_trivia() {
+    let tree = parse(&tokenize("-- header\nSELECT id -- the id\nFROM t;"));
+    let tokens = tree.root.tokens();
+    assert_eq!(tokens[0].token, Token::Keyword("SELECT".into()));
+    assert_eq!(tokens[0].comments().collect::<Vec<_>>(), vec!["-- header"]);
+    assert_eq!(tokens[1].token, Token::Identifier("id".into()));
+    assert_eq!(tokens[1].comments().collect::<Vec<_>>(), vec!["-- the id"]);
+    assert!(tokens[2].comments().next().is_none());
+}
+
+#[test]
+fn parse_tolerates_malformed_sql() {
+    let inputs = [
+        "SELECT (a, b FROM t;",
+        "SELECT a) FROM t;",
+        "SELECT CASE WHEN a THEN 1 FROM t",
+        ")))",
+        "((((",
+        "WHERE AND OR ,,, ;;",
+        "SELECT 'unterminated",
+    ];
+    for input in inputs {
+        let tree = parse(&tokenize(input));
+        assert_eq!(tree.text(), input);
+    }
+
+    let tree = parse(&tokenize("SELECT a) FROM t;"));
+    assert!(kinds(&tree.root).contains(&NodeKind::Error));
+}
@@ -0,0 +1,379 @@
+// Property tests: random inputs checked against the tokenizer and formatter
+// invariants. SQLFMT_PROPTEST_CASES runs more cases and SQLFMT_PROPTEST_SEED
+// replays a run. A failing input is minimized and saved to tests/corpus/,
+// which `corpus_cases_hold` replays on every run.
+
+use sqlfmt::dialect::Dialect;
+use sqlfmt::formatter::{beautify, minify};
+use sqlfmt::tokenizer::{tokenize, tokenize_with, Token};
+
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+
+const DEFAULT_CASES: usize = 2000;
+
+struct Rng(u64);
+
+impl Rng {
+    // xorshift64*
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        items[self.below(items.len())]
+    }
+
+    fn chance(&mut self, percent: usize) -> bool {
+        self.below(100) < percent
+    }
+}
+
+fn env_number(name: &str) -> Option<u64> {
+    let value = std::env::var(name).ok()?;
+    match value.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+fn seed() -> u64 {
+    env_number("SQLFMT_PROPTEST_SEED").unwrap_or(0x5eed_5a1f)
+}
+
+fn cases() -> usize {
+    env_number("SQLFMT_PROPTEST_CASES").map_or(DEFAULT_CASES, |n| n as usize)
+}
+
+// ---------------------------------------------------------------------------
+// Generators
+
+const KEYWORDS: &[&str] = &[
+    "SELECT", "select", "FROM", "WHERE", "AND", "OR", "NOT", "IN", "IS", "NULL", "JOIN", "LEFT", "ON",
+    "GROUP", "ORDER", "BY", "HAVING", "LIMIT", "AS", "CASE", "WHEN", "THEN", "ELSE", "END", "UNION",
+    "ALL", "INSERT", "INTO", "VALUES", "UPDATE", "SET", "DELETE", "CREATE", "TABLE", "WITH", "OVER",
+    "PARTITION", "DISTINCT", "BETWEEN", "EXISTS", "DESC", "BEGIN", "COMMIT", "DEFAULT", "PRIMARY", "KEY",
+];
+const IDENTIFIERS: &[&str] = &["a", "b", "id", "users", "t1", "\"Quoted Name\"", "`tick`", "[br]", "x.y", "t.*", "_n"];
+const NUMBERS: &[&str] = &["1", "42", "3.14", "1e5", ".5", "0"];
+const STRINGS: &[&str] = &["'x'", "'it''s'", "''", "$$ body $$", "$q$ x; $q$", "'multi\nline'"];
+const OPERATORS: &[&str] = &["=", "<>", "<=", ">=", "!=", "||", "+", "-", "*", "/", "%", "::", "->", "->>", "<", ">"];
+const PUNCTUATION: &[&str] = &[",", ";", "(", ")", "[", "]", "."];
+const PLACEHOLDERS: &[&str] = &["$1", "?", ":name", "@p"];
+const COMMENTS: &[&str] = &["-- note\n", "/* c */", "/* multi\n line */", "-- sqlfmt: off\n", "-- sqlfmt: on\n"];
+const WHITESPACE: &[&str] = &[" ", "  ", "\n", "\n\n", "\n\n\n", "\t", " \n  "];
+const FUNCTIONS: &[&str] = &["count", "COALESCE", "max", "lower", "sum"];
+
+// Free-form text from a small alphabet that is dense in SQL delimiters, so
+// unterminated quotes and comments come up often.
+fn random_text(rng: &mut Rng) -> String {
+    const ALPHABET: &[char] = &[
+        'a', 'b', 'S', 'E', 'L', '1', '.', ' ', '\n', '\t', '\'', '"', '`', '[', ']', '$', '-', '/', '*', '#',
+        ':', '?', '@', '%', '(', ')', ',', ';', '=', '<', '>', '!', '|', '\\', 'é', '字', 'e', '_', '{', '}',
+    ];
+    let len = rng.below(40);
+    (0..len).map(|_| ALPHABET[rng.below(ALPHABET.len())]).collect()
+}
+
+// A soup of SQL tokens with random spacing.
+fn token_soup(rng: &mut Rng) -> String {
+    let mut out = String::new();
+    for _ in 0..rng.below(30) {
+        let piece = match rng.below(10) {
+            0 | 1 => rng.pick(KEYWORDS),
+            2 => rng.pick(IDENTIFIERS),
+            3 => rng.pick(NUMBERS),
+            4 => rng.pick(STRINGS),
+            5 => rng.pick(OPERATORS),
+            6 => rng.pick(PUNCTUATION),
+            7 => rng.pick(PLACEHOLDERS),
+            8 => rng.pick(COMMENTS),
+            _ => rng.pick(FUNCTIONS),
+        };
+        out.push_str(piece);
+        out.push_str(if rng.chance(80) { " " } else { rng.pick(WHITESPACE) });
+    }
+    out
+}
+
+// Statements shaped like real queries, with comments and blank lines
+// sprinkled between tokens.
+fn random_sql(rng: &mut Rng) -> String {
+    let mut out = String::new();
+    for _ in 0..1 + rng.below(3) {
+        statement(rng, &mut out, 0);
+        out.push(';');
+        out.push_str(rng.pick(WHITESPACE));
+    }
+    out
+}
+
+fn sep(rng: &mut Rng, out: &mut String) {
+    match rng.below(20) {
+        0 => out.push_str(rng.pick(COMMENTS)),
+        1 => out.push_str(rng.pick(WHITESPACE)),
+        _ => {}
+    }
+    out.push(' ');
+}
+
+fn statement(rng: &mut Rng, out: &mut String, depth: usize) {
+    match rng.below(4) {
+        0 if depth == 0 => {
+            out.push_str("INSERT INTO ");
+            out.push_str(rng.pick(IDENTIFIERS));
+            out.push_str(" (a, b) VALUES (");
+            expr(rng, out, depth + 1);
+            out.push_str(", ");
+            expr(rng, out, depth + 1);
+            out.push(')');
+        }
+        1 if depth == 0 => {
+            out.push_str("UPDATE t SET a = ");
+            expr(rng, out, depth + 1);
+            sep(rng, out);
+            out.push_str("WHERE ");
+            expr(rng, out, depth + 1);
+        }
+        _ => select(rng, out, depth),
+    }
+}
+
+fn select(rng: &mut Rng, out: &mut String, depth: usize) {
+    out.push_str(if rng.chance(50) { "SELECT" } else { "select" });
+    sep(rng, out);
+    for i in 0..1 + rng.below(4) {
+        if i > 0 {
+            out.push(',');
+            sep(rng, out);
+        }
+        expr(rng, out, depth + 1);
+        if rng.chance(20) {
+            out.push_str(" AS ");
+            out.push_str(rng.pick(IDENTIFIERS));
+        }
+    }
+    sep(rng, out);
+    out.push_str("FROM ");
+    if depth < 2 && rng.chance(20) {
+        out.push('(');
+        select(rng, out, depth + 1);
+        out.push_str(") s");
+    } else {
+        out.push_str(rng.pick(IDENTIFIERS));
+    }
+    if rng.chance(30) {
+        sep(rng, out);
+        out.push_str("LEFT JOIN u ON ");
+        expr(rng, out, depth + 1);
+    }
+    if rng.chance(50) {
+        sep(rng, out);
+        out.push_str("WHERE ");
+        expr(rng, out, depth + 1);
+    }
+    if rng.chance(20) {
+        sep(rng, out);
+        out.push_str("GROUP BY a HAVING ");
+        expr(rng, out, depth + 1);
+    }
+    if rng.chance(20) {
+        sep(rng, out);
+        out.push_str("ORDER BY a DESC LIMIT 10");
+    }
+}
+
+fn expr(rng: &mut Rng, out: &mut String, depth: usize) {
+    let leaf = depth > 3 || rng.chance(40);
+    if leaf {
+        let atom = match rng.below(4) {
+            0 => rng.pick(NUMBERS),
+            1 => rng.pick(STRINGS),
+            2 => rng.pick(PLACEHOLDERS),
+            _ => rng.pick(IDENTIFIERS),
+        };
+        out.push_str(atom);
+        return;
+    }
+    match rng.below(6) {
+        0 => {
+            out.push_str(rng.pick(FUNCTIONS));
+            out.push('(');
+            expr(rng, out, depth + 1);
+            out.push(')');
+        }
+        1 => {
+            out.push_str("CASE WHEN ");
+            expr(rng, out, depth + 1);
+            out.push_str(" THEN ");
+            expr(rng, out, depth + 1);
+            out.push_str(" ELSE ");
+            expr(rng, out, depth + 1);
+            out.push_str(" END");
+        }
+        2 if depth < 3 => {
+            out.push_str("a IN (");
+            select(rng, out, depth + 1);
+            out.push(')');
+        }
+        3 => {
+            out.push('(');
+            expr(rng, out, depth + 1);
+            out.push(')');
+        }
+        _ => {
+            expr(rng, out, depth + 1);
+            out.push(' ');
+            out.push_str(rng.pick(&["=", "<>", "+", "-", "*", "AND", "OR", "||", "<="]));
+            sep(rng, out);
+            expr(rng, out, depth + 1);
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Invariants
+
+fn dialects() -> impl Iterator<Item = Dialect> {
+    Dialect::NAMES.iter().filter_map(|name| Dialect::from_name(name))
+}
+
+fn no_panic<T>(what: &str, f: impl FnOnce() -> T) -> Result<T, String> {
+    panic::catch_unwind(AssertUnwindSafe(f)).map_err(|_| format!("{what} panicked"))
+}
+
+// `tokenize` never panics and its tokens cover the input exactly.
+fn check_tokens(input: &str) -> Result<(), String> {
+    for dialect in dialects() {
+        let tokens = no_panic("tokenize", || tokenize_with(input, dialect))?;
+        let text: String = tokens.iter().map(Token::text).collect();
+        if text != input {
+            return Err(format!("token texts do not reproduce the input ({dialect:?})"));
+        }
+    }
+    Ok(())
+}
+
+// `beautify` is idempotent and keeps the query minify sees.
+fn check_formatting(input: &str) -> Result<(), String> {
+    let once = no_panic("beautify", || beautify(&tokenize(input)))?;
+    let twice = no_panic("beautify", || beautify(&tokenize(&once)))?;
+    if twice != once {
+        return Err("beautify is not idempotent".to_string());
+    }
+    let minified = no_panic("minify", || minify(&tokenize(input)))?;
+    if minify(&tokenize(&once)) != minified {
+        return Err("minify(beautify(x)) != minify(x)".to_string());
+    }
+    Ok(())
+}
+
+fn check_all(input: &str) -> Result<(), String> {
+    check_tokens(input)?;
+    check_formatting(input)
+}
+
+// ---------------------------------------------------------------------------
+// Minimization and the regression corpus
+
+fn corpus_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/corpus")
+}
+
+// Greedily drops chunks of characters, halving the chunk size, as long as
+// the input still fails with the same error.
+fn minimize(input: &str, error: &str) -> String {
+    let still_fails = |s: &str| check_all(s).err().as_deref() == Some(error);
+    let mut chars: Vec<char> = input.chars().collect();
+    let mut chunk = chars.len().div_ceil(2).max(1);
+    loop {
+        let mut start = 0;
+        let mut shrunk = false;
+        while start < chars.len() {
+            let end = (start + chunk).min(chars.len());
+            let candidate: String = chars[..start].iter().chain(&chars[end..]).collect();
+            if still_fails(&candidate) {
+                chars = candidate.chars().collect();
+                shrunk = true;
+            } else {
+                start += chunk;
+            }
+        }
+        if chunk == 1 && !shrunk {
+            break;
+        }
+        if !shrunk {
+            chunk = chunk.div_ceil(2);
+        }
+    }
+    chars.into_iter().collect()
+}
+
+fn fnv1a(text: &str) -> u64 {
+    text.bytes().fold(0xcbf2_9ce4_8422_2325, |h, b| (h ^ b as u64).wrapping_mul(0x100_0000_01b3))
+}
+
+// Minimizes a failing input, saves it to the corpus and fails the test.
+fn report(input: &str, error: &str, seed: u64, case: usize) -> ! {
+    let small = minimize(input, error);
+    let dir = corpus_dir();
+    let path = dir.join(format!("{:016x}.sql", fnv1a(&small)));
+    let saved = std::fs::create_dir_all(&dir).and_then(|_| std::fs::write(&path, &small));
+    let note = match saved {
+        Ok(()) => format!("saved to {}", path.display()),
+        Err(e) => format!("could not save it: {e}"),
+    };
+    panic!("{error} (seed {seed:#x}, case {case}); minimized input {small:?} {note}");
+}
+
+fn run(generate: fn(&mut Rng) -> String, check: fn(&str) -> Result<(), String>, salt: u64) {
+    let mut rng = Rng((seed() ^ salt) | 1);
+    for case in 0..cases() {
+        let input = generate(&mut rng);
+        if let Err(error) = check(&input) {
+            report(&input, &error, seed(), case);
+        }
+    }
+}
+
+#[test]
+fn tokenize_covers_random_text() {
+    run(random_text, check_tokens, 1);
+}
+
+#[test]
+fn tokenize_covers_token_soup() {
+    run(token_soup, check_tokens, 2);
+}
+
+#[test]
+fn formatting_invariants_hold_for_queries() {
+    run(random_sql, check_all, 3);
+}
+
+#[test]
+fn formatting_invariants_hold_for_token_soup() {
+    run(token_soup, check_all, 4);
+}
+
+#[test]
+fn corpus_cases_hold() {
+    let Ok(entries) = std::fs::read_dir(corpus_dir()) else {
+        return;
+    };
+    let mut paths: Vec<PathBuf> = entries.filter_map(|e| e.ok().map(|e| e.path())).collect();
+    paths.sort();
+    for path in paths.iter().filter(|p| p.extension().is_some_and(|e| e == "sql")) {
+        let input = std::fs::read_to_string(path).unwrap();
+        if let Err(error) = check_all(&input) {
+            panic!("{}: {error}", path.display());
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use sqlfmt::dialect::Dialect;
+use sqlfmt::splitter::{
+    split_statements, split_statements_with, statement_range, Chunk, StatementKind, StatementReader,
+};
+use sqlfmt::tokenizer::Span;
+
+fn read(sql: &str, dialect: Dialect) -> Vec<Chunk> {
+    let chunks: Vec<Chunk> = StatementReader::new(sql.as_bytes(), dialect).map(Result::unwrap).collect();
+    let text: String = chunks
+        .iter()
+        .map(|c| match c {
+            Chunk::Statement(s) | Chunk::Data(s) => s.as_str(),
+        })
+        .collect();
+    assert_eq!(text, sql, "chunks must cover the input");
+    chunks
+}
+
+fn statements(sql: &str, dialect: Dialect) -> Vec<String> {
+    read(sql, dialect)
+        .into_iter()
+        .map(|c| match c {
+            Chunk::Statement(s) => s,
+            Chunk::Data(d) => panic!("unexpected data {d:?}"),
+        })
+        .collect()
+}
+
+#[test]
+fn reader_splits_on_semicolons_outside_quotes_and_comments() {
+    let sql = "select 'a;b', \"c;d\" /* ; */ -- ;\nfrom t; select $$ x; $$;\nselect `e;f`";
+    assert_eq!(statements(sql, Dialect::Generic), [
+        "select 'a;b', \"c;d\" /* ; */ -- ;\nfrom t; ",
+        "select $$ x; $$;\n",
+        "select `e;f`",
+    ]);
+}
+
+#[test]
+fn reader_keeps_comments_on_the_semicolon_line() {
+    let sql = "select 1; -- one\n-- about two\nselect 2; /* two\n */\nselect 3;\n";
+    assert_eq!(statements(sql, Dialect::Generic), [
+        "select 1; -- one\n",
+        "-- about two\nselect 2; /* two\n */\n",
+        "select 3;\n",
+    ]);
+}
+
+#[test]
+fn reader_keeps_begin_end_blocks_together() {
+    let sql = "\
+BEGIN;
+CREATE TRIGGER t AFTER INSERT ON x BEGIN
+  UPDATE y SET a = CASE WHEN b THEN 1 END;
+  IF a THEN SELECT 1; END IF;
+END;
+COMMIT;
+";
+    assert_eq!(statements(sql, Dialect::Sqlite), [
+        "BEGIN;\n",
+        "CREATE TRIGGER t AFTER INSERT ON x BEGIN\n  UPDATE y SET a = CASE WHEN b THEN 1 END;\n  IF a THEN SELECT 1; END IF;\nEND;\n",
+        "COMMIT;\n",
+    ]);
+}
+
+#[test]
+fn reader_does_not_split_sqlfmt_off_regions() {
+    let sql = "-- sqlfmt: off\nselect 1; select 2;\n-- sqlfmt: on\nselect 3;";
+    assert_eq!(statements(sql, Dialect::Generic), [
+        "-- sqlfmt: off\nselect 1; select 2;\n-- sqlfmt: on\nselect 3;",
+    ]);
+}
+
+#[test]
+fn reader_resumes_in_strings_and_comments_left_open_across_lines() {
+    let sql = "select 'a;\nb'; select $f$\n x;\n$f$ /* ;\n */;\nselect [c;\nd];\nselect 1";
+    assert_eq!(statements(sql, Dialect::Generic), [
+        "select 'a;\nb'; ",
+        "select $f$\n x;\n$f$ /* ;\n */;\n",
+        "select [c;\nd];\n",
+        "select 1",
+    ]);
+
+    // A long body is scanned once, not again for every line read.
+    let body: String = (0..20_000).map(|n| format!("  perform f({n});\n")).collect();
+    let sql = format!("create function g() returns void as $$\nbegin\n{body}end;\n$$ language plpgsql;\nselect 2;\n");
+    let statements = statements(&sql, Dialect::PostgreSql);
+    assert_eq!(statements.len(), 2);
+    assert_eq!(statements[1], "select 2;\n");
+}
+
+#[test]
+fn reader_passes_copy_data_through() {
+    let sql = "COPY t (a, b) FROM stdin;\n1\tit's; here\n\\.\nselect 1;\n";
+    assert_eq!(read(sql, Dialect::PostgreSql), [
+        Chunk::Statement("COPY t (a, b) FROM stdin;\n".to_string()),
+        Chunk::Data("1\tit's; here\n".to_string()),
+        Chunk::Data("\\.\n".to_string()),
+        Chunk::Statement("select 1;\n".to_string()),
+    ]);
+}
+
+#[test]
+fn split_statements_gives_text_and_spans() {
+    let sql = "-- setup\nselect 'a;b';;\n  /* x */ insert into t values (1) ; -- done\n\nselect 2";
+    let statements = split_statements(sql);
+    let texts: Vec<&str> = statements.iter().map(|s| s.text.as_str()).collect();
+    assert_eq!(texts, ["select 'a;b';", "insert into t values (1) ;", "select 2"]);
+    assert_eq!(statements[1].span, Span { start: 34, end: 60, line: 3, column: 11 });
+    assert_eq!(statements[2].span, Span { start: 70, end: 78, line: 5, column: 1 });
+    for statement in &statements {
+        assert_eq!(&sql[statement.span.start..statement.span.end], statement.text);
+    }
+}
+
+#[test]
+fn split_statements_keeps_procedural_bodies_together() {
+    let sql = "create trigger t after insert on a begin\n  update b set n = n + 1;\n  delete from c;\nend;\n\
+               begin transaction; commit;";
+    let statements = split_statements_with(sql, Dialect::Sqlite);
+    assert_eq!(statements.len(), 3);
+    assert!(statements[0].text.ends_with("end;"));
+}
+
+#[test]
+fn split_statements_classifies_by_leading_words() {
+    let sql = "create table t (id int); alter table t add x int; drop view v; truncate t;\n\
+               select 1; (select 2); with c as (select 1) select * from c; insert into t values (1);\n\
+               update t set x = 1; delete from t; merge into t using s on true when matched then delete;\n\
+               begin; begin transaction; start transaction; set transaction read only; commit; rollback;\n\
+               savepoint s; grant select on t to u; revoke select on t from u;\n\
+               set search_path = x; explain select 1; begin select 1; end;";
+    let kinds: Vec<StatementKind> = split_statements(sql).into_iter().map(|s| s.kind).collect();
+    use StatementKind::*;
+    assert_eq!(kinds, [
+        Ddl, Ddl, Ddl, Ddl, Dml, Dml, Dml, Dml, Dml, Dml, Dml, Tcl, Tcl, Tcl, Tcl, Tcl, Tcl, Tcl, Dcl, Dcl, Other,
+        Other, Other,
+    ]);
+}
+
+#[test]
+fn statement_range_widens_to_whole_statements() {
+    let sql = "select 1; select 2;\n-- sqlfmt: off\nselect   3;\n-- sqlfmt: on\nselect 4;";
+    let range = |start, end| statement_range(sql, start..end, Dialect::Generic).map(|r| &sql[r]);
+    assert_eq!(range(2, 2), Some("select 1;"));
+    assert_eq!(range(8, 12), Some("select 1; select 2;"));
+    assert_eq!(range(9, 10), None);
+    // Starting in a `sqlfmt: off` region takes in its directive.
+    let off = sql.find("select   3").unwrap();
+    assert_eq!(range(off, off), Some("-- sqlfmt: off\nselect   3;\n-- sqlfmt: on\nselect 4;"));
+}
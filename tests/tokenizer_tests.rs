@@ -1,4 +1,7 @@
-use sqlfmt::tokenizer::{tokenize, Token};
+use sqlfmt::dialect::Dialect;
+use sqlfmt::tokenizer::{
+    token_errors, tokenize, tokenize_with, tokenize_with_spans, tokens, tokens_with, Span, Token, TokenRef,
+};
 
 #[test]
 fn tokenize_select_star() {
@@ -103,3 +106,222 @@ fn tokenize_case_insensitive_keywords() {
         &Token::Keyword("where".into()),
     ]);
 }
+
+#[test]
+fn tokenize_unterminated_block_comment() {
+    let tokens = tokenize("SELECT /* open");
+    assert_eq!(tokens.last(), Some(&Token::Comment("/* open".into())));
+}
+
+#[test]
+fn tokenize_with_spans_tracks_offsets_and_positions() {
+    let input = "SELECT id,\n  'é' FROM t";
+    let spanned = tokenize_with_spans(input);
+    assert_eq!(spanned.iter().map(|t| t.token.clone()).collect::<Vec<_>>(), tokenize(input));
+
+    let string = spanned.iter().find(|t| matches!(t.token, Token::StringLiteral(_))).unwrap();
+    assert_eq!(string.span, Span { start: 13, end: 17, line: 2, column: 3 });
+    assert_eq!(&input[string.span.start..string.span.end], "'é'");
+
+    let from = spanned.iter().find(|t| t.token == Token::Keyword("FROM".into())).unwrap();
+    assert_eq!((from.span.line, from.span.column), (2, 7));
+}
+
+#[test]
+fn tokenize_with_spans_covers_input() {
+    let input = "-- c\nSELECT \"a b\", `x` FROM [t] /* unterminated";
+    let spanned = tokenize_with_spans(input);
+    let mut expected_start = 0;
+    for t in &spanned {
+        assert_eq!(t.span.start, expected_start);
+        assert_eq!(&input[t.span.start..t.span.end], t.token.text());
+        expected_start = t.span.end;
+    }
+    assert_eq!(expected_start, input.len());
+}
+
+fn significant(tokens: Vec<Token>) -> Vec<Token> {
+    tokens.into_iter().filter(|t| !matches!(t, Token::Whitespace(_))).collect()
+}
+
+#[test]
+fn tokenize_postgres_array_subscript_is_not_identifier() {
+    let tokens = significant(tokenize_with("arr[1]", Dialect::PostgreSql));
+    assert_eq!(tokens, vec![
+        Token::Identifier("arr".into()),
+        Token::Other("[".into()),
+        Token::NumberLiteral("1".into()),
+        Token::Other("]".into()),
+    ]);
+}
+
+#[test]
+fn tokenize_postgres_operators() {
+    let tokens = significant(tokenize_with("a::int, b->>'k', c @> d", Dialect::PostgreSql));
+    let ops: Vec<_> = tokens.iter().filter_map(|t| match t {
+        Token::Operator(op) => Some(op.as_str()),
+        _ => None,
+    }).collect();
+    assert_eq!(ops, vec!["::", "->>", "@>"]);
+}
+
+#[test]
+fn tokenize_mysql_quoting_and_hash_comments() {
+    let tokens = significant(tokenize_with("SELECT \"a\", 'it\\'s' # note\nFROM `t`", Dialect::MySql));
+    assert_eq!(tokens, vec![
+        Token::Keyword("SELECT".into()),
+        Token::StringLiteral("\"a\"".into()),
+        Token::Comma,
+        Token::StringLiteral("'it\\'s'".into()),
+        Token::Comment("# note".into()),
+        Token::Keyword("FROM".into()),
+        Token::Identifier("`t`".into()),
+    ]);
+}
+
+#[test]
+fn tokenize_dialect_keywords() {
+    assert_eq!(tokenize_with("TOP", Dialect::TSql), vec![Token::Keyword("TOP".into())]);
+    assert_eq!(tokenize_with("TOP", Dialect::PostgreSql), vec![Token::Identifier("TOP".into())]);
+    assert_eq!(tokenize_with("qualify", Dialect::Snowflake), vec![Token::Keyword("qualify".into())]);
+}
+
+#[test]
+fn tokenize_ansi_rejects_backticks_and_brackets() {
+    let tokens = tokenize_with("[a]", Dialect::Ansi);
+    assert_eq!(tokens[0], Token::Other("[".into()));
+    assert_eq!(tokenize_with("[a]", Dialect::TSql), vec![Token::Identifier("[a]".into())]);
+}
+
+#[test]
+fn dialect_from_name() {
+    assert_eq!(Dialect::from_name("postgres"), Some(Dialect::PostgreSql));
+    assert_eq!(Dialect::from_name("MySQL"), Some(Dialect::MySql));
+    assert_eq!(Dialect::from_name("oracle"), None);
+    for name in Dialect::NAMES {
+        assert!(Dialect::from_name(name).is_some());
+    }
+}
+
+#[test]
+fn tokenize_dollar_quoted_strings() {
+    let tokens = significant(tokenize("SELECT $$it's; a body$$, $fn$ $$ nested $$ $fn$"));
+    assert_eq!(tokens, vec![
+        Token::Keyword("SELECT".into()),
+        Token::StringLiteral("$$it's; a body$$".into()),
+        Token::Comma,
+        Token::StringLiteral("$fn$ $$ nested $$ $fn$".into()),
+    ]);
+}
+
+#[test]
+fn tokenize_unterminated_dollar_quote_runs_to_end() {
+    let tokens = tokenize_with("AS $body$ SELECT 1;", Dialect::PostgreSql);
+    assert_eq!(tokens.last(), Some(&Token::StringLiteral("$body$ SELECT 1;".into())));
+}
+
+#[test]
+fn tokenize_dollar_quotes_only_in_supporting_dialects() {
+    let tokens = tokenize_with("$$x$$", Dialect::MySql);
+    assert_eq!(tokens[0], Token::Other("$".into()));
+}
+
+#[test]
+fn tokenize_placeholders() {
+    let tokens = significant(tokenize("$1 ? ?2 :name @param @@rowcount %(user_id)s %s"));
+    assert_eq!(tokens, vec![
+        Token::Placeholder("$1".into()),
+        Token::Placeholder("?".into()),
+        Token::Placeholder("?2".into()),
+        Token::Placeholder(":name".into()),
+        Token::Placeholder("@param".into()),
+        Token::Placeholder("@@rowcount".into()),
+        Token::Placeholder("%(user_id)s".into()),
+        Token::Placeholder("%s".into()),
+    ]);
+    // `%` before a longer word is modulo.
+    let tokens = significant(tokenize("a %sum"));
+    assert_eq!(tokens[1], Token::Operator("%".into()));
+}
+
+#[test]
+fn tokenize_jinja_templates() {
+    let tokens = significant(tokenize("{{ ref('a }}') }} stg_{{ var('x') }}_v {% if x %}{# c #}"));
+    assert_eq!(tokens, vec![
+        Token::Template("{{ ref('a }}') }}".into()),
+        Token::Template("stg_{{ var('x') }}_v".into()),
+        Token::Template("{% if x %}".into()),
+        Token::Template("{# c #}".into()),
+    ]);
+    // A single brace is not a tag.
+    assert_eq!(significant(tokenize("{d '2024-01-01'}"))[0], Token::Other("{".into()));
+}
+
+#[test]
+fn tokenize_cast_is_not_placeholder() {
+    let tokens = significant(tokenize("a::int"));
+    assert_eq!(tokens, vec![
+        Token::Identifier("a".into()),
+        Token::Operator("::".into()),
+        Token::Identifier("int".into()),
+    ]);
+}
+
+#[test]
+fn tokenize_postgres_question_operators_win_over_placeholders() {
+    let tokens = significant(tokenize_with("data ?| keys", Dialect::PostgreSql));
+    assert_eq!(tokens[1], Token::Operator("?|".into()));
+    // A bare `?` is the key-exists operator, not a bind parameter.
+    let tokens = significant(tokenize_with("data ? 'k'", Dialect::PostgreSql));
+    assert_eq!(tokens[1], Token::Operator("?".into()));
+}
+
+#[test]
+fn tokens_borrow_from_the_input() {
+    let sql = "select naïve, 'x' from t; -- done";
+    let tokens: Vec<TokenRef> = tokens(sql).collect();
+    assert_eq!(tokens[..4], [
+        TokenRef::Keyword("select"),
+        TokenRef::Whitespace(" "),
+        TokenRef::Identifier("naïve"),
+        TokenRef::Comma,
+    ]);
+    let word = tokens[2].text();
+    assert!(sql.as_bytes().as_ptr_range().contains(&word.as_ptr()));
+    assert_eq!(tokens.iter().map(TokenRef::text).collect::<String>(), sql);
+}
+
+#[test]
+fn tokens_match_keywords_in_any_case() {
+    let words: Vec<TokenRef> = tokens_with("SeLeCt ſelect selects row_number qualify", Dialect::BigQuery)
+        .filter(|t| !t.is_trivia())
+        .collect();
+    assert_eq!(words, [
+        TokenRef::Keyword("SeLeCt"),
+        TokenRef::Keyword("ſelect"),
+        TokenRef::Identifier("selects"),
+        TokenRef::Keyword("row_number"),
+        TokenRef::Keyword("qualify"),
+    ]);
+}
+
+#[test]
+fn token_errors_point_at_unterminated_openers() {
+    let errors = |sql: &str, dialect: Dialect| -> Vec<(String, Span)> {
+        token_errors(sql, dialect).into_iter().map(|e| (e.message, e.span)).collect()
+    };
+    assert_eq!(errors("select 'a''b', \"c\", [d], $$x$$ /* ok */", Dialect::Generic), []);
+    assert_eq!(errors("select 'it''", Dialect::Generic), [(
+        "unterminated string literal".to_string(),
+        Span { start: 7, end: 8, line: 1, column: 8 }
+    )]);
+    assert_eq!(errors("select 1\n/* open", Dialect::Generic)[0].1, Span { start: 9, end: 11, line: 2, column: 1 });
+    assert_eq!(errors("select $body$ x", Dialect::PostgreSql)[0].0, "unterminated dollar-quoted string");
+    assert_eq!(errors("select `a", Dialect::MySql)[0].0, "unterminated quoted identifier");
+    assert_eq!(errors("select [a", Dialect::TSql)[0].0, "unterminated quoted identifier");
+    assert_eq!(errors("select 'a\\'", Dialect::MySql)[0].0, "unterminated string literal");
+    assert_eq!(errors("select {{ x }}, a_{{ y", Dialect::Generic), [(
+        "unterminated template tag".to_string(),
+        Span { start: 16, end: 18, line: 1, column: 17 }
+    )]);
+}
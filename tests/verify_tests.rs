@@ -0,0 +1,41 @@
+use sqlfmt::dialect::Dialect;
+use sqlfmt::formatter::{FormatOptions, KeywordCase};
+use sqlfmt::verify::{verify_format, verify_minify, VerifyError};
+
+#[test]
+fn verify_returns_the_formatted_output() {
+    let sql = "select id, -- key\n name from users where id = 1;";
+    let formatted = verify_format(sql, Dialect::Generic, &FormatOptions::default()).unwrap();
+    assert_eq!(formatted, "SELECT\n  id, -- key\n  name\nFROM\n  users\nWHERE\n  id = 1;\n");
+    assert_eq!(verify_minify(sql, Dialect::Generic).unwrap(), "SELECT id,name FROM users WHERE id=1;");
+}
+
+#[test]
+fn verify_ignores_keyword_case_and_reformatted_bodies() {
+    let opts = FormatOptions {
+        keyword_case: KeywordCase::Lower,
+        format_sql_bodies: true,
+        ..FormatOptions::default()
+    };
+    let sql = "CREATE FUNCTION one() RETURNS int LANGUAGE sql AS $$ SELECT   1 $$;";
+    assert!(verify_format(sql, Dialect::PostgreSql, &opts).is_ok());
+}
+
+#[test]
+fn verify_minify_keeps_signs_apart() {
+    // `- -1` must not become the comment `--1`.
+    assert_eq!(verify_minify("select a - -1, b / *c", Dialect::Generic).unwrap(), "SELECT a- -1,b/ *c");
+}
+
+#[test]
+fn verify_error_messages() {
+    let err = VerifyError::TokensChanged { line: 3, expected: "-".to_string(), found: "--1".to_string() };
+    assert_eq!(
+        err.to_string(),
+        "formatting would change the query at output line 3: expected '-', found '--1'"
+    );
+    assert_eq!(
+        VerifyError::NotIdempotent { line: 2 }.to_string(),
+        "formatting is not stable: a second pass changes output line 2"
+    );
+}